@@ -0,0 +1,74 @@
+//! Conversion of screen tiles to text with ANSI escape sequences (written by crossterm),
+//! to display the screen grid in a terminal (that supports 24-bit colors).
+
+use crossterm::cursor::MoveTo;
+use crossterm::style::{Colors, SetColors};
+use crossterm::Command;
+use sdl2::pixels::Color;
+
+use crate::cp437::CP437_TO_UNICODE;
+use crate::{ScreenTile, Sprite};
+
+fn sprite_char(sprite: Sprite) -> char {
+	match sprite {
+		Sprite::Index(index) => CP437_TO_UNICODE.get(index as usize).copied().unwrap_or('?'),
+		Sprite::Char(character) => character,
+	}
+}
+
+/// The character that looks like the sprite of the tile, and its color.
+/// There is only one character per tile in a terminal, so a background pattern
+/// is shown only if the sprite is blank.
+pub fn tile_char(tile: &ScreenTile) -> (char, Color) {
+	let mut character = sprite_char(tile.sprite);
+	let mut color = tile.fg_color;
+	if let Some((bg_pattern, pattern_color)) = tile.bg_pattern {
+		if character == ' ' || character == '\u{a0}' {
+			character = sprite_char(Sprite::Index(bg_pattern.sprite_index()));
+			color = pattern_color;
+		}
+	}
+	// Control characters would mess with the terminal.
+	if character.is_control() {
+		(' ', color)
+	} else {
+		(character, color)
+	}
+}
+
+/// Writes the escape sequence of the crossterm command.
+pub fn write_command(text: &mut String, command: impl Command) {
+	command.write_ansi(text).unwrap();
+}
+
+fn terminal_color(color: Color) -> crossterm::style::Color {
+	crossterm::style::Color::Rgb { r: color.r, g: color.g, b: color.b }
+}
+
+/// Moves the cursor to the given grid coordinates (starting at zero).
+pub fn move_cursor(text: &mut String, xy: (u32, u32)) {
+	let x = xy.0.min(u16::MAX as u32) as u16;
+	let y = xy.1.min(u16::MAX as u32) as u16;
+	write_command(text, MoveTo(x, y));
+}
+
+/// Writes a tile at the cursor position, where `current_colors` is the colors that the
+/// terminal currently uses (if known), so that the color escape sequences are only written
+/// when the colors change.
+pub fn write_tile(
+	text: &mut String,
+	tile: &ScreenTile,
+	current_colors: &mut Option<(Color, Color)>,
+) {
+	let (character, fg_color) = tile_char(tile);
+	let colors = (fg_color, tile.bg_color);
+	if *current_colors != Some(colors) {
+		let (fg, bg) = colors;
+		write_command(
+			text,
+			SetColors(Colors::new(terminal_color(fg), terminal_color(bg))),
+		);
+		*current_colors = Some(colors);
+	}
+	text.push(character);
+}
@@ -0,0 +1,139 @@
+//! Access to the assets (tilesets and data files) by their path in the `assets` directory,
+//! like `"lang/en.toml"`, wherever they actually come from.
+//!
+//! An asset is looked for, in order:
+//! - in the user override directory (see `set_override_dir`), to mod or fix assets
+//!   without touching the game files,
+//! - in the `assets` directory next to the executable,
+//! - in the `assets` directory of the working directory (as when using `cargo run`),
+//! - in the copies embedded in the executable, so that it works on its own.
+//!
+//! Assets are read only once, and then kept.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+/// Copies of the assets that are embedded in the executable.
+const EMBEDDED_ASSETS: [(&str, &[u8]); 6] = [
+	(
+		"Pastiche_8x8.png",
+		include_bytes!("../assets/Pastiche_8x8.png"),
+	),
+	(
+		"data/keybindings.toml",
+		include_bytes!("../assets/data/keybindings.toml"),
+	),
+	(
+		"data/names.toml",
+		include_bytes!("../assets/data/names.toml"),
+	),
+	(
+		"data/themes.toml",
+		include_bytes!("../assets/data/themes.toml"),
+	),
+	("lang/en.toml", include_bytes!("../assets/lang/en.toml")),
+	("lang/fr.toml", include_bytes!("../assets/lang/fr.toml")),
+];
+
+static OVERRIDE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+static CACHE: RwLock<Option<HashMap<String, Arc<[u8]>>>> = RwLock::new(None);
+
+/// Sets a directory where assets are looked for before anywhere else.
+pub fn set_override_dir(dir: impl Into<PathBuf>) {
+	*OVERRIDE_DIR.write().unwrap() = Some(dir.into());
+}
+
+/// The directories that may have the asset files, in the order they are looked in.
+fn asset_dirs() -> Vec<PathBuf> {
+	let mut dirs = Vec::new();
+	if let Some(override_dir) = OVERRIDE_DIR.read().unwrap().as_ref() {
+		dirs.push(override_dir.clone());
+	}
+	let executable_dir = std::env::current_exe()
+		.ok()
+		.and_then(|executable| Some(executable.parent()?.join("assets")));
+	if let Some(executable_dir) = executable_dir {
+		dirs.push(executable_dir);
+	}
+	dirs.push(PathBuf::from("assets"));
+	dirs
+}
+
+/// The file that the asset is read from, if it is not only embedded in the executable.
+/// The file may have changed since the asset was read (see `read`).
+pub fn filepath(asset_path: &str) -> Option<PathBuf> {
+	asset_dirs()
+		.into_iter()
+		.map(|dir| dir.join(asset_path))
+		.find(|filepath| filepath.is_file())
+}
+
+/// The content of the asset, like `read("lang/en.toml")`.
+pub fn read(asset_path: &str) -> Result<Arc<[u8]>, String> {
+	if let Some(bytes) = CACHE
+		.read()
+		.unwrap()
+		.as_ref()
+		.and_then(|cache| cache.get(asset_path))
+	{
+		return Ok(Arc::clone(bytes));
+	}
+
+	let dirs = asset_dirs();
+	let from_file = dirs
+		.iter()
+		.find_map(|dir| std::fs::read(dir.join(asset_path)).ok());
+	let embedded = || {
+		EMBEDDED_ASSETS
+			.iter()
+			.find(|(embedded_path, _)| *embedded_path == asset_path)
+			.map(|(_, bytes)| bytes.to_vec())
+	};
+	let Some(bytes) = from_file.or_else(embedded) else {
+		let searched_dirs: Vec<String> = dirs
+			.iter()
+			.map(|dir| format!("\"{}\"", dir.display()))
+			.collect();
+		return Err(format!(
+			"asset \"{asset_path}\" not found in {} nor embedded in the executable",
+			searched_dirs.join(", ")
+		));
+	};
+
+	let bytes: Arc<[u8]> = bytes.into();
+	let mut cache = CACHE.write().unwrap();
+	let cache = cache.get_or_insert_with(HashMap::new);
+	cache.insert(asset_path.to_string(), Arc::clone(&bytes));
+	Ok(bytes)
+}
+
+/// Writes the asset in a file, in the override directory if there is one
+/// (see `set_override_dir`), or else over the file it is read from (or in the `assets`
+/// directory of the working directory if it is only embedded). Returns the file path.
+pub fn write(asset_path: &str, bytes: &[u8]) -> Result<PathBuf, String> {
+	let override_dir = OVERRIDE_DIR.read().unwrap().clone();
+	let filepath = match override_dir {
+		Some(override_dir) => override_dir.join(asset_path),
+		None => filepath(asset_path).unwrap_or_else(|| PathBuf::from("assets").join(asset_path)),
+	};
+	let write_file = || {
+		if let Some(dir) = filepath.parent() {
+			std::fs::create_dir_all(dir)?;
+		}
+		std::fs::write(&filepath, bytes)
+	};
+	write_file().map_err(|error| format!("could not write \"{}\": {error}", filepath.display()))?;
+	let mut cache = CACHE.write().unwrap();
+	let cache = cache.get_or_insert_with(HashMap::new);
+	cache.insert(asset_path.to_string(), bytes.into());
+	Ok(filepath)
+}
+
+/// The content of the asset as text (see `read`).
+pub fn read_to_string(asset_path: &str) -> Result<String, String> {
+	let bytes = read(asset_path)?;
+	String::from_utf8(bytes.to_vec())
+		.map_err(|error| format!("asset \"{asset_path}\" is not UTF-8 text: {error}"))
+}
@@ -0,0 +1,163 @@
+//! Color math: HSV conversions, interpolation and gradient ramps.
+
+use sdl2::pixels::Color;
+
+/// Hue (in degrees, from 0 to 360), saturation and value (both from 0 to 1).
+#[derive(Clone, Copy)]
+pub struct Hsv {
+	pub hue: f32,
+	pub saturation: f32,
+	pub value: f32,
+}
+
+pub fn rgb_to_hsv(color: Color) -> Hsv {
+	let (r, g, b) = (
+		color.r as f32 / 255.0,
+		color.g as f32 / 255.0,
+		color.b as f32 / 255.0,
+	);
+	let max = r.max(g).max(b);
+	let min = r.min(g).min(b);
+	let delta = max - min;
+	let hue = if delta == 0.0 {
+		0.0
+	} else if max == r {
+		60.0 * ((g - b) / delta).rem_euclid(6.0)
+	} else if max == g {
+		60.0 * ((b - r) / delta + 2.0)
+	} else {
+		60.0 * ((r - g) / delta + 4.0)
+	};
+	let saturation = if max == 0.0 { 0.0 } else { delta / max };
+	Hsv { hue, saturation, value: max }
+}
+
+/// The alpha is opaque.
+pub fn hsv_to_rgb(hsv: Hsv) -> Color {
+	let hue = hsv.hue.rem_euclid(360.0);
+	let chroma = hsv.value * hsv.saturation;
+	let x = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+	let (r, g, b) = match (hue / 60.0) as u32 {
+		0 => (chroma, x, 0.0),
+		1 => (x, chroma, 0.0),
+		2 => (0.0, chroma, x),
+		3 => (0.0, x, chroma),
+		4 => (x, 0.0, chroma),
+		_ => (chroma, 0.0, x),
+	};
+	let m = hsv.value - chroma;
+	let channel = |value: f32| ((value + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+	Color::RGB(channel(r), channel(g), channel(b))
+}
+
+/// The color that is `t` of the way from `a` to `b` (with `t` clamped between 0 and 1).
+pub fn lerp(a: Color, b: Color, t: f32) -> Color {
+	let t = t.clamp(0.0, 1.0);
+	let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+	Color::RGBA(
+		channel(a.r, b.r),
+		channel(a.g, b.g),
+		channel(a.b, b.b),
+		channel(a.a, b.a),
+	)
+}
+
+/// Turns the hue of the color by the given angle (in degrees).
+pub fn rotate_hue(color: Color, angle: f32) -> Color {
+	let mut hsv = rgb_to_hsv(color);
+	hsv.hue += angle;
+	let mut rotated = hsv_to_rgb(hsv);
+	rotated.a = color.a;
+	rotated
+}
+
+/// Gradient going through colors at given positions, to pick colors for values
+/// (like red for low health to green for full health).
+pub struct Ramp {
+	/// Positions and their colors, sorted by position.
+	stops: Vec<(f32, Color)>,
+}
+
+impl Ramp {
+	pub fn new(mut stops: Vec<(f32, Color)>) -> Ramp {
+		assert!(!stops.is_empty(), "a ramp needs at least one color");
+		stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+		Ramp { stops }
+	}
+
+	/// The color at the given position, the colors of the first and last stops being
+	/// used before and after them.
+	pub fn sample(&self, position: f32) -> Color {
+		let after_index = self.stops.iter().position(|&(stop, _)| stop > position);
+		match after_index {
+			None => self.stops.last().unwrap().1,
+			Some(0) => self.stops[0].1,
+			Some(index) => {
+				let (before, before_color) = self.stops[index - 1];
+				let (after, after_color) = self.stops[index];
+				lerp(
+					before_color,
+					after_color,
+					(position - before) / (after - before),
+				)
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hsv_of_primary_colors() {
+		let hsv = rgb_to_hsv(Color::RGB(0, 255, 0));
+		assert_eq!((hsv.hue, hsv.saturation, hsv.value), (120.0, 1.0, 1.0));
+		let hsv = rgb_to_hsv(Color::RGB(128, 128, 128));
+		assert_eq!(hsv.saturation, 0.0);
+	}
+
+	#[test]
+	fn hsv_goes_back_to_the_same_rgb() {
+		for color in [
+			Color::RGB(0, 0, 0),
+			Color::RGB(255, 255, 255),
+			Color::RGB(255, 0, 0),
+			Color::RGB(12, 200, 99),
+			Color::RGB(250, 10, 180),
+			Color::RGB(90, 60, 30),
+		] {
+			assert_eq!(hsv_to_rgb(rgb_to_hsv(color)), color);
+		}
+	}
+
+	#[test]
+	fn lerp_is_clamped() {
+		let black = Color::RGB(0, 0, 0);
+		let white = Color::RGB(255, 255, 255);
+		assert_eq!(lerp(black, white, 0.5), Color::RGB(128, 128, 128));
+		assert_eq!(lerp(black, white, -1.0), black);
+		assert_eq!(lerp(black, white, 2.0), white);
+	}
+
+	#[test]
+	fn rotating_the_hue_keeps_the_alpha() {
+		let rotated = rotate_hue(Color::RGBA(255, 0, 0, 40), 120.0);
+		assert_eq!(rotated, Color::RGBA(0, 255, 0, 40));
+		assert_eq!(
+			rotate_hue(Color::RGB(255, 0, 0), -120.0),
+			Color::RGB(0, 0, 255)
+		);
+	}
+
+	#[test]
+	fn ramps_interpolate_between_their_stops() {
+		let ramp = Ramp::new(vec![
+			(1.0, Color::RGB(0, 200, 0)),
+			(0.0, Color::RGB(200, 0, 0)),
+		]);
+		assert_eq!(ramp.sample(-5.0), Color::RGB(200, 0, 0));
+		assert_eq!(ramp.sample(0.5), Color::RGB(100, 100, 0));
+		assert_eq!(ramp.sample(5.0), Color::RGB(0, 200, 0));
+	}
+}
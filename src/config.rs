@@ -0,0 +1,221 @@
+//! Settings of the engine read from a configuration file (`why-crystals.toml` in the
+//! working directory by default), with defaults for what it does not set, like this:
+//!
+//! ```text
+//! title = "My cave"
+//! window_size = [1200, 600]
+//! tileset = "Pastiche_8x8.png"
+//! tileset_tile_size = [8, 8]
+//! alternate_tilesets = ["Curses_square_16x16.png"]
+//! watch_tileset = true
+//! tileset_transparency = "magenta"
+//! tile_size = [16, 16]
+//! grid_size = [80, 40]
+//! theme = "amber"
+//! ```
+//!
+//! The tileset is an asset path (see `assets`), the tile size is the size in pixels
+//! of the tiles in the window, and without a grid size the grid fills the window.
+//! The alternate tilesets (that F7 switches to) are asset paths too, of images of 16x16
+//! sprites, and watching the tileset reloads it when its file changes.
+//! The tileset transparency is which pixels of the tilesets are transparent, among
+//! `"magenta_and_black"` (the default), `"magenta"`, `"black"`, `"top_left_pixel"`
+//! (the color of the top-left pixel) and `"alpha"` (the alpha channel of the images).
+
+use std::path::Path;
+
+use crate::render::TransparencyPolicy;
+
+pub const DEFAULT_CONFIG_PATH: &str = "why-crystals.toml";
+
+pub struct Config {
+	/// `None` for the title of the current language.
+	pub title: Option<String>,
+	pub window_wh: Option<(u32, u32)>,
+	pub tileset: String,
+	pub tileset_tile_wh: (u32, u32),
+	pub alternate_tilesets: Vec<String>,
+	pub watch_tileset: bool,
+	pub tileset_transparency: TransparencyPolicy,
+	pub tile_wh: (u32, u32),
+	pub grid_wh: Option<(u32, u32)>,
+	/// `None` for the default theme (see `theme::load_themes`).
+	pub theme: Option<String>,
+}
+
+impl Default for Config {
+	fn default() -> Config {
+		Config {
+			title: None,
+			window_wh: None,
+			// You can get more of these from
+			// [the Dwarf Fortress wiki tileset repo](https://dwarffortresswiki.org/Tileset_repository).
+			tileset: "Pastiche_8x8.png".to_string(),
+			tileset_tile_wh: (8, 8),
+			alternate_tilesets: Vec::new(),
+			watch_tileset: false,
+			tileset_transparency: TransparencyPolicy::default(),
+			tile_wh: (16, 16),
+			grid_wh: None,
+			theme: None,
+		}
+	}
+}
+
+impl Config {
+	/// The default configuration if there is no file there.
+	pub fn load(path: &Path) -> Result<Config, String> {
+		let text = match std::fs::read_to_string(path) {
+			Ok(text) => text,
+			Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+				return Ok(Config::default())
+			},
+			Err(error) => return Err(error.to_string()),
+		};
+		let table = text
+			.parse::<toml::Table>()
+			.map_err(|error| error.to_string())?;
+		let mut config = Config::default();
+		for (key, value) in table.iter() {
+			let string = || {
+				value
+					.as_str()
+					.map(str::to_string)
+					.ok_or_else(|| format!("\"{key}\" should be a string"))
+			};
+			let string_list = || {
+				let strings = value.as_array().and_then(|array| {
+					array
+						.iter()
+						.map(|value| value.as_str().map(str::to_string))
+						.collect::<Option<Vec<String>>>()
+				});
+				strings.ok_or_else(|| format!("\"{key}\" should be a list of strings"))
+			};
+			let boolean = || {
+				value
+					.as_bool()
+					.ok_or_else(|| format!("\"{key}\" should be true or false"))
+			};
+			let size = || {
+				let size = value
+					.as_array()
+					.filter(|array| array.len() == 2)
+					.and_then(|array| {
+						let dimension = |value: &toml::Value| {
+							u32::try_from(value.as_integer()?)
+								.ok()
+								.filter(|&dimension| dimension > 0)
+						};
+						Some((dimension(&array[0])?, dimension(&array[1])?))
+					});
+				size.ok_or_else(|| format!("\"{key}\" should be a size (like [16, 16])"))
+			};
+			match key.as_str() {
+				"title" => config.title = Some(string()?),
+				"window_size" => config.window_wh = Some(size()?),
+				"tileset" => config.tileset = string()?,
+				"tileset_tile_size" => config.tileset_tile_wh = size()?,
+				"alternate_tilesets" => config.alternate_tilesets = string_list()?,
+				"watch_tileset" => config.watch_tileset = boolean()?,
+				"tileset_transparency" => {
+					let name = string()?;
+					config.tileset_transparency = TransparencyPolicy::from_name(&name)
+						.ok_or_else(|| format!("\"{name}\" is not a tileset transparency"))?;
+				},
+				"tile_size" => config.tile_wh = size()?,
+				"grid_size" => config.grid_wh = Some(size()?),
+				"theme" => config.theme = Some(string()?),
+				unknown => return Err(format!("unknown setting \"{unknown}\"")),
+			}
+		}
+		Ok(config)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A file in the temporary directory, removed when dropped.
+	struct TempFile(std::path::PathBuf);
+
+	impl TempFile {
+		fn with_text(name: &str, text: &str) -> TempFile {
+			let filename = format!("why-crystals-test-{}-{name}.toml", std::process::id());
+			let file = TempFile(std::env::temp_dir().join(filename));
+			std::fs::write(&file.0, text).unwrap();
+			file
+		}
+	}
+
+	impl Drop for TempFile {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_file(&self.0);
+		}
+	}
+
+	#[test]
+	fn no_file_gives_the_defaults() {
+		let path = std::env::temp_dir().join("why-crystals-test-there-is-no-such-config.toml");
+		let config = Config::load(&path).unwrap();
+		let default = Config::default();
+		assert_eq!(config.title, default.title);
+		assert_eq!(config.window_wh, default.window_wh);
+		assert_eq!(config.tileset, default.tileset);
+		assert_eq!(config.tileset_tile_wh, default.tileset_tile_wh);
+		assert!(config.alternate_tilesets.is_empty());
+		assert!(!config.watch_tileset);
+		assert_eq!(
+			config.tileset_transparency,
+			TransparencyPolicy::MagentaAndBlack
+		);
+		assert_eq!(config.tile_wh, default.tile_wh);
+		assert_eq!(config.grid_wh, None);
+		assert_eq!(config.theme, None);
+	}
+
+	#[test]
+	fn settings_override_the_defaults() {
+		let file = TempFile::with_text(
+			"config-overrides",
+			"title = \"My cave\"\n\
+			tile_size = [8, 12]\n\
+			grid_size = [80, 40]\n\
+			alternate_tilesets = [\"a.png\", \"b.png\"]\n\
+			watch_tileset = true\n\
+			tileset_transparency = \"alpha\"\n",
+		);
+		let config = Config::load(&file.0).unwrap();
+		assert_eq!(config.title.as_deref(), Some("My cave"));
+		assert_eq!(config.tile_wh, (8, 12));
+		assert_eq!(config.grid_wh, Some((80, 40)));
+		assert_eq!(config.alternate_tilesets, ["a.png", "b.png"]);
+		assert!(config.watch_tileset);
+		assert_eq!(config.tileset_transparency, TransparencyPolicy::Alpha);
+		// What is not set keeps its default.
+		assert_eq!(config.tileset, Config::default().tileset);
+		assert_eq!(config.window_wh, None);
+	}
+
+	#[test]
+	fn invalid_settings_are_errors() {
+		for (name, text) in [
+			("zero-size", "tile_size = [0, 16]"),
+			("short-size", "tile_size = [16]"),
+			("negative-size", "grid_size = [-1, 16]"),
+			("number-title", "title = 3"),
+			("string-boolean", "watch_tileset = \"yes\""),
+			("mixed-list", "alternate_tilesets = [\"a.png\", 1]"),
+			("transparency", "tileset_transparency = \"pink\""),
+			("unknown", "tileset_colour = \"red\""),
+			("not-toml", "title = "),
+		] {
+			let file = TempFile::with_text(&format!("config-invalid-{name}"), text);
+			assert!(
+				Config::load(&file.0).is_err(),
+				"{text:?} should be rejected"
+			);
+		}
+	}
+}
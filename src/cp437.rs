@@ -0,0 +1,59 @@
+//! The [CP437](https://en.wikipedia.org/wiki/Code_page_437) code page, in which order the
+//! sprites of the main sprite sheet are expected to be.
+
+/// The Unicode characters that look like the CP437 glyphs, in the CP437 order.
+///
+/// The control characters have glyphs in CP437 (as on the IBM PC screen), and 0 and 255
+/// are blank.
+pub const CP437_TO_UNICODE: [char; 256] = [
+	' ', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼', '►', '◄', '↕',
+	'‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼', ' ', '!', '"', '#', '$', '%',
+	'&', '\'', '(', ')', '*', '+', ',', '-', '.', '/', '0', '1', '2', '3', '4', '5', '6', '7', '8',
+	'9', ':', ';', '<', '=', '>', '?', '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K',
+	'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^',
+	'_', '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q',
+	'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂', 'Ç', 'ü', 'é', 'â', 'ä',
+	'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', 'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù',
+	'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', 'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬',
+	'½', '¼', '¡', '«', '»', '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜',
+	'╛', '┐', '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', '╨',
+	'╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', 'α', 'ß', 'Γ', 'π',
+	'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', '≡', '±', '≥', '≤', '⌠', '⌡', '÷',
+	'≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// The CP437 index of the glyph that looks like the character, if there is one.
+///
+/// ASCII characters are their own index, even the control characters (that have glyphs
+/// in CP437), and the non-breaking space is the blank 255.
+pub fn unicode_to_cp437(character: char) -> Option<u8> {
+	if character.is_ascii() {
+		return Some(character as u8);
+	}
+	CP437_TO_UNICODE
+		.iter()
+		.skip(128)
+		.position(|&glyph| glyph == character)
+		.map(|index| index as u8 + 128)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn the_non_ascii_glyphs_map_back_to_their_index() {
+		for index in 128..=255u8 {
+			let glyph = CP437_TO_UNICODE[index as usize];
+			assert_eq!(unicode_to_cp437(glyph), Some(index), "{glyph:?}");
+		}
+	}
+
+	#[test]
+	fn ascii_stays_ascii() {
+		assert_eq!(unicode_to_cp437('A'), Some(b'A'));
+		assert_eq!(unicode_to_cp437('~'), Some(b'~'));
+		assert_eq!(unicode_to_cp437('☺'), None);
+		assert_eq!(unicode_to_cp437('€'), None);
+	}
+}
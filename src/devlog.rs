@@ -0,0 +1,104 @@
+//! Messages for the developers (warnings, errors, etc.), written to the standard error
+//! and also kept so that the latest ones can be shown on top of the grid (F12 toggles
+//! the log overlay, see `Engine::run`).
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+use sdl2::pixels::Color;
+
+/// How many of the latest lines are kept for the overlay.
+const KEPT_LINE_COUNT: usize = 64;
+
+#[derive(Clone, Copy)]
+pub enum Level {
+	Info,
+	Warning,
+	Error,
+}
+
+impl Level {
+	pub fn tag(self) -> &'static str {
+		match self {
+			Level::Info => "info",
+			Level::Warning => "warning",
+			Level::Error => "error",
+		}
+	}
+
+	pub fn color(self) -> Color {
+		match self {
+			Level::Info => Color::RGB(170, 170, 170),
+			Level::Warning => Color::RGB(240, 200, 40),
+			Level::Error => Color::RGB(240, 60, 40),
+		}
+	}
+}
+
+/// A kept line, and how many times it was logged (while it was kept).
+struct KeptLine {
+	level: Level,
+	text: String,
+	count: u32,
+}
+
+static LINES: Mutex<VecDeque<KeptLine>> = Mutex::new(VecDeque::new());
+
+/// A message of one line that is logged again while it is still kept is moved to the end
+/// with a counter (and is not written again to the standard error), so that the lines
+/// logged every frame do not push all the others out.
+pub fn log_line(level: Level, text: String) {
+	let mut lines = LINES.lock().unwrap();
+	let same_line_index = lines.iter().position(|line| line.text == text);
+	if let Some(same_line_index) = same_line_index {
+		let mut line = lines.remove(same_line_index).unwrap();
+		line.count += 1;
+		lines.push_back(line);
+		return;
+	}
+	eprintln!("{text}");
+	// A message of several lines takes several lines in the overlay.
+	for line in text.lines() {
+		if lines.len() >= KEPT_LINE_COUNT {
+			lines.pop_front();
+		}
+		lines.push_back(KeptLine { level, text: line.to_string(), count: 1 });
+	}
+}
+
+static REPORTED_PROBLEMS: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Whether the problem of the given description is reported for the first time, so that
+/// the problems that are noticed again and again (like every frame) are logged only once.
+pub fn is_first_report(problem: &str) -> bool {
+	let mut reported_problems = REPORTED_PROBLEMS.lock().unwrap();
+	reported_problems
+		.get_or_insert_with(HashSet::new)
+		.insert(problem.to_string())
+}
+
+/// The latest lines, oldest first, at most `count` of them.
+pub fn latest_lines(count: usize) -> Vec<(Level, String)> {
+	let lines = LINES.lock().unwrap();
+	lines
+		.iter()
+		.skip(lines.len().saturating_sub(count))
+		.map(|line| {
+			let text = if line.count > 1 {
+				format!("{} (x{})", line.text, line.count)
+			} else {
+				line.text.clone()
+			};
+			(line.level, text)
+		})
+		.collect()
+}
+
+/// Logs the formatted text, like `log!(Warning, "Could not do {thing}: {error}")`.
+#[macro_export]
+macro_rules! log {
+	($level:ident, $($format_args:tt)*) => {
+		$crate::devlog::log_line($crate::devlog::Level::$level, format!($($format_args)*))
+	};
+}
+pub use crate::log;
@@ -0,0 +1,292 @@
+//! Display settings that affect everything on screen, mostly adjustments made to the colors
+//! right when they are drawn in the window (like the colorblind modes and the brightness).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use sdl2::pixels::Color;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorblindMode {
+	None,
+	/// No red cones.
+	Protanopia,
+	/// No green cones.
+	Deuteranopia,
+	/// No blue cones.
+	Tritanopia,
+}
+
+impl ColorblindMode {
+	pub fn from_name(name: &str) -> Option<ColorblindMode> {
+		match name {
+			"none" => Some(ColorblindMode::None),
+			"protanopia" => Some(ColorblindMode::Protanopia),
+			"deuteranopia" => Some(ColorblindMode::Deuteranopia),
+			"tritanopia" => Some(ColorblindMode::Tritanopia),
+			_ => None,
+		}
+	}
+}
+
+/// What is applied to the colors, to get once before drawing a frame (see `filter`).
+#[derive(Clone, Copy)]
+pub struct DisplayFilter {
+	colorblind_mode: ColorblindMode,
+	/// What each channel value becomes, according to the brightness and gamma.
+	channel_table: [u8; 256],
+}
+
+const fn identity_channel_table() -> [u8; 256] {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		table[i] = i as u8;
+		i += 1;
+	}
+	table
+}
+
+static DISPLAY_FILTER: RwLock<DisplayFilter> = RwLock::new(DisplayFilter {
+	colorblind_mode: ColorblindMode::None,
+	channel_table: identity_channel_table(),
+});
+
+pub fn set_colorblind_mode(colorblind_mode: ColorblindMode) {
+	DISPLAY_FILTER.write().unwrap().colorblind_mode = colorblind_mode;
+}
+
+/// The brightness multiplies the channels (1 changes nothing) and a gamma above 1
+/// brightens the dark colors more than the bright ones (1 changes nothing either),
+/// which helps to see the dark colors of caves on displays that make them too dark.
+pub fn set_brightness_and_gamma(brightness: f32, gamma: f32) {
+	DISPLAY_FILTER.write().unwrap().channel_table = channel_table(brightness, gamma);
+}
+
+fn channel_table(brightness: f32, gamma: f32) -> [u8; 256] {
+	let mut channel_table = [0; 256];
+	for (value, new_value) in channel_table.iter_mut().enumerate() {
+		let linear = (value as f32 / 255.0).powf(1.0 / gamma) * brightness;
+		*new_value = (linear * 255.0).round().clamp(0.0, 255.0) as u8;
+	}
+	channel_table
+}
+
+/// The current filter.
+pub fn filter() -> DisplayFilter {
+	*DISPLAY_FILTER.read().unwrap()
+}
+
+static SMOOTH_MOVEMENT: AtomicBool = AtomicBool::new(true);
+
+/// Whether things that move slide between the tiles (see `Slide`) or just jump.
+pub fn smooth_movement() -> bool {
+	SMOOTH_MOVEMENT.load(Ordering::Relaxed)
+}
+
+pub fn set_smooth_movement(smooth_movement: bool) {
+	SMOOTH_MOVEMENT.store(smooth_movement, Ordering::Relaxed);
+}
+
+/// Darkening of the map tiles with their distance from the player, to make it feel like
+/// the only light comes from the torch (see `ScreenGrid::apply_fog`).
+#[derive(Clone, Copy)]
+pub struct Fog {
+	/// Distance (in tiles) from which the tiles are as dark as they get.
+	pub radius: f32,
+	/// The shape of the fall-off, 1 for a linear fall-off, more for a torch that lights
+	/// well around and then fades quickly, less for a light that fades right away.
+	pub falloff_exponent: f32,
+	/// The brightness of the tiles that are the furthest (0 for black).
+	pub min_brightness: f32,
+}
+
+impl Fog {
+	/// How bright a tile at the given distance should be, from 1 to `min_brightness`.
+	pub fn brightness(&self, distance: f32) -> f32 {
+		let darkness = (distance / self.radius)
+			.min(1.0)
+			.powf(self.falloff_exponent);
+		1.0 - darkness * (1.0 - self.min_brightness)
+	}
+}
+
+static FOG: RwLock<Option<Fog>> = RwLock::new(Some(Fog {
+	radius: 12.0,
+	falloff_exponent: 2.0,
+	min_brightness: 0.25,
+}));
+
+/// `None` turns the fog off.
+pub fn fog() -> Option<Fog> {
+	*FOG.read().unwrap()
+}
+
+pub fn set_fog(fog: Option<Fog>) {
+	*FOG.write().unwrap() = fog;
+}
+
+static LINEAR_LIGHT: AtomicBool = AtomicBool::new(false);
+
+/// Whether `dim_color` works in linear light (see `set_linear_light`).
+pub fn linear_light() -> bool {
+	LINEAR_LIGHT.load(Ordering::Relaxed)
+}
+
+/// The channels of the colors are not proportional to the amount of light (they are sRGB),
+/// so multiplying them directly makes fades that get dark too fast and look muddy.
+/// With linear light, the colors are converted to linear values first, which looks better
+/// but costs more.
+pub fn set_linear_light(linear_light: bool) {
+	LINEAR_LIGHT.store(linear_light, Ordering::Relaxed);
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+	let value = value as f32 / 255.0;
+	if value <= 0.04045 {
+		value / 12.92
+	} else {
+		((value + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+	let value = if value <= 0.0031308 {
+		value * 12.92
+	} else {
+		1.055 * value.powf(1.0 / 2.4) - 0.055
+	};
+	(value * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// Multiplies the amount of light of the color by the factor (between 0 and 1 to dim it).
+pub fn dim_color(color: Color, factor: f32) -> Color {
+	let channel = if linear_light() {
+		|value: u8, factor: f32| linear_to_srgb(srgb_to_linear(value) * factor)
+	} else {
+		|value: u8, factor: f32| (value as f32 * factor).round().clamp(0.0, 255.0) as u8
+	};
+	Color::RGBA(
+		channel(color.r, factor),
+		channel(color.g, factor),
+		channel(color.b, factor),
+		color.a,
+	)
+}
+
+type Rgb = [f32; 3];
+
+fn multiply(matrix: &[Rgb; 3], vector: Rgb) -> Rgb {
+	matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+const RGB_TO_LMS: [Rgb; 3] = [
+	[17.8824, 43.5161, 4.11935],
+	[3.45565, 27.1554, 3.86714],
+	[0.0299566, 0.184309, 1.46709],
+];
+
+const LMS_TO_RGB: [Rgb; 3] = [
+	[0.080944, -0.130504, 0.116721],
+	[-0.010249, 0.054019, -0.113615],
+	[-0.000365, -0.004122, 0.693511],
+];
+
+/// Daltonization, that is shifting the parts of the color that the colorblind eye cannot
+/// see into parts that it can see, so that colors that would look the same
+/// look different again.
+///
+/// See [Fidaner, Lin and Ozguven](http://scien.stanford.edu/pages/labsite/2005/psych221/projects/05/ofidaner/colorblindness_project.htm).
+fn daltonize(color: Color, colorblind_mode: ColorblindMode) -> Color {
+	let rgb = [color.r as f32, color.g as f32, color.b as f32];
+	let [l, m, s] = multiply(&RGB_TO_LMS, rgb);
+	// What the colorblind eye sees.
+	let seen_lms = match colorblind_mode {
+		ColorblindMode::None => return color,
+		ColorblindMode::Protanopia => [2.02344 * m - 2.52581 * s, m, s],
+		ColorblindMode::Deuteranopia => [l, 0.494207 * l + 1.24827 * s, s],
+		ColorblindMode::Tritanopia => [l, m, -0.395913 * l + 0.801109 * m],
+	};
+	let seen_rgb = multiply(&LMS_TO_RGB, seen_lms);
+	let error = [
+		rgb[0] - seen_rgb[0],
+		rgb[1] - seen_rgb[1],
+		rgb[2] - seen_rgb[2],
+	];
+	let shift = [0.0, 0.7 * error[0] + error[1], 0.7 * error[0] + error[2]];
+	let channel = |i: usize| (rgb[i] + shift[i]).round().clamp(0.0, 255.0) as u8;
+	Color::RGBA(channel(0), channel(1), channel(2), color.a)
+}
+
+impl DisplayFilter {
+	pub fn apply(&self, color: Color) -> Color {
+		let color = daltonize(color, self.colorblind_mode);
+		let channel = |value: u8| self.channel_table[value as usize];
+		Color::RGBA(
+			channel(color.r),
+			channel(color.g),
+			channel(color.b),
+			color.a,
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn neutral_brightness_and_gamma_change_nothing() {
+		assert_eq!(channel_table(1.0, 1.0), identity_channel_table());
+	}
+
+	#[test]
+	fn gamma_brightens_the_dark_colors_more() {
+		let table = channel_table(1.0, 2.0);
+		assert_eq!((table[0], table[255]), (0, 255));
+		assert!(table[32] - 32 > table[224] - 224);
+		assert!(table.windows(2).all(|pair| pair[0] <= pair[1]));
+	}
+
+	#[test]
+	fn the_lms_matrices_undo_each_other() {
+		for (i, column) in [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+			.into_iter()
+			.enumerate()
+		{
+			let back = multiply(&LMS_TO_RGB, multiply(&RGB_TO_LMS, column));
+			for (j, value) in back.into_iter().enumerate() {
+				assert!((value - column[j]).abs() < 0.01, "{back:?} for column {i}");
+			}
+		}
+	}
+
+	#[test]
+	fn no_colorblind_mode_changes_nothing() {
+		let colors = [
+			Color::RGBA(0, 0, 0, 255),
+			Color::RGBA(200, 40, 90, 128),
+			Color::RGBA(255, 255, 255, 0),
+		];
+		for color in colors {
+			assert_eq!(daltonize(color, ColorblindMode::None), color);
+		}
+		// Black is the same for everyone.
+		for colorblind_mode in [
+			ColorblindMode::Protanopia,
+			ColorblindMode::Deuteranopia,
+			ColorblindMode::Tritanopia,
+		] {
+			assert_eq!(daltonize(colors[0], colorblind_mode), colors[0]);
+		}
+	}
+
+	#[test]
+	fn srgb_values_survive_going_to_linear_and_back() {
+		for value in 0..=255 {
+			assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+		}
+		assert_eq!(srgb_to_linear(0), 0.0);
+		assert_eq!(srgb_to_linear(255), 1.0);
+	}
+}
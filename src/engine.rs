@@ -0,0 +1,1517 @@
+//! Setup of the window, the sprite sheets and the screen grid (see `EngineBuilder`),
+//! the `Engine` that owns all of it, and the main loop that runs a `Game`.
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use sdl2::clipboard::ClipboardUtil;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::image::{ImageRWops, LoadSurface};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::rwops::RWops;
+use sdl2::surface::Surface;
+use sdl2::video::{FullscreenType, Window, WindowContext};
+use sdl2::EventPump;
+
+use crate::devlog::{self, log};
+use crate::input::{self, KeyCombo};
+use crate::localization::tr;
+use crate::message::msg;
+use crate::terminal::Terminal;
+use crate::{
+	ansi, assets, display, narration, theme, CharSpriteSheet, CharSpriteSheets, GlyphStyle,
+	RegionSnapshot, RichText, ScreenGrid, ScreenTile, TransparencyPolicy,
+};
+
+/// Everything needed to draw the screen grid in a window and to get the input events.
+pub struct Engine {
+	pub window_canvas: Canvas<Window>,
+	pub texture_creator: TextureCreator<WindowContext>,
+	pub char_sprite_sheets: CharSpriteSheets,
+	pub event_pump: EventPump,
+	pub screen_grid: ScreenGrid,
+	pub debug_window: Option<DebugWindow>,
+	/// The tilesets that F7 switches between, the main sheet being the one at `tileset_index`.
+	tilesets: Vec<Tileset>,
+	tileset_index: usize,
+	is_tileset_watched: bool,
+	/// For all the tilesets, and the extra sheets too.
+	tileset_transparency: TransparencyPolicy,
+	/// Set if the file of the current tileset is watched (see `EngineBuilder::watch_tileset`).
+	tileset_watch: Option<TilesetWatch>,
+	/// Short notice shown at the bottom of the screen for a while (see `show_toast`),
+	/// and since when.
+	toast: Option<(RichText, Instant)>,
+	/// The tile that the mouse is over, if it is over the main window.
+	mouse_grid_xy: Option<(u32, u32)>,
+	is_system_cursor_shown: bool,
+	/// The keys pressed in the last recorded macro (see `run`).
+	key_macro: Vec<KeyCombo>,
+	is_recording_key_macro: bool,
+	/// Set after a recording, until the next key press that the macro is bound to.
+	is_binding_key_macro: bool,
+	/// The keys of the macro being replayed that are yet to be given to the game, and when
+	/// the last one was (see `replay_macro_key`).
+	macro_replay: VecDeque<KeyCombo>,
+	last_macro_key_time: Option<Instant>,
+	/// What `Game::is_in_danger` was at the last key of the replay.
+	was_in_danger: bool,
+	/// Whether the latest lines of the log (see `devlog`) are shown over the grid.
+	is_log_shown: bool,
+	backbuffer: Option<Backbuffer>,
+	scaling: Scaling,
+	/// The color around the grid when it does not fill the window (see `Scaling`).
+	margin_color: Color,
+	/// Set if drawing in the backbuffer failed even once, not to try again every frame.
+	is_backbuffer_unsupported: bool,
+	/// Set if the backbuffer was turned off (see `EngineBuilder::backbuffer`), which only
+	/// matters with `Scaling::Resize`, as the other policies need it to scale the grid.
+	is_backbuffer_disabled: bool,
+	is_vsync: bool,
+	/// Set by `Terminal::clear`, until the next `Terminal::present`.
+	is_cleared_as_terminal: bool,
+	/// Kept to make the sprite sheets again when the renderer is (see `set_vsync`).
+	extra_sheet_filepaths: Vec<String>,
+	fallback_char: char,
+	// The fields are dropped in order, and SDL should be the last thing to go.
+	_sdl_image_context: sdl2::image::Sdl2ImageContext,
+	video_subsystem: sdl2::VideoSubsystem,
+	sdl_context: sdl2::Sdl,
+}
+
+/// What happens to the grid when the window is resized.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Scaling {
+	/// The grid changes size to fill the window with tiles of the same size.
+	Resize,
+	/// The grid keeps its size and is scaled to the biggest whole multiple of its size
+	/// that fits in the window, centered.
+	IntegerScale,
+	/// The grid keeps its size and is stretched to the size of the window.
+	Stretch,
+	/// The grid keeps its size and is scaled as much as it can without being distorted,
+	/// centered.
+	Letterbox,
+}
+
+impl Scaling {
+	pub fn from_name(name: &str) -> Option<Scaling> {
+		match name {
+			"resize" => Some(Scaling::Resize),
+			"integer" => Some(Scaling::IntegerScale),
+			"stretch" => Some(Scaling::Stretch),
+			"letterbox" => Some(Scaling::Letterbox),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Scaling::Resize => "resize",
+			Scaling::IntegerScale => "integer",
+			Scaling::Stretch => "stretch",
+			Scaling::Letterbox => "letterbox",
+		}
+	}
+
+	/// The policy after this one, to go through all of them (like in an options menu).
+	pub fn next(self) -> Scaling {
+		match self {
+			Scaling::Resize => Scaling::IntegerScale,
+			Scaling::IntegerScale => Scaling::Stretch,
+			Scaling::Stretch => Scaling::Letterbox,
+			Scaling::Letterbox => Scaling::Resize,
+		}
+	}
+}
+
+/// A change to how the game is shown, that a game can ask for
+/// (see `Game::take_display_changes`), like from an options menu.
+#[derive(Clone, Copy)]
+pub enum DisplayChange {
+	ToggleFullscreen,
+	ToggleVsync,
+	NextScaling,
+	NextTileset,
+}
+
+/// Texture that the grid is drawn in, and that is then copied to the window,
+/// so that only the tiles that changed have to be drawn each frame
+/// (see `ScreenGrid::draw_changes_to_canvas`).
+struct Backbuffer {
+	texture: Texture,
+	/// What is drawn in the texture, `None` if it has to be drawn again entirely.
+	drawn_grid: Option<ScreenGrid>,
+}
+
+/// The window as a terminal, to present a screen grid that is not the engine's own to it
+/// with `ScreenGrid::present` (outside of `Engine::run`).
+/// The game loop does not go through this, and games (even `Spectator`) draw straight into
+/// the engine's grid: the loop adds overlays (toasts, the log, the mouse cursor) to that grid
+/// and takes them off afterwards, and the backbuffer already only draws the tiles that changed.
+impl Terminal for Engine {
+	fn grid_wh(&self) -> (u32, u32) {
+		self.screen_grid.grid_wh
+	}
+
+	fn put(&mut self, xy: (u32, u32), tile: &ScreenTile) {
+		*self.screen_grid.tile_mut(xy) = *tile;
+	}
+
+	fn clear(&mut self) {
+		self.screen_grid.clear();
+		self.is_cleared_as_terminal = true;
+	}
+
+	fn was_cleared(&self) -> bool {
+		self.is_cleared_as_terminal
+	}
+
+	fn present(&mut self) -> Result<(), String> {
+		Engine::present(self);
+		self.is_cleared_as_terminal = false;
+		Ok(())
+	}
+}
+
+/// A tileset that can be switched to (see `Engine::switch_to_next_tileset`).
+#[derive(Clone)]
+struct Tileset {
+	/// An asset path (see `assets`), or a file path for the files dropped on the window.
+	path: String,
+	is_asset: bool,
+	/// `None` to deduce it from the size of the image, as a grid of 16x16 sprites.
+	tile_wh: Option<(u32, u32)>,
+}
+
+impl Tileset {
+	/// The file that the tileset is loaded from, if it is not embedded in the executable.
+	fn filepath(&self) -> Option<PathBuf> {
+		if self.is_asset {
+			assets::filepath(&self.path)
+		} else {
+			Some(PathBuf::from(&self.path))
+		}
+	}
+
+	fn filename(&self) -> String {
+		let path = std::path::Path::new(&self.path);
+		path.file_name().map_or(self.path.clone(), |name| {
+			name.to_string_lossy().into_owned()
+		})
+	}
+
+	fn load_image(&self) -> Result<Surface<'static>, String> {
+		if self.is_asset {
+			let asset_bytes = assets::read(&self.path)?;
+			let image = RWops::from_bytes(&asset_bytes)?.load()?;
+			Ok(image)
+		} else {
+			Surface::from_file(&self.path)
+		}
+	}
+
+	fn sprite_wh(&self, image: &Surface) -> Result<(u32, u32), String> {
+		if let Some(tile_wh) = self.tile_wh {
+			return Ok(tile_wh);
+		}
+		let image_wh = image.size();
+		if !image_wh.0.is_multiple_of(16) || !image_wh.1.is_multiple_of(16) {
+			return Err(format!(
+				"a {}x{} image is not a grid of 16x16 sprites",
+				image_wh.0, image_wh.1
+			));
+		}
+		Ok((image_wh.0 / 16, image_wh.1 / 16))
+	}
+}
+
+/// The file of the current tileset and when it was last modified, to notice changes.
+struct TilesetWatch {
+	filepath: PathBuf,
+	modified: Option<SystemTime>,
+	last_check: Instant,
+}
+
+/// Modifier keys are part of the keys they are held with, not keys of their own.
+fn is_modifier_key(keycode: Keycode) -> bool {
+	matches!(
+		keycode,
+		Keycode::LCtrl
+			| Keycode::RCtrl
+			| Keycode::LShift
+			| Keycode::RShift
+			| Keycode::LAlt
+			| Keycode::RAlt
+			| Keycode::LGui
+			| Keycode::RGui
+	)
+}
+
+fn modification_time(filepath: &std::path::Path) -> Option<SystemTime> {
+	std::fs::metadata(filepath).ok()?.modified().ok()
+}
+
+/// Second window with its own screen grid, to show debugging stuff
+/// without covering what the game shows (see `Game::draw_debug`).
+///
+/// Closing it only closes it, the game goes on.
+pub struct DebugWindow {
+	window_canvas: Canvas<Window>,
+	char_sprite_sheets: CharSpriteSheets,
+	screen_grid: ScreenGrid,
+}
+
+pub struct EngineBuilder {
+	title: String,
+	/// `None` means that the window fits the grid, or is maximized if the grid size
+	/// is not given either.
+	window_wh: Option<(u32, u32)>,
+	/// `None` means that the grid fills the window.
+	grid_wh: Option<(u32, u32)>,
+	tile_wh: (u32, u32),
+	/// See `assets`.
+	tileset_asset_path: String,
+	tileset_tile_wh: (u32, u32),
+	extra_sheet_filepaths: Vec<String>,
+	alternate_tileset_asset_paths: Vec<String>,
+	watch_tileset: bool,
+	tileset_transparency: TransparencyPolicy,
+	fallback_char: char,
+	vsync: bool,
+	fullscreen: bool,
+	debug_window: bool,
+	backbuffer: bool,
+	scaling: Scaling,
+	margin_color: Color,
+}
+
+impl Default for EngineBuilder {
+	fn default() -> EngineBuilder {
+		EngineBuilder::new()
+	}
+}
+
+impl EngineBuilder {
+	pub fn new() -> EngineBuilder {
+		EngineBuilder {
+			title: "Why Crystals ?".to_string(),
+			window_wh: None,
+			grid_wh: None,
+			tile_wh: (16, 16),
+			tileset_asset_path: "Pastiche_8x8.png".to_string(),
+			tileset_tile_wh: (8, 8),
+			extra_sheet_filepaths: Vec::new(),
+			alternate_tileset_asset_paths: Vec::new(),
+			watch_tileset: false,
+			tileset_transparency: TransparencyPolicy::default(),
+			fallback_char: '?',
+			vsync: true,
+			fullscreen: false,
+			debug_window: false,
+			backbuffer: false,
+			scaling: Scaling::Resize,
+			margin_color: Color::RGB(0, 0, 0),
+		}
+	}
+
+	pub fn title(mut self, title: &str) -> EngineBuilder {
+		self.title = title.to_string();
+		self
+	}
+
+	pub fn window_wh(mut self, window_wh: (u32, u32)) -> EngineBuilder {
+		self.window_wh = Some(window_wh);
+		self
+	}
+
+	pub fn grid_wh(mut self, grid_wh: (u32, u32)) -> EngineBuilder {
+		self.grid_wh = Some(grid_wh);
+		self
+	}
+
+	/// The size (in pixels) of the tiles of the screen grid in the window.
+	pub fn tile_wh(mut self, tile_wh: (u32, u32)) -> EngineBuilder {
+		self.tile_wh = tile_wh;
+		self
+	}
+
+	/// The main sprite sheet (in the CP437 order, see `CharSpriteSheet`)
+	/// and the size (in pixels) of its sprites, Pastiche 8x8 by default.
+	pub fn tileset(mut self, asset_path: &str, tile_wh: (u32, u32)) -> EngineBuilder {
+		self.tileset_asset_path = asset_path.to_string();
+		self.tileset_tile_wh = tile_wh;
+		self
+	}
+
+	/// See `CharSpriteSheets::register_extra_sheet_from_filepath`.
+	pub fn extra_sheets(mut self, filepaths: &[String]) -> EngineBuilder {
+		self.extra_sheet_filepaths.extend(filepaths.iter().cloned());
+		self
+	}
+
+	/// Other tilesets (asset paths of images of 16x16 sprites in the CP437 order)
+	/// that F7 switches to, after the main one.
+	pub fn alternate_tilesets(mut self, asset_paths: &[String]) -> EngineBuilder {
+		self.alternate_tileset_asset_paths
+			.extend(asset_paths.iter().cloned());
+		self
+	}
+
+	/// Reloads the current tileset when its file changes (checking about every second),
+	/// which is handy when drawing a tileset. Tilesets embedded in the executable
+	/// cannot change.
+	pub fn watch_tileset(mut self, watch_tileset: bool) -> EngineBuilder {
+		self.watch_tileset = watch_tileset;
+		self
+	}
+
+	/// Which pixels of the tilesets (and of the extra sheets) are transparent,
+	/// the magenta and black ones by default.
+	pub fn tileset_transparency(mut self, transparency: TransparencyPolicy) -> EngineBuilder {
+		self.tileset_transparency = transparency;
+		self
+	}
+
+	pub fn vsync(mut self, vsync: bool) -> EngineBuilder {
+		self.vsync = vsync;
+		self
+	}
+
+	pub fn fullscreen(mut self, fullscreen: bool) -> EngineBuilder {
+		self.fullscreen = fullscreen;
+		self
+	}
+
+	/// Also opens a `DebugWindow`.
+	pub fn debug_window(mut self, debug_window: bool) -> EngineBuilder {
+		self.debug_window = debug_window;
+		self
+	}
+
+	/// See `CharSpriteSheets::set_fallback_char`.
+	pub fn fallback_char(mut self, fallback_char: char) -> EngineBuilder {
+		self.fallback_char = fallback_char;
+		self
+	}
+
+	/// What to do with the grid when the window size changes, and the color around
+	/// the grid when it does not fill the window.
+	pub fn scaling(mut self, scaling: Scaling, margin_color: Color) -> EngineBuilder {
+		self.scaling = scaling;
+		self.margin_color = margin_color;
+		self
+	}
+
+	/// Draws the grid in a `Backbuffer` instead of directly in the window.
+	/// The scaling policies other than `Scaling::Resize` always use one.
+	pub fn backbuffer(mut self, backbuffer: bool) -> EngineBuilder {
+		self.backbuffer = backbuffer;
+		self
+	}
+
+	pub fn build(self) -> Result<Engine, String> {
+		let sdl_context = sdl2::init()?;
+		let video_subsystem = sdl_context.video()?;
+		let sdl_image_context = sdl2::image::init(sdl2::image::InitFlag::all())?;
+
+		let window_wh = self.window_wh.or_else(|| {
+			self.grid_wh
+				.map(|grid_wh| (grid_wh.0 * self.tile_wh.0, grid_wh.1 * self.tile_wh.1))
+		});
+		let mut window_builder = video_subsystem.window(
+			&self.title,
+			window_wh.map_or(1200, |wh| wh.0),
+			window_wh.map_or(600, |wh| wh.1),
+		);
+		window_builder.position_centered().resizable();
+		if window_wh.is_none() {
+			window_builder.maximized();
+		}
+		let mut window = window_builder.build().map_err(|error| error.to_string())?;
+		if self.fullscreen {
+			window.set_fullscreen(FullscreenType::Desktop)?;
+		}
+
+		let window_canvas = new_window_canvas(window, self.vsync)?;
+		let texture_creator = window_canvas.texture_creator();
+
+		let char_sprite_sheet = CharSpriteSheet::from_asset(
+			&self.tileset_asset_path,
+			self.tileset_tile_wh,
+			self.tileset_transparency,
+			&texture_creator,
+		)?;
+		let mut char_sprite_sheets = CharSpriteSheets::new(char_sprite_sheet);
+		char_sprite_sheets.set_fallback_char(self.fallback_char)?;
+
+		let grid_wh = self.grid_wh.unwrap_or_else(|| {
+			let (w, h) = window_canvas.output_size().unwrap_or((1200, 600));
+			(w / self.tile_wh.0, h / self.tile_wh.1)
+		});
+		let screen_grid = ScreenGrid::new(grid_wh, self.tile_wh);
+
+		let debug_window = if self.debug_window {
+			let debug_window_wh = (800, 400);
+			let mut window_canvas = video_subsystem
+				.window(
+					&format!("{} (debug)", self.title),
+					debug_window_wh.0,
+					debug_window_wh.1,
+				)
+				.resizable()
+				.build()
+				.map_err(|error| error.to_string())?
+				.into_canvas()
+				.accelerated()
+				.build()
+				.map_err(|error| error.to_string())?;
+			window_canvas.set_blend_mode(BlendMode::Blend);
+			// Textures belong to the renderer they were made with, so the debug window
+			// needs its own copy of all the sheets.
+			let debug_texture_creator = window_canvas.texture_creator();
+			let char_sprite_sheet = CharSpriteSheet::from_asset(
+				&self.tileset_asset_path,
+				self.tileset_tile_wh,
+				self.tileset_transparency,
+				&debug_texture_creator,
+			)?;
+			let mut char_sprite_sheets = CharSpriteSheets::new(char_sprite_sheet);
+			char_sprite_sheets.set_fallback_char(self.fallback_char)?;
+			register_extra_sheets(
+				&mut char_sprite_sheets,
+				&self.extra_sheet_filepaths,
+				self.tileset_transparency,
+				&debug_texture_creator,
+			);
+			let grid_wh = (
+				debug_window_wh.0 / self.tile_wh.0,
+				debug_window_wh.1 / self.tile_wh.1,
+			);
+			Some(DebugWindow {
+				window_canvas,
+				char_sprite_sheets,
+				screen_grid: ScreenGrid::new(grid_wh, self.tile_wh),
+			})
+		} else {
+			None
+		};
+
+		let event_pump = sdl_context.event_pump()?;
+
+		let main_tileset = Tileset {
+			path: self.tileset_asset_path.clone(),
+			is_asset: true,
+			tile_wh: Some(self.tileset_tile_wh),
+		};
+		let alternate_tilesets =
+			self.alternate_tileset_asset_paths
+				.iter()
+				.map(|asset_path| Tileset {
+					path: asset_path.clone(),
+					is_asset: true,
+					tile_wh: None,
+				});
+		let tilesets = std::iter::once(main_tileset)
+			.chain(alternate_tilesets)
+			.collect();
+
+		let mut engine = Engine {
+			window_canvas,
+			texture_creator,
+			char_sprite_sheets,
+			event_pump,
+			screen_grid,
+			debug_window,
+			tilesets,
+			tileset_index: 0,
+			is_tileset_watched: self.watch_tileset,
+			tileset_transparency: self.tileset_transparency,
+			tileset_watch: None,
+			toast: None,
+			mouse_grid_xy: None,
+			is_system_cursor_shown: true,
+			key_macro: Vec::new(),
+			is_recording_key_macro: false,
+			is_binding_key_macro: false,
+			macro_replay: VecDeque::new(),
+			last_macro_key_time: None,
+			was_in_danger: false,
+			is_log_shown: false,
+			backbuffer: None,
+			scaling: self.scaling,
+			margin_color: self.margin_color,
+			is_backbuffer_unsupported: false,
+			is_backbuffer_disabled: !self.backbuffer,
+			is_vsync: self.vsync,
+			is_cleared_as_terminal: false,
+			extra_sheet_filepaths: self.extra_sheet_filepaths,
+			fallback_char: self.fallback_char,
+			_sdl_image_context: sdl_image_context,
+			video_subsystem,
+			sdl_context,
+		};
+		register_extra_sheets(
+			&mut engine.char_sprite_sheets,
+			&engine.extra_sheet_filepaths,
+			engine.tileset_transparency,
+			&engine.texture_creator,
+		);
+		engine.watch_current_tileset();
+		Ok(engine)
+	}
+}
+
+fn new_window_canvas(window: Window, vsync: bool) -> Result<Canvas<Window>, String> {
+	let mut window_canvas_builder = window.into_canvas().accelerated();
+	if vsync {
+		window_canvas_builder = window_canvas_builder.present_vsync();
+	}
+	let mut window_canvas = window_canvas_builder
+		.build()
+		.map_err(|error| error.to_string())?;
+	window_canvas.set_blend_mode(BlendMode::Blend);
+	Ok(window_canvas)
+}
+
+/// Registers the extra sprite sheets that can be loaded, the others being skipped.
+fn register_extra_sheets(
+	char_sprite_sheets: &mut CharSpriteSheets,
+	filepaths: &[String],
+	transparency: TransparencyPolicy,
+	texture_creator: &TextureCreator<WindowContext>,
+) {
+	for filepath in filepaths.iter() {
+		let result = char_sprite_sheets.register_extra_sheet_from_filepath(
+			filepath,
+			transparency,
+			texture_creator,
+		);
+		if let Err(error) = result {
+			log!(
+				Warning,
+				"Extra sprite sheet \"{filepath}\" skipped: {error}"
+			);
+		}
+	}
+}
+
+/// The duration of a tick, the game being updated at a fixed rate (of 60 ticks per second)
+/// whatever the frame rate is, so that the game speed does not depend on vsync
+/// or on the refresh rate of the display.
+pub const TICK_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// If the frames take too long then the ticks that are late past that many are dropped,
+/// so that the game slows down instead of trying to catch up forever.
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+
+/// The time between two keys of a macro being replayed, so that it can be seen (and
+/// interrupted, see `Game::is_in_danger`).
+const MACRO_KEY_PERIOD: Duration = Duration::from_millis(50);
+
+/// How often the file of the current tileset is checked for changes, when it is watched.
+const TILESET_WATCH_PERIOD: Duration = Duration::from_secs(1);
+
+/// The zoom levels, as how many times bigger than in the sprite sheet the tiles are.
+const ZOOM_SCALES: [u32; 4] = [1, 2, 3, 4];
+
+/// How the mouse cursor looks like over the screen grid.
+#[derive(Clone, Copy, PartialEq)]
+pub enum GridCursor {
+	/// The usual cursor of the system.
+	System,
+	/// The tile under the mouse has its colors swapped, and the system cursor is hidden.
+	Highlight,
+}
+
+/// What the mouse does over the grid, in grid coordinates (whatever the scaling).
+pub enum GridMouseEvent {
+	/// The mouse moved to another tile, `None` being out of the grid.
+	Hover(Option<(u32, u32)>),
+	Click {
+		grid_xy: (u32, u32),
+		button: MouseButton,
+	},
+}
+
+/// What a game built on the engine has to do, the engine taking care of the rest
+/// (getting the events, timing, presenting the screen grid in the window).
+pub trait Game {
+	fn handle_event(&mut self, _event: &Event) {}
+
+	/// The mouse events are also given (in window coordinates) to `handle_event`.
+	fn handle_grid_mouse_event(&mut self, _event: GridMouseEvent) {}
+
+	/// Moves the game forward by one tick, `dt` being always `TICK_DURATION`.
+	fn update(&mut self, dt: Duration);
+
+	/// Draws the game in the screen grid, that is cleared before.
+	///
+	/// Frames can happen between ticks, `tick_progress` (from 0 to 1) is how far the
+	/// time is from the last tick to the next one, to smooth things that move
+	/// if needed.
+	fn draw(&mut self, screen_grid: &mut ScreenGrid, tick_progress: f32);
+
+	/// Draws debugging stuff in the debug window (if there is one), the first line
+	/// being taken by the engine.
+	fn draw_debug(&mut self, _screen_grid: &mut ScreenGrid) {}
+
+	/// Changes to how the game is shown (see `Engine::apply_display_change`),
+	/// asked for after the events.
+	fn take_display_changes(&mut self) -> Vec<DisplayChange> {
+		Vec::new()
+	}
+
+	/// A notice to show as a toast (see `Engine::show_toast`), asked for after the events.
+	fn take_toast(&mut self) -> Option<RichText> {
+		None
+	}
+
+	/// The system cursor is shown anyway when the mouse is out of the grid.
+	fn grid_cursor(&self) -> GridCursor {
+		GridCursor::Highlight
+	}
+
+	/// The engine stops running the game when it returns `true`
+	/// (or when the window is closed).
+	fn is_over(&self) -> bool {
+		false
+	}
+
+	/// When it becomes `true`, the macro being replayed (if any) stops, not to walk
+	/// the player into whatever just showed up.
+	fn is_in_danger(&self) -> bool {
+		false
+	}
+}
+
+impl Engine {
+	/// Runs the game until it is over or the window is closed.
+	///
+	/// Dropping a PNG file on the window makes it the tileset, if it can be one,
+	/// F7 switches to the next tileset, F8 switches to the next color theme, F11 toggles
+	/// fullscreen, and Ctrl+Plus and Ctrl+Minus zoom in and out (changing the size of the grid
+	/// so that it still fills the window).
+	///
+	/// F5 starts recording a keyboard macro, and pressing it again stops the recording,
+	/// the next key pressed being then bound to the macro in the keybindings file (see
+	/// `input::bind_macro`), or Escape not to bind it. F6 or the bound key then replays the
+	/// recorded keys, as if they were typed again (the replay stopping if the game gets
+	/// in danger, see `Game::is_in_danger`).
+	/// F12 shows (or hides) the latest lines of the log over the grid.
+	///
+	/// For debugging, F9 pauses (or unpauses) the ticks while the frames keep being drawn,
+	/// and F10 makes exactly one tick happen (pausing first if needed).
+	pub fn run(&mut self, game: &mut impl Game) {
+		let mut previous_frame = Instant::now();
+		// Time that passed and that the ticks have yet to catch up with.
+		let mut time_to_simulate = Duration::ZERO;
+		let mut is_paused = false;
+		let mut steps_to_do = 0;
+		let mut frame_rate_start = Instant::now();
+		let mut frames_since_frame_rate_start = 0;
+		let mut frame_rate = 0;
+		let mut tick_number: u64 = 0;
+		loop {
+			let main_window_id = self.window_canvas.window().id();
+			let debug_window_id = self
+				.debug_window
+				.as_ref()
+				.map(|debug_window| debug_window.window_canvas.window().id());
+			let events: Vec<Event> = self.event_pump.poll_iter().collect();
+			for event in events {
+				if let Event::Window { window_id, win_event, .. } = &event {
+					if Some(*window_id) == debug_window_id {
+						let debug_window = self.debug_window.as_mut().unwrap();
+						match win_event {
+							WindowEvent::Close => self.debug_window = None,
+							WindowEvent::Resized(new_w, new_h) => {
+								debug_window.screen_grid.resize_grid((
+									*new_w as u32 / debug_window.screen_grid.tile_wh.0,
+									*new_h as u32 / debug_window.screen_grid.tile_wh.1,
+								));
+							},
+							_ => {},
+						}
+						continue;
+					}
+				}
+				match event {
+					// With two windows, closing the main one is not a quit event.
+					Event::Quit { .. } => return,
+					Event::Window { window_id, win_event: WindowEvent::Close, .. }
+						if window_id == main_window_id =>
+					{
+						return;
+					},
+					Event::KeyDown { keycode: Some(Keycode::F9), repeat: false, .. } => {
+						is_paused = !is_paused;
+						continue;
+					},
+					Event::KeyDown { keycode: Some(Keycode::F7), repeat: false, .. } => {
+						self.switch_to_next_tileset();
+						continue;
+					},
+					Event::KeyDown { keycode: Some(Keycode::F8), repeat: false, .. } => {
+						if let Some(theme_name) = theme::switch_to_next_theme() {
+							self.show_toast(msg!("engine.theme_changed", theme => theme_name));
+						}
+						continue;
+					},
+					Event::KeyDown { keycode: Some(keycode), keymod, .. }
+						if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD)
+							&& matches!(
+								keycode,
+								Keycode::Equals
+									| Keycode::Plus | Keycode::KpPlus
+									| Keycode::Minus | Keycode::KpMinus
+							) =>
+					{
+						let is_zoom_in = !matches!(keycode, Keycode::Minus | Keycode::KpMinus);
+						self.zoom(is_zoom_in);
+						continue;
+					},
+					Event::KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => {
+						self.toggle_fullscreen();
+						continue;
+					},
+					Event::KeyDown { keycode: Some(keycode), keymod, repeat: false, .. }
+						if self.is_binding_key_macro && !is_modifier_key(keycode) =>
+					{
+						self.is_binding_key_macro = false;
+						if keycode != Keycode::Escape {
+							self.bind_key_macro(KeyCombo::from_key(keycode, keymod));
+						}
+						continue;
+					},
+					Event::KeyDown { keycode: Some(Keycode::F5), repeat: false, .. } => {
+						self.is_recording_key_macro = !self.is_recording_key_macro;
+						if self.is_recording_key_macro {
+							self.key_macro.clear();
+							self.show_toast(msg!("engine.macro_recording"));
+						} else {
+							let key_count = self.key_macro.len();
+							self.is_binding_key_macro = key_count > 0;
+							self.show_toast(msg!("engine.macro_recorded", key_count => key_count));
+						}
+						continue;
+					},
+					Event::KeyDown { keycode: Some(Keycode::F6), repeat: false, .. }
+						if !self.is_recording_key_macro =>
+					{
+						self.start_macro_replay(self.key_macro.clone(), game);
+						continue;
+					},
+					Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } => {
+						self.is_log_shown = !self.is_log_shown;
+						continue;
+					},
+					Event::KeyDown { keycode: Some(Keycode::F10), .. } => {
+						is_paused = true;
+						steps_to_do += 1;
+						continue;
+					},
+					Event::DropFile { ref filename, .. } => self.handle_dropped_file(filename),
+					Event::MouseMotion { window_id, x, y, .. } if window_id == main_window_id => {
+						let mouse_grid_xy = self.window_xy_to_grid_xy((x, y));
+						if mouse_grid_xy != self.mouse_grid_xy {
+							self.mouse_grid_xy = mouse_grid_xy;
+							self.narrate_hovered_tile();
+							game.handle_grid_mouse_event(GridMouseEvent::Hover(mouse_grid_xy));
+						}
+					},
+					Event::MouseButtonDown { window_id, mouse_btn, x, y, .. }
+						if window_id == main_window_id =>
+					{
+						if let Some(grid_xy) = self.window_xy_to_grid_xy((x, y)) {
+							game.handle_grid_mouse_event(GridMouseEvent::Click {
+								grid_xy,
+								button: mouse_btn,
+							});
+						}
+					},
+					Event::Window { win_event: WindowEvent::Leave, .. }
+						if self.mouse_grid_xy.is_some() =>
+					{
+						self.mouse_grid_xy = None;
+						game.handle_grid_mouse_event(GridMouseEvent::Hover(None));
+					},
+					Event::Window { win_event: WindowEvent::Resized(..), .. } => {
+						self.fit_grid_to_window();
+					},
+					_ => {},
+				}
+				if !self.is_recording_key_macro {
+					if let Some(macro_keys) = input::macro_for_event(&event) {
+						self.start_macro_replay(macro_keys, game);
+						continue;
+					}
+				}
+				if let Event::KeyDown { keycode: Some(keycode), keymod, .. } = event {
+					if self.is_recording_key_macro && !is_modifier_key(keycode) {
+						self.key_macro.push(KeyCombo::from_key(keycode, keymod));
+					}
+				}
+				game.handle_event(&event);
+			}
+			self.replay_macro_key(game);
+			for display_change in game.take_display_changes() {
+				self.apply_display_change(display_change);
+			}
+			if let Some(toast) = game.take_toast() {
+				self.show_toast(toast);
+			}
+			self.reload_tileset_if_changed();
+			if game.is_over() {
+				return;
+			}
+
+			let now = Instant::now();
+			time_to_simulate += now - previous_frame;
+			previous_frame = now;
+			if is_paused {
+				time_to_simulate = Duration::ZERO;
+				for _ in 0..steps_to_do {
+					game.update(TICK_DURATION);
+					tick_number += 1;
+				}
+				steps_to_do = 0;
+			}
+			let mut tick_count = 0;
+			while time_to_simulate >= TICK_DURATION {
+				time_to_simulate -= TICK_DURATION;
+				game.update(TICK_DURATION);
+				tick_number += 1;
+				tick_count += 1;
+				if tick_count >= MAX_TICKS_PER_FRAME {
+					time_to_simulate = time_to_simulate.min(TICK_DURATION);
+					break;
+				}
+			}
+			let tick_progress = time_to_simulate.as_secs_f32() / TICK_DURATION.as_secs_f32();
+
+			self.screen_grid.clear();
+			game.draw(&mut self.screen_grid, tick_progress.min(1.0));
+			// What the engine draws over what the game drew is taken away after presenting,
+			// so that the grid keeps what the game drew (like for the hovered tile narration).
+			let mut under_overlays = Vec::new();
+			let full_width = self.screen_grid.grid_wh.0;
+			if is_paused {
+				under_overlays.push(self.screen_grid.save_region(Rect::new(0, 0, full_width, 1)));
+				let text = RichText::from(tr!("engine.paused"))
+					.bg_color(theme::number_color())
+					.glyph_style(GlyphStyle::Outline);
+				let text_length = text.tiles().len() as u32;
+				if text_length <= self.screen_grid.grid_wh.0 && self.screen_grid.grid_wh.1 > 0 {
+					let x = self.screen_grid.grid_wh.0 - text_length;
+					self.screen_grid.darw_text(text, (x, 0));
+				}
+			}
+			if self.is_log_shown {
+				under_overlays.push(self.draw_log_overlay());
+			}
+			under_overlays.extend(self.draw_grid_cursor(game.grid_cursor()));
+			if let Some((text, since)) = &self.toast {
+				if since.elapsed() < TOAST_DURATION {
+					let y = self.screen_grid.grid_wh.1.saturating_sub(1);
+					under_overlays.push(
+						self.screen_grid
+							.save_region(Rect::new(0, y as i32, full_width, 1)),
+					);
+					draw_text_clipped(&mut self.screen_grid, text.clone(), (0, y));
+				} else {
+					self.toast = None;
+				}
+			}
+			self.present();
+			for snapshot in under_overlays.iter().rev() {
+				self.screen_grid.restore(snapshot);
+			}
+
+			frames_since_frame_rate_start += 1;
+			if frame_rate_start.elapsed() >= Duration::from_secs(1) {
+				frame_rate = frames_since_frame_rate_start;
+				frames_since_frame_rate_start = 0;
+				frame_rate_start = Instant::now();
+			}
+			if let Some(debug_window) = &mut self.debug_window {
+				debug_window.screen_grid.clear();
+				let paused = if is_paused { ", paused" } else { "" };
+				let text = RichText::from(format!(
+					"{frame_rate} fps, tick {tick_number}{paused}, grid {}x{}",
+					self.screen_grid.grid_wh.0, self.screen_grid.grid_wh.1
+				))
+				.fg_color(theme::number_color());
+				draw_text_clipped(&mut debug_window.screen_grid, text, (0, 0));
+				game.draw_debug(&mut debug_window.screen_grid);
+				present_grid(
+					&debug_window.screen_grid,
+					&mut debug_window.window_canvas,
+					&mut debug_window.char_sprite_sheets,
+				);
+			}
+		}
+	}
+
+	/// Highlights the tile under the mouse (if the game wants that), and returns what the
+	/// tile was before.
+	fn draw_grid_cursor(&mut self, grid_cursor: GridCursor) -> Option<RegionSnapshot> {
+		let grid_wh = self.screen_grid.grid_wh;
+		let mouse_grid_xy = self
+			.mouse_grid_xy
+			.filter(|xy| xy.0 < grid_wh.0 && xy.1 < grid_wh.1);
+		let show_system_cursor = grid_cursor == GridCursor::System || mouse_grid_xy.is_none();
+		if show_system_cursor != self.is_system_cursor_shown {
+			self.sdl_context.mouse().show_cursor(show_system_cursor);
+			self.is_system_cursor_shown = show_system_cursor;
+		}
+		let (GridCursor::Highlight, Some(xy)) = (grid_cursor, mouse_grid_xy) else {
+			return None;
+		};
+		let under_cursor = self
+			.screen_grid
+			.save_region(Rect::new(xy.0 as i32, xy.1 as i32, 1, 1));
+		let tile = self.screen_grid.tile_mut(xy);
+		std::mem::swap(&mut tile.fg_color, &mut tile.bg_color);
+		Some(under_cursor)
+	}
+
+	/// Switches to the next (or previous) zoom level.
+	fn zoom(&mut self, is_zoom_in: bool) {
+		let sheet_tile_wh = self.char_sprite_sheets.main_sheet.tile_wh;
+		let current_scale = self.screen_grid.tile_wh.0 / sheet_tile_wh.0;
+		let new_scale = if is_zoom_in {
+			ZOOM_SCALES.iter().find(|&&scale| scale > current_scale)
+		} else {
+			ZOOM_SCALES
+				.iter()
+				.rev()
+				.find(|&&scale| scale < current_scale)
+		};
+		let Some(&new_scale) = new_scale else {
+			return;
+		};
+		let tile_wh = (sheet_tile_wh.0 * new_scale, sheet_tile_wh.1 * new_scale);
+		self.screen_grid.tile_wh = tile_wh;
+		self.fit_grid_to_window();
+		self.show_toast(msg!("engine.zoom", tile_size => tile_wh.0));
+	}
+
+	pub fn apply_display_change(&mut self, display_change: DisplayChange) {
+		match display_change {
+			DisplayChange::ToggleFullscreen => self.toggle_fullscreen(),
+			DisplayChange::ToggleVsync => {
+				let vsync = !self.is_vsync;
+				match self.set_vsync(vsync) {
+					Ok(()) if vsync => self.show_toast(msg!("engine.vsync_on")),
+					Ok(()) => self.show_toast(msg!("engine.vsync_off")),
+					Err(error) => log!(Error, "Could not toggle vsync: {error}"),
+				}
+			},
+			DisplayChange::NextScaling => {
+				let scaling = self.scaling.next();
+				self.set_scaling(scaling);
+				self.show_toast(msg!("engine.scaling_changed", scaling => scaling.name()));
+			},
+			DisplayChange::NextTileset => self.switch_to_next_tileset(),
+		}
+	}
+
+	/// Goes fullscreen (on the whole desktop) or back to a window, and makes the grid
+	/// fit the new window size.
+	pub fn toggle_fullscreen(&mut self) {
+		let window = self.window_canvas.window_mut();
+		let new_fullscreen_type = match window.fullscreen_state() {
+			FullscreenType::Off => FullscreenType::Desktop,
+			_ => FullscreenType::Off,
+		};
+		if let Err(error) = window.set_fullscreen(new_fullscreen_type) {
+			log!(Error, "Could not toggle fullscreen: {error}");
+			return;
+		}
+		self.fit_grid_to_window();
+	}
+
+	/// Changes what happens to the grid when the window is resized, the grid being
+	/// drawn again entirely (and resized to fit the window for `Scaling::Resize`).
+	pub fn set_scaling(&mut self, scaling: Scaling) {
+		self.scaling = scaling;
+		if let Some(backbuffer) = &mut self.backbuffer {
+			backbuffer.drawn_grid = None;
+		}
+		self.fit_grid_to_window();
+	}
+
+	/// Turns vsync on or off, which SDL can only do when creating a renderer, so the
+	/// window canvas is made again, with the sprite sheets (textures are per renderer)
+	/// and without the backbuffer (that will be made again when drawing).
+	pub fn set_vsync(&mut self, vsync: bool) -> Result<(), String> {
+		if vsync == self.is_vsync {
+			return Ok(());
+		}
+		// Read before anything is destroyed, so that not finding it changes nothing.
+		let tileset = self.tilesets[self.tileset_index].clone();
+		let tileset_surface = tileset.load_image()?;
+		let tileset_tile_wh = tileset.sprite_wh(&tileset_surface)?;
+
+		// A window can only have one renderer, so a hidden window holds the place
+		// of the window canvas while the old renderer is destroyed (along with all its
+		// textures, that must not be used anymore) and the new one is created.
+		let placeholder_canvas = self
+			.video_subsystem
+			.window("", 1, 1)
+			.hidden()
+			.build()
+			.map_err(|error| error.to_string())?
+			.into_canvas()
+			.software()
+			.build()
+			.map_err(|error| error.to_string())?;
+		self.backbuffer = None;
+		self.texture_creator = placeholder_canvas.texture_creator();
+		let window = std::mem::replace(&mut self.window_canvas, placeholder_canvas).into_window();
+		// SAFETY: Both refer to the same window, that lives as long as one of them does.
+		let window_copy = unsafe { Window::from_ref(window.context()) };
+		let (window_canvas, result) = match new_window_canvas(window, vsync) {
+			Ok(window_canvas) => (window_canvas, Ok(())),
+			Err(error) => match new_window_canvas(window_copy, self.is_vsync) {
+				Ok(window_canvas) => (window_canvas, Err(error)),
+				Err(error) => panic!("Could not make the window canvas again: {error}"),
+			},
+		};
+		if result.is_ok() {
+			self.is_vsync = vsync;
+		}
+		self.window_canvas = window_canvas;
+		self.texture_creator = self.window_canvas.texture_creator();
+
+		let main_sheet = CharSpriteSheet::from_surface(
+			&tileset_surface,
+			tileset_tile_wh,
+			self.tileset_transparency,
+			&self.texture_creator,
+		)
+		.unwrap_or_else(|error| panic!("Could not make the main sprite sheet again: {error}"));
+		let mut char_sprite_sheets = CharSpriteSheets::new(main_sheet);
+		char_sprite_sheets
+			.set_fallback_char(self.fallback_char)
+			.expect("the fallback character was accepted before");
+		register_extra_sheets(
+			&mut char_sprite_sheets,
+			&self.extra_sheet_filepaths,
+			self.tileset_transparency,
+			&self.texture_creator,
+		);
+		// The textures of the old sheets were destroyed with the old renderer.
+		self.char_sprite_sheets = char_sprite_sheets;
+		result
+	}
+
+	/// Changes the size of the grid so that it fills the window, if the scaling policy
+	/// is to resize the grid (the other policies keep the grid size and scale it instead).
+	fn fit_grid_to_window(&mut self) {
+		if self.scaling != Scaling::Resize {
+			return;
+		}
+		let window_wh = self.window_canvas.window().size();
+		let tile_wh = self.screen_grid.tile_wh;
+		self.screen_grid
+			.resize_grid((window_wh.0 / tile_wh.0, window_wh.1 / tile_wh.1));
+	}
+
+	/// Where the grid is drawn in the window, in pixels.
+	fn grid_dst_rect(&self) -> Rect {
+		let grid_wh = self.screen_grid.grid_wh;
+		let tile_wh = self.screen_grid.tile_wh;
+		let drawn_wh = (grid_wh.0 * tile_wh.0, grid_wh.1 * tile_wh.1);
+		let window_wh = self.window_canvas.window().size();
+		if self.is_drawing_directly() || drawn_wh.0 == 0 || drawn_wh.1 == 0 {
+			// The grid is drawn directly in the window, without scaling.
+			return Rect::new(0, 0, drawn_wh.0.max(1), drawn_wh.1.max(1));
+		}
+		let scaled_wh = match self.scaling {
+			Scaling::Resize => drawn_wh,
+			Scaling::Stretch => window_wh,
+			Scaling::IntegerScale => {
+				let scale = (window_wh.0 / drawn_wh.0)
+					.min(window_wh.1 / drawn_wh.1)
+					.max(1);
+				(drawn_wh.0 * scale, drawn_wh.1 * scale)
+			},
+			Scaling::Letterbox => {
+				let scale = (window_wh.0 as f32 / drawn_wh.0 as f32)
+					.min(window_wh.1 as f32 / drawn_wh.1 as f32);
+				(
+					((drawn_wh.0 as f32 * scale) as u32).max(1),
+					((drawn_wh.1 as f32 * scale) as u32).max(1),
+				)
+			},
+		};
+		let xy = match self.scaling {
+			Scaling::Resize => (0, 0),
+			_ => (
+				(window_wh.0 as i32 - scaled_wh.0 as i32) / 2,
+				(window_wh.1 as i32 - scaled_wh.1 as i32) / 2,
+			),
+		};
+		Rect::new(xy.0, xy.1, scaled_wh.0, scaled_wh.1)
+	}
+
+	/// The tile at the given position in the window (in pixels), if any.
+	fn window_xy_to_grid_xy(&self, window_xy: (i32, i32)) -> Option<(u32, u32)> {
+		self.screen_grid
+			.pixel_to_grid_coords(window_xy, self.grid_dst_rect())
+	}
+
+	/// Draws the latest lines of the log at the bottom of the grid (above the toast),
+	/// colored by their level, and returns what was there before.
+	fn draw_log_overlay(&mut self) -> RegionSnapshot {
+		let grid_wh = self.screen_grid.grid_wh;
+		let line_count = (grid_wh.1 / 3) as usize;
+		let lines = devlog::latest_lines(line_count);
+		let first_y = grid_wh.1.saturating_sub(1 + lines.len() as u32);
+		let under_overlay = self.screen_grid.save_region(Rect::new(
+			0,
+			first_y as i32,
+			grid_wh.0,
+			lines.len() as u32,
+		));
+		for (i, (level, line)) in lines.into_iter().enumerate() {
+			let y = first_y + i as u32;
+			for x in 0..grid_wh.0 {
+				*self.screen_grid.tile_mut((x, y)) = ScreenTile::new();
+			}
+			let text = RichText::from(format!("{}: {line}", level.tag())).fg_color(level.color());
+			draw_text_clipped(&mut self.screen_grid, text, (0, y));
+		}
+		under_overlay
+	}
+
+	/// Narrates what the tile under the mouse shows (see `narration`), if it is not blank.
+	fn narrate_hovered_tile(&self) {
+		let Some(xy) = self.mouse_grid_xy else {
+			return;
+		};
+		let grid_wh = self.screen_grid.grid_wh;
+		if !narration::is_enabled() || xy.0 >= grid_wh.0 || xy.1 >= grid_wh.1 {
+			return;
+		}
+		let (character, _) = ansi::tile_char(self.screen_grid.tile(xy));
+		if character != ' ' && character != '\u{a0}' {
+			let text = msg!("engine.tile_narration", character => character.to_string(), x => xy.0, y => xy.1);
+			narration::narrate("tile", &text.plain_text());
+		}
+	}
+
+	/// Binds the key to the last recorded macro (see `input::bind_macro`), telling how it went
+	/// in a toast.
+	fn bind_key_macro(&mut self, key_combo: KeyCombo) {
+		match input::bind_macro(key_combo, &self.key_macro) {
+			Ok(filepath) => self.show_toast(msg!(
+				"engine.macro_bound",
+				key => key_combo.name(),
+				path => filepath.display().to_string()
+			)),
+			Err(error) => {
+				log!(
+					Warning,
+					"Could not bind the macro to \"{}\": {error}",
+					key_combo.name()
+				);
+				self.show_toast(msg!("engine.macro_bind_error", error => error));
+			},
+		}
+	}
+
+	fn start_macro_replay(&mut self, macro_keys: Vec<KeyCombo>, game: &impl Game) {
+		self.macro_replay = macro_keys.into();
+		self.last_macro_key_time = None;
+		self.was_in_danger = game.is_in_danger();
+	}
+
+	/// Gives the next key of the macro being replayed to the game, if it is time to,
+	/// or stops the replay if the game just got in danger.
+	fn replay_macro_key(&mut self, game: &mut impl Game) {
+		if self.macro_replay.is_empty() {
+			return;
+		}
+		let is_in_danger = game.is_in_danger();
+		if is_in_danger && !self.was_in_danger {
+			self.macro_replay.clear();
+			self.show_toast(msg!("engine.macro_interrupted"));
+			return;
+		}
+		self.was_in_danger = is_in_danger;
+		let is_time = self
+			.last_macro_key_time
+			.is_none_or(|last_macro_key_time| last_macro_key_time.elapsed() >= MACRO_KEY_PERIOD);
+		if is_time {
+			let key_combo = self.macro_replay.pop_front().unwrap();
+			game.handle_event(&key_combo.key_down_event());
+			self.last_macro_key_time = Some(Instant::now());
+		}
+	}
+
+	/// Shows the text for a few seconds at the bottom of the screen.
+	pub fn show_toast(&mut self, text: RichText) {
+		narration::narrate("notice", &text.plain_text());
+		self.toast = Some((text.bg_color(theme::notice_color()), Instant::now()));
+	}
+
+	fn handle_dropped_file(&mut self, filepath: &str) {
+		let path = std::path::Path::new(filepath);
+		let filename = path.file_name().map_or(filepath.to_string(), |name| {
+			name.to_string_lossy().into_owned()
+		});
+		let is_png = path
+			.extension()
+			.is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+		if !is_png {
+			self.show_toast(msg!("engine.drop_unsupported", filename => filename));
+			return;
+		}
+		match self.load_tileset(filepath) {
+			Ok(()) => self.show_toast(msg!("engine.tileset_changed", filename => filename)),
+			Err(error) => {
+				self.show_toast(msg!("engine.tileset_error", filename => filename, error => error))
+			},
+		}
+	}
+
+	/// Makes the PNG image (of 16x16 sprites in the CP437 order) the main sprite sheet,
+	/// and one of the tilesets that F7 switches between.
+	pub fn load_tileset(&mut self, filepath: &str) -> Result<(), String> {
+		let known_index = self
+			.tilesets
+			.iter()
+			.position(|tileset| !tileset.is_asset && tileset.path == filepath);
+		let tileset_index = known_index.unwrap_or_else(|| {
+			self.tilesets.push(Tileset {
+				path: filepath.to_string(),
+				is_asset: false,
+				tile_wh: None,
+			});
+			self.tilesets.len() - 1
+		});
+		let result = self.switch_to_tileset(tileset_index);
+		if result.is_err() && known_index.is_none() {
+			self.tilesets.pop();
+		}
+		result
+	}
+
+	/// Switches to the next tileset (see `EngineBuilder::alternate_tilesets`), the ones
+	/// that fail to load being dropped from the ones to switch between.
+	pub fn switch_to_next_tileset(&mut self) {
+		if self.tilesets.len() <= 1 {
+			self.show_toast(msg!("engine.no_other_tileset"));
+			return;
+		}
+		let next_index = (self.tileset_index + 1) % self.tilesets.len();
+		let filename = self.tilesets[next_index].filename();
+		match self.switch_to_tileset(next_index) {
+			Ok(()) => self.show_toast(msg!("engine.tileset_changed", filename => filename)),
+			Err(error) => {
+				log!(Warning, "Tileset \"{filename}\" dropped: {error}");
+				self.tilesets.remove(next_index);
+				if next_index < self.tileset_index {
+					self.tileset_index -= 1;
+				}
+				self.show_toast(msg!("engine.tileset_error", filename => filename, error => error))
+			},
+		}
+	}
+
+	fn switch_to_tileset(&mut self, tileset_index: usize) -> Result<(), String> {
+		let tileset = self.tilesets[tileset_index].clone();
+		let surface = tileset.load_image()?;
+		self.use_tileset_image(&tileset, &surface)?;
+		self.tileset_index = tileset_index;
+		self.watch_current_tileset();
+		Ok(())
+	}
+
+	/// Makes the image of the tileset the main sprite sheet.
+	fn use_tileset_image(&mut self, tileset: &Tileset, surface: &Surface) -> Result<(), String> {
+		let tile_wh = tileset.sprite_wh(surface)?;
+		let sheet = CharSpriteSheet::from_surface(
+			surface,
+			tile_wh,
+			self.tileset_transparency,
+			&self.texture_creator,
+		)?;
+		self.char_sprite_sheets.set_main_sheet(sheet);
+		if let Some(backbuffer) = &mut self.backbuffer {
+			backbuffer.drawn_grid = None;
+		}
+		log!(
+			Info,
+			"Tileset \"{}\" loaded, with sprites of {}x{} pixels",
+			tileset.path,
+			tile_wh.0,
+			tile_wh.1
+		);
+		Ok(())
+	}
+
+	fn watch_current_tileset(&mut self) {
+		let filepath = self.tilesets[self.tileset_index].filepath();
+		self.tileset_watch = filepath
+			.filter(|_| self.is_tileset_watched)
+			.map(|filepath| TilesetWatch {
+				modified: modification_time(&filepath),
+				filepath,
+				last_check: Instant::now(),
+			});
+	}
+
+	fn reload_tileset_if_changed(&mut self) {
+		let Some(watch) = &mut self.tileset_watch else {
+			return;
+		};
+		if watch.last_check.elapsed() < TILESET_WATCH_PERIOD {
+			return;
+		}
+		watch.last_check = Instant::now();
+		let modified = modification_time(&watch.filepath);
+		if modified == watch.modified {
+			return;
+		}
+		// If the file is still being written then it will change again, and be reloaded then.
+		watch.modified = modified;
+		let filepath = watch.filepath.clone();
+		let tileset = self.tilesets[self.tileset_index].clone();
+		let filename = tileset.filename();
+		// From the file and not from the assets, that are read only once.
+		let result = Surface::from_file(&filepath)
+			.and_then(|surface| self.use_tileset_image(&tileset, &surface));
+		match result {
+			Ok(()) => self.show_toast(msg!("engine.tileset_reloaded", filename => filename)),
+			Err(error) => {
+				self.show_toast(msg!("engine.tileset_error", filename => filename, error => error))
+			},
+		}
+	}
+
+	/// Access to the system clipboard.
+	pub fn clipboard(&self) -> ClipboardUtil {
+		self.video_subsystem.clipboard()
+	}
+
+	/// Whether the grid is drawn directly in the window, without a backbuffer
+	/// (and so without scaling).
+	fn is_drawing_directly(&self) -> bool {
+		self.is_backbuffer_unsupported
+			|| (self.is_backbuffer_disabled && self.scaling == Scaling::Resize)
+	}
+
+	/// Draws the screen grid in the window.
+	pub fn present(&mut self) {
+		if !self.is_drawing_directly() {
+			if let Err(error) = self.present_with_backbuffer() {
+				log!(
+					Warning,
+					"Drawing in a backbuffer did not work, \
+					drawing directly (without scaling): {error}"
+				);
+				self.is_backbuffer_unsupported = true;
+				if let Some(backbuffer) = self.backbuffer.take() {
+					// SAFETY: The texture is not used anymore.
+					unsafe { backbuffer.texture.destroy() };
+				}
+			} else {
+				return;
+			}
+		}
+		present_grid(
+			&self.screen_grid,
+			&mut self.window_canvas,
+			&mut self.char_sprite_sheets,
+		);
+	}
+
+	fn present_with_backbuffer(&mut self) -> Result<(), String> {
+		let grid_wh = self.screen_grid.grid_wh;
+		let tile_wh = self.screen_grid.tile_wh;
+		let texture_wh = (grid_wh.0 * tile_wh.0, grid_wh.1 * tile_wh.1);
+		if texture_wh.0 == 0 || texture_wh.1 == 0 {
+			return Ok(());
+		}
+		let is_right_size = self.backbuffer.as_ref().is_some_and(|backbuffer| {
+			let query = backbuffer.texture.query();
+			(query.width, query.height) == texture_wh
+		});
+		if !is_right_size {
+			if let Some(backbuffer) = self.backbuffer.take() {
+				// SAFETY: The texture is not used anymore.
+				unsafe { backbuffer.texture.destroy() };
+			}
+			let texture = self
+				.texture_creator
+				.create_texture_target(PixelFormatEnum::RGBA8888, texture_wh.0, texture_wh.1)
+				.map_err(|error| error.to_string())?;
+			self.backbuffer = Some(Backbuffer { texture, drawn_grid: None });
+		}
+		let backbuffer = self.backbuffer.as_mut().unwrap();
+
+		let screen_grid = &self.screen_grid;
+		let char_sprite_sheets = &mut self.char_sprite_sheets;
+		let drawn_grid = backbuffer.drawn_grid.as_ref();
+		self.window_canvas
+			.with_texture_canvas(&mut backbuffer.texture, |texture_canvas| match drawn_grid {
+				Some(drawn_grid) => screen_grid.draw_changes_to_canvas(
+					texture_canvas,
+					char_sprite_sheets,
+					drawn_grid,
+				),
+				None => screen_grid.draw_to_canvas(texture_canvas, char_sprite_sheets),
+			})
+			.map_err(|error| error.to_string())?;
+		backbuffer.drawn_grid = Some(screen_grid.snapshot());
+
+		let margin_color = match self.scaling {
+			Scaling::Resize => theme::background_color(),
+			_ => self.margin_color,
+		};
+		self.window_canvas
+			.set_draw_color(display::filter().apply(margin_color));
+		self.window_canvas.clear();
+		let dst = self.grid_dst_rect();
+		let backbuffer = self.backbuffer.as_ref().unwrap();
+		self.window_canvas.copy(&backbuffer.texture, None, dst)?;
+		self.window_canvas.present();
+		Ok(())
+	}
+}
+
+fn present_grid(
+	screen_grid: &ScreenGrid,
+	window_canvas: &mut Canvas<Window>,
+	char_sprite_sheets: &mut CharSpriteSheets,
+) {
+	window_canvas.set_draw_color(display::filter().apply(theme::background_color()));
+	window_canvas.clear();
+	screen_grid.draw_to_canvas(window_canvas, char_sprite_sheets);
+	window_canvas.present();
+}
+
+/// Like `ScreenGrid::darw_text` but cuts off what does not fit instead of panicking.
+pub fn draw_text_clipped(screen_grid: &mut ScreenGrid, text: RichText, dst_xy: (u32, u32)) {
+	if dst_xy.1 >= screen_grid.grid_wh.1 {
+		return;
+	}
+	for (i, tile) in text.tiles().into_iter().enumerate() {
+		let x = dst_xy.0 + i as u32;
+		if x >= screen_grid.grid_wh.0 {
+			break;
+		}
+		*screen_grid.tile_mut((x, dst_xy.1)) = tile;
+	}
+}
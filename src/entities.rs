@@ -0,0 +1,124 @@
+//! The things that move around in the world: the player and the creatures.
+
+use sdl2::pixels::Color;
+
+use crate::render::{ScreenGrid, Sprite};
+use crate::rng::Rng;
+use crate::world::{Camera, TileMap};
+
+pub struct Entity {
+	pub xy: (u32, u32),
+	pub sprite: Sprite,
+	pub color: Color,
+}
+
+impl Entity {
+	/// The player `@`.
+	pub fn player(xy: (u32, u32)) -> Entity {
+		Entity {
+			xy,
+			sprite: Sprite::Char('@'),
+			color: Color::RGB(250, 220, 120),
+		}
+	}
+
+	/// A small creature that wanders around.
+	pub fn mite(xy: (u32, u32)) -> Entity {
+		Entity {
+			xy,
+			sprite: Sprite::Char('m'),
+			color: Color::RGB(200, 110, 90),
+		}
+	}
+}
+
+/// The player and the creatures, two of them never being on the same tile.
+pub struct Entities {
+	pub player: Entity,
+	pub creatures: Vec<Entity>,
+}
+
+impl Entities {
+	pub fn new(player: Entity) -> Entities {
+		Entities { player, creatures: Vec::new() }
+	}
+
+	pub fn all(&self) -> impl Iterator<Item = &Entity> {
+		std::iter::once(&self.player).chain(self.creatures.iter())
+	}
+
+	pub fn is_occupied(&self, xy: (u32, u32)) -> bool {
+		self.all().any(|entity| entity.xy == xy)
+	}
+
+	/// Whether an entity could move there, the map edges and the terrain that cannot
+	/// be walked on being in the way, as well as the other entities.
+	fn can_move_to(&self, map: &TileMap, xy: (i32, i32)) -> bool {
+		map.contains(xy) && {
+			let xy = (xy.0 as u32, xy.1 as u32);
+			map.terrain(xy).is_passable() && !self.is_occupied(xy)
+		}
+	}
+
+	/// Moves the player by one step, unless something is in the way.
+	/// Returns whether the player moved.
+	pub fn move_player(&mut self, map: &TileMap, dxy: (i32, i32)) -> bool {
+		let to_xy = (
+			self.player.xy.0 as i32 + dxy.0,
+			self.player.xy.1 as i32 + dxy.1,
+		);
+		let can_move = self.can_move_to(map, to_xy);
+		if can_move {
+			self.player.xy = (to_xy.0 as u32, to_xy.1 as u32);
+		}
+		can_move
+	}
+
+	/// Adds creatures on random free tiles where they can walk (fewer if such tiles
+	/// are hard to find).
+	pub fn spawn_mites(&mut self, map: &TileMap, rng: &mut Rng, count: u32) {
+		for _ in 0..count {
+			for _ in 0..100 {
+				let xy = (rng.below(map.map_wh.0), rng.below(map.map_wh.1));
+				if self.can_move_to(map, (xy.0 as i32, xy.1 as i32)) {
+					self.creatures.push(Entity::mite(xy));
+					break;
+				}
+			}
+		}
+	}
+
+	/// Makes each creature take a step in a random direction, or stay in place
+	/// (also when the way is blocked).
+	pub fn wander_creatures(&mut self, map: &TileMap, rng: &mut Rng) {
+		for creature_index in 0..self.creatures.len() {
+			let dxy = [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)][rng.below(5) as usize];
+			let xy = self.creatures[creature_index].xy;
+			let to_xy = (xy.0 as i32 + dxy.0, xy.1 as i32 + dxy.1);
+			if dxy != (0, 0) && self.can_move_to(map, to_xy) {
+				self.creatures[creature_index].xy = (to_xy.0 as u32, to_xy.1 as u32);
+			}
+		}
+	}
+
+	/// Draws the entities that are in the view of the camera, for which `is_seen`
+	/// returns `true` (as the ones in the field of view).
+	pub fn draw(
+		&self,
+		camera: &Camera,
+		screen_grid: &mut ScreenGrid,
+		is_seen: impl Fn((u32, u32)) -> bool,
+	) {
+		for entity in self.all().filter(|entity| is_seen(entity.xy)) {
+			let screen_xy = camera.map_xy_to_screen_xy((entity.xy.0 as i32, entity.xy.1 as i32));
+			let Some(screen_xy) = screen_xy else {
+				continue;
+			};
+			if screen_xy.0 < screen_grid.grid_wh.0 && screen_xy.1 < screen_grid.grid_wh.1 {
+				let tile = screen_grid.tile_mut(screen_xy);
+				tile.sprite = entity.sprite;
+				tile.fg_color = entity.color;
+			}
+		}
+	}
+}
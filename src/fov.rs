@@ -0,0 +1,280 @@
+//! Field of view, what can be seen from a tile of the map, by symmetric shadowcasting
+//! (see [the article by Albert Ford](https://www.albertford.com/shadowcasting/)).
+//!
+//! Symmetric means that if a tile can be seen from another then the other can be seen
+//! from it, so that nothing can see without being seen. The walls (the tiles that are
+//! not transparent) in view are seen too.
+
+use crate::render::{ScreenGrid, ScreenTile};
+use crate::world::{Camera, TileMap};
+
+/// How much the tiles that are remembered but not in view are dimmed.
+const REMEMBERED_BRIGHTNESS: f32 = 0.35;
+
+/// What is in view from where the map is seen from, and what was seen before.
+pub struct Visibility {
+	/// In the same order as the map tiles.
+	visible: Vec<bool>,
+	remembered: Vec<bool>,
+	map_wh: (u32, u32),
+}
+
+/// The rows of a quadrant are lines of tiles at a given distance from the origin,
+/// vertical for the east and west quadrants, and horizontal for the other two.
+#[derive(Clone, Copy)]
+enum Quadrant {
+	North,
+	East,
+	South,
+	West,
+}
+
+impl Quadrant {
+	/// The map coordinates of the tile at the distance `depth` from the origin in the
+	/// quadrant, at `column` from the middle of the row.
+	fn map_xy(self, origin_xy: (i32, i32), depth: i32, column: i32) -> (i32, i32) {
+		match self {
+			Quadrant::North => (origin_xy.0 + column, origin_xy.1 - depth),
+			Quadrant::South => (origin_xy.0 + column, origin_xy.1 + depth),
+			Quadrant::East => (origin_xy.0 + depth, origin_xy.1 + column),
+			Quadrant::West => (origin_xy.0 - depth, origin_xy.1 + column),
+		}
+	}
+}
+
+/// The tiles of a row between two slopes (from the origin), that are seen
+/// if nothing closer is in the way.
+struct Row {
+	depth: i32,
+	start_slope: f32,
+	end_slope: f32,
+}
+
+impl Row {
+	fn columns(&self) -> std::ops::RangeInclusive<i32> {
+		let min_column = (self.depth as f32 * self.start_slope + 0.5).floor() as i32;
+		let max_column = (self.depth as f32 * self.end_slope - 0.5).ceil() as i32;
+		min_column..=max_column
+	}
+
+	fn next(&self) -> Row {
+		Row { depth: self.depth + 1, ..*self }
+	}
+
+	/// Whether the tile is seen from the origin and the origin from the tile too (which
+	/// the tiles on the edges of the row may miss by a bit).
+	fn is_symmetric(&self, column: i32) -> bool {
+		column as f32 >= self.depth as f32 * self.start_slope
+			&& column as f32 <= self.depth as f32 * self.end_slope
+	}
+}
+
+/// The slope from the origin to the edge of the tile that comes before it in its row.
+fn slope(depth: i32, column: i32) -> f32 {
+	(2 * column - 1) as f32 / (2 * depth) as f32
+}
+
+impl Visibility {
+	/// Nothing is seen nor remembered.
+	pub fn new(map_wh: (u32, u32)) -> Visibility {
+		let tile_count = (map_wh.0 * map_wh.1) as usize;
+		Visibility {
+			visible: vec![false; tile_count],
+			remembered: vec![false; tile_count],
+			map_wh,
+		}
+	}
+
+	fn tile_index(&self, xy: (u32, u32)) -> usize {
+		assert!(xy.0 < self.map_wh.0);
+		assert!(xy.1 < self.map_wh.1);
+		(xy.0 * self.map_wh.1 + xy.1) as usize
+	}
+
+	pub fn is_visible(&self, xy: (u32, u32)) -> bool {
+		self.visible[self.tile_index(xy)]
+	}
+
+	/// Seen now or before.
+	pub fn is_remembered(&self, xy: (u32, u32)) -> bool {
+		self.remembered[self.tile_index(xy)]
+	}
+
+	/// Forgets everything that was seen.
+	pub fn forget(&mut self) {
+		self.remembered.fill(false);
+	}
+
+	/// Sees what can be seen from the origin up to the radius (in tiles), which is then
+	/// remembered for later. What was in view and is not anymore stays remembered.
+	pub fn update(&mut self, map: &TileMap, origin_xy: (u32, u32), radius: u32) {
+		assert!(map.map_wh == self.map_wh);
+		self.visible.fill(false);
+		let origin_xy = (origin_xy.0 as i32, origin_xy.1 as i32);
+		self.reveal(map, origin_xy);
+		for quadrant in [
+			Quadrant::North,
+			Quadrant::East,
+			Quadrant::South,
+			Quadrant::West,
+		] {
+			let first_row = Row { depth: 1, start_slope: -1.0, end_slope: 1.0 };
+			self.scan(map, origin_xy, radius, quadrant, first_row);
+		}
+	}
+
+	fn reveal(&mut self, map: &TileMap, xy: (i32, i32)) {
+		if map.contains(xy) {
+			let tile_index = self.tile_index((xy.0 as u32, xy.1 as u32));
+			self.visible[tile_index] = true;
+			self.remembered[tile_index] = true;
+		}
+	}
+
+	fn scan(
+		&mut self,
+		map: &TileMap,
+		origin_xy: (i32, i32),
+		radius: u32,
+		quadrant: Quadrant,
+		mut row: Row,
+	) {
+		if row.depth > radius as i32 {
+			return;
+		}
+		let depth = row.depth;
+		// Out of the map is like walls, to stop the scan there.
+		let is_wall = |column: Option<i32>| {
+			column.is_some_and(|column| {
+				let xy = quadrant.map_xy(origin_xy, depth, column);
+				!map.contains(xy) || !map.terrain((xy.0 as u32, xy.1 as u32)).is_transparent()
+			})
+		};
+		let is_floor = |column: Option<i32>| column.is_some() && !is_wall(column);
+		let mut previous_column = None;
+		for column in row.columns() {
+			// A bit more than the radius, for the circle to look round.
+			let in_radius =
+				depth * depth + column * column <= radius as i32 * radius as i32 + radius as i32;
+			if in_radius && (is_wall(Some(column)) || row.is_symmetric(column)) {
+				self.reveal(map, quadrant.map_xy(origin_xy, depth, column));
+			}
+			if is_wall(previous_column) && is_floor(Some(column)) {
+				row.start_slope = slope(depth, column);
+			}
+			if is_floor(previous_column) && is_wall(Some(column)) {
+				let mut next_row = row.next();
+				next_row.end_slope = slope(depth, column);
+				self.scan(map, origin_xy, radius, quadrant, next_row);
+			}
+			previous_column = Some(column);
+		}
+		if is_floor(previous_column) {
+			self.scan(map, origin_xy, radius, quadrant, row.next());
+		}
+	}
+
+	/// Changes the view of the camera (where the map was drawn) to show only what is
+	/// in view, what is remembered being dimmed and the rest being blank.
+	pub fn apply_to_view(&self, camera: &Camera, screen_grid: &mut ScreenGrid) {
+		for view_x in 0..camera.view_rect.width() {
+			for view_y in 0..camera.view_rect.height() {
+				let screen_xy = (
+					camera.view_rect.x() as u32 + view_x,
+					camera.view_rect.y() as u32 + view_y,
+				);
+				if screen_xy.0 >= screen_grid.grid_wh.0 || screen_xy.1 >= screen_grid.grid_wh.1 {
+					continue;
+				}
+				let map_xy = camera.screen_xy_to_map_xy(screen_xy).unwrap();
+				let in_map = (0..self.map_wh.0 as i32).contains(&map_xy.0)
+					&& (0..self.map_wh.1 as i32).contains(&map_xy.1);
+				let map_xy = (map_xy.0 as u32, map_xy.1 as u32);
+				let tile = screen_grid.tile_mut(screen_xy);
+				if !in_map || !self.is_remembered(map_xy) {
+					*tile = ScreenTile::new();
+				} else if !self.is_visible(map_xy) {
+					tile.dim(REMEMBERED_BRIGHTNESS);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rng::Rng;
+
+	fn map(rows: &[&str]) -> TileMap {
+		let rows: Vec<String> = rows.iter().map(|row| row.to_string()).collect();
+		TileMap::from_rows(&rows).unwrap()
+	}
+
+	#[test]
+	fn everything_is_seen_in_an_open_room() {
+		let map = map(&[".....", ".....", ".....", ".....", "....."]);
+		let mut visibility = Visibility::new(map.map_wh);
+		visibility.update(&map, (2, 2), 10);
+		for y in 0..5 {
+			for x in 0..5 {
+				assert!(visibility.is_visible((x, y)), "({x}, {y}) is not visible");
+			}
+		}
+	}
+
+	#[test]
+	fn walls_are_seen_but_hide_what_is_behind() {
+		let map = map(&[".....", "..#..", ".....", "....."]);
+		let mut visibility = Visibility::new(map.map_wh);
+		visibility.update(&map, (2, 3), 10);
+		assert!(visibility.is_visible((2, 1)));
+		assert!(!visibility.is_visible((2, 0)));
+		assert!(visibility.is_visible((0, 0)));
+	}
+
+	#[test]
+	fn the_radius_limits_the_view() {
+		let map = map(&[".........."]);
+		let mut visibility = Visibility::new(map.map_wh);
+		visibility.update(&map, (0, 0), 3);
+		assert!(visibility.is_visible((3, 0)));
+		assert!(!visibility.is_visible((4, 0)));
+	}
+
+	#[test]
+	fn what_was_seen_is_remembered() {
+		let map = map(&["...#..."]);
+		let mut visibility = Visibility::new(map.map_wh);
+		visibility.update(&map, (0, 0), 10);
+		visibility.update(&map, (6, 0), 10);
+		assert!(!visibility.is_visible((0, 0)));
+		assert!(visibility.is_remembered((0, 0)));
+		visibility.forget();
+		assert!(!visibility.is_remembered((0, 0)));
+	}
+
+	#[test]
+	fn seeing_is_symmetric() {
+		let map = TileMap::generate_cave((24, 16), &mut Rng::new(5));
+		let floor: Vec<(u32, u32)> = (0..map.map_wh.0)
+			.flat_map(|x| (0..map.map_wh.1).map(move |y| (x, y)))
+			.filter(|&xy| map.terrain(xy).is_transparent())
+			.collect();
+		let mut visibilities = Vec::new();
+		for &xy in floor.iter() {
+			let mut visibility = Visibility::new(map.map_wh);
+			visibility.update(&map, xy, 100);
+			visibilities.push(visibility);
+		}
+		for (a_index, &a_xy) in floor.iter().enumerate() {
+			for (b_index, &b_xy) in floor.iter().enumerate() {
+				assert_eq!(
+					visibilities[a_index].is_visible(b_xy),
+					visibilities[b_index].is_visible(a_xy),
+					"{a_xy:?} and {b_xy:?}"
+				);
+			}
+		}
+	}
+}
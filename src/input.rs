@@ -0,0 +1,418 @@
+//! Keybindings, that turn key presses into actions of the game, so that the keys can be
+//! changed without touching the code.
+//!
+//! The keybindings are described in `assets/data/keybindings.toml`, mapping action names
+//! to lists of keys. A key is an SDL key name (like `"Escape"`, `"Up"` or `"Q"`) that can
+//! have modifiers before it, like this:
+//!
+//! ```text
+//! quit = ["Escape", "Ctrl+Q"]
+//! move_north = ["Up", "K"]
+//! ```
+//!
+//! To rebind keys, a modified copy of the file can be put in the asset override directory
+//! (see `assets::set_override_dir`).
+//!
+//! The file can also have a `[macros]` table, of keys that replay other keys
+//! (see `macro_for_event`), which is written by `bind_macro` (the rest of the file
+//! being kept as it is):
+//!
+//! ```text
+//! [macros]
+//! "Ctrl+1" = ["Right", "Right", "Down"]
+//! ```
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+
+use crate::assets;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+	Quit,
+	TogglePause,
+	MoveNorth,
+	MoveEast,
+	MoveSouth,
+	MoveWest,
+	Save,
+	Load,
+}
+
+impl Action {
+	pub const ALL: [Action; 8] = [
+		Action::Quit,
+		Action::TogglePause,
+		Action::MoveNorth,
+		Action::MoveEast,
+		Action::MoveSouth,
+		Action::MoveWest,
+		Action::Save,
+		Action::Load,
+	];
+
+	/// The name of the action in the keybindings file.
+	pub fn name(self) -> &'static str {
+		match self {
+			Action::Quit => "quit",
+			Action::TogglePause => "toggle_pause",
+			Action::MoveNorth => "move_north",
+			Action::MoveEast => "move_east",
+			Action::MoveSouth => "move_south",
+			Action::MoveWest => "move_west",
+			Action::Save => "save",
+			Action::Load => "load",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Action> {
+		Action::ALL.into_iter().find(|action| action.name() == name)
+	}
+}
+
+/// A key with the modifiers that must be held with it (and the others must not).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyCombo {
+	pub keycode: Keycode,
+	pub ctrl: bool,
+	pub shift: bool,
+	pub alt: bool,
+}
+
+impl KeyCombo {
+	/// Parses keys like `"Ctrl+Shift+Q"`, the modifiers being `Ctrl`, `Shift` and `Alt`.
+	pub fn from_name(name: &str) -> Result<KeyCombo, String> {
+		let mut parts: Vec<&str> = name.split('+').collect();
+		// So that `"Ctrl++"` is Ctrl with the `+` key.
+		if name.ends_with("++") || name == "+" {
+			parts.truncate(parts.len() - 2);
+			parts.push("+");
+		}
+		let key_name = parts.pop().unwrap();
+		let keycode = Keycode::from_name(key_name)
+			.ok_or_else(|| format!("\"{key_name}\" is not a key name (in \"{name}\")"))?;
+		let mut key_combo = KeyCombo { keycode, ctrl: false, shift: false, alt: false };
+		for modifier in parts {
+			match modifier {
+				"Ctrl" => key_combo.ctrl = true,
+				"Shift" => key_combo.shift = true,
+				"Alt" => key_combo.alt = true,
+				_ => {
+					return Err(format!(
+						"\"{modifier}\" is not a modifier (in \"{name}\"), \
+						it should be \"Ctrl\", \"Shift\" or \"Alt\""
+					))
+				},
+			}
+		}
+		Ok(key_combo)
+	}
+
+	pub fn from_key(keycode: Keycode, keymod: Mod) -> KeyCombo {
+		KeyCombo {
+			keycode,
+			ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+			shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+			alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+		}
+	}
+
+	/// The name that `from_name` reads back.
+	pub fn name(self) -> String {
+		let mut name = String::new();
+		for (is_held, modifier) in [
+			(self.ctrl, "Ctrl+"),
+			(self.shift, "Shift+"),
+			(self.alt, "Alt+"),
+		] {
+			if is_held {
+				name.push_str(modifier);
+			}
+		}
+		name.push_str(&self.keycode.name());
+		name
+	}
+
+	/// A press of the key, as if it was typed (in no particular window).
+	pub fn key_down_event(self) -> Event {
+		let mut keymod = Mod::NOMOD;
+		for (is_held, modifier) in [
+			(self.ctrl, Mod::LCTRLMOD),
+			(self.shift, Mod::LSHIFTMOD),
+			(self.alt, Mod::LALTMOD),
+		] {
+			if is_held {
+				keymod |= modifier;
+			}
+		}
+		Event::KeyDown {
+			timestamp: 0,
+			window_id: 0,
+			keycode: Some(self.keycode),
+			scancode: Scancode::from_keycode(self.keycode),
+			keymod,
+			repeat: false,
+		}
+	}
+}
+
+struct Keybindings {
+	/// Where they were loaded from, to save the macros there.
+	asset_path: String,
+	actions: HashMap<KeyCombo, Action>,
+	macros: HashMap<KeyCombo, Vec<KeyCombo>>,
+}
+
+static KEYBINDINGS: RwLock<Option<Keybindings>> = RwLock::new(None);
+
+/// Loads the keybindings, replacing the ones loaded before.
+pub fn load_keybindings(asset_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+	let text = assets::read_to_string(asset_path)?;
+	let mut table: toml::Table = text.parse()?;
+	let macro_table = match table.remove("macros") {
+		Some(toml::Value::Table(macro_table)) => macro_table,
+		Some(_) => return Err("\"macros\" is not a table".into()),
+		None => toml::Table::new(),
+	};
+	let mut keybindings = HashMap::new();
+	for (action_name, key_names) in table.iter() {
+		let action = Action::from_name(action_name)
+			.ok_or_else(|| format!("\"{action_name}\" is not an action"))?;
+		let key_names = key_names
+			.as_array()
+			.ok_or_else(|| format!("the keys of \"{action_name}\" are not a list"))?;
+		for key_name in key_names.iter() {
+			let key_name = key_name
+				.as_str()
+				.ok_or_else(|| format!("a key of \"{action_name}\" is not a string"))?;
+			let key_combo = KeyCombo::from_name(key_name)?;
+			if let Some(other_action) = keybindings.insert(key_combo, action) {
+				if other_action != action {
+					return Err(format!(
+						"\"{key_name}\" is bound to both \"{}\" and \"{action_name}\"",
+						other_action.name()
+					)
+					.into());
+				}
+			}
+		}
+	}
+	let mut macros = HashMap::new();
+	for (key_name, macro_key_names) in macro_table.iter() {
+		let key_combo = KeyCombo::from_name(key_name)?;
+		if let Some(action) = keybindings.get(&key_combo) {
+			return Err(format!(
+				"\"{key_name}\" is bound to both \"{}\" and a macro",
+				action.name()
+			)
+			.into());
+		}
+		let macro_keys: Option<Result<Vec<KeyCombo>, String>> =
+			macro_key_names.as_array().map(|array| {
+				array
+					.iter()
+					.map(|key_name| {
+						let key_name = key_name.as_str().ok_or_else(|| {
+							format!("a key of the macro \"{key_name}\" is not a string")
+						})?;
+						KeyCombo::from_name(key_name)
+					})
+					.collect()
+			});
+		let macro_keys = macro_keys
+			.ok_or_else(|| format!("the keys of the macro \"{key_name}\" are not a list"))??;
+		macros.insert(key_combo, macro_keys);
+	}
+	*KEYBINDINGS.write().unwrap() = Some(Keybindings {
+		asset_path: asset_path.to_string(),
+		actions: keybindings,
+		macros,
+	});
+	Ok(())
+}
+
+/// The action bound to the key pressed in the event, if it is a key press
+/// (the repeats of a held key included).
+pub fn action_for_event(event: &Event) -> Option<Action> {
+	let Event::KeyDown { keycode: Some(keycode), keymod, .. } = event else {
+		return None;
+	};
+	let keybindings = KEYBINDINGS.read().unwrap();
+	keybindings
+		.as_ref()?
+		.actions
+		.get(&KeyCombo::from_key(*keycode, *keymod))
+		.copied()
+}
+
+/// The keys that the key pressed in the event replays, if it is a key press
+/// (not a repeat) of a key bound to a macro.
+pub fn macro_for_event(event: &Event) -> Option<Vec<KeyCombo>> {
+	let Event::KeyDown { keycode: Some(keycode), keymod, repeat: false, .. } = event else {
+		return None;
+	};
+	let keybindings = KEYBINDINGS.read().unwrap();
+	keybindings
+		.as_ref()?
+		.macros
+		.get(&KeyCombo::from_key(*keycode, *keymod))
+		.cloned()
+}
+
+/// Binds the key to a macro that replays the given keys, and saves it in the `[macros]`
+/// table at the end of the keybindings file (see `assets::write`), returning the file path.
+pub fn bind_macro(key_combo: KeyCombo, macro_keys: &[KeyCombo]) -> Result<PathBuf, String> {
+	let mut keybindings = KEYBINDINGS.write().unwrap();
+	let keybindings = keybindings
+		.as_mut()
+		.ok_or_else(|| "there are no keybindings to add it to".to_string())?;
+	if let Some(action) = keybindings.actions.get(&key_combo) {
+		return Err(format!(
+			"\"{}\" is already bound to \"{}\"",
+			key_combo.name(),
+			action.name()
+		));
+	}
+	// Bound only once saved, not to have a macro that is gone at the next run.
+	let mut macros = keybindings.macros.clone();
+	macros.insert(key_combo, macro_keys.to_vec());
+	let text = assets::read_to_string(&keybindings.asset_path)?;
+	let filepath = assets::write(
+		&keybindings.asset_path,
+		with_macros(&text, &macros).as_bytes(),
+	)?;
+	keybindings.macros = macros;
+	Ok(filepath)
+}
+
+/// The keybindings file with its `[macros]` table replaced by the given macros (or added at
+/// the end if there is none). Everything else is kept as it is, along with the comments
+/// of the macros table (the ones after the last macro going with the next table).
+fn with_macros(text: &str, macros: &HashMap<KeyCombo, Vec<KeyCombo>>) -> String {
+	let mut macro_lines: Vec<String> = macros
+		.iter()
+		.map(|(key_combo, macro_keys)| {
+			let key_names = macro_keys.iter().map(|key| key.name().into()).collect();
+			let key_names = toml::Value::Array(key_names);
+			format!("{} = {key_names}\n", toml::Value::from(key_combo.name()))
+		})
+		.collect();
+	macro_lines.sort();
+	let macro_lines = macro_lines.concat();
+
+	let mut new_text = String::new();
+	let mut lines = text.lines();
+	let mut is_there_a_macro_table = false;
+	while let Some(line) = lines.next() {
+		new_text.push_str(line);
+		new_text.push('\n');
+		if line.trim() != "[macros]" {
+			continue;
+		}
+		is_there_a_macro_table = true;
+		// The old macros go, up to the next table.
+		let mut next_table_line = None;
+		let mut section_lines = Vec::new();
+		for line in lines.by_ref() {
+			if line.trim_start().starts_with('[') {
+				next_table_line = Some(line);
+				break;
+			}
+			section_lines.push(line);
+		}
+		let is_comment = |line: &str| line.trim_start().starts_with('#');
+		let end_of_macros = section_lines
+			.iter()
+			.rposition(|line| !line.trim().is_empty() && !is_comment(line))
+			.map_or(section_lines.len(), |index| index + 1);
+		for line in section_lines[..end_of_macros].iter() {
+			if is_comment(line) {
+				new_text.push_str(line);
+				new_text.push('\n');
+			}
+		}
+		new_text.push_str(&macro_lines);
+		if let Some(next_table_line) = next_table_line {
+			new_text.push('\n');
+			for line in section_lines[end_of_macros..].iter() {
+				if !line.trim().is_empty() {
+					new_text.push_str(line);
+					new_text.push('\n');
+				}
+			}
+			new_text.push_str(next_table_line);
+			new_text.push('\n');
+		}
+	}
+	if !is_there_a_macro_table {
+		if !new_text.is_empty() && !new_text.ends_with("\n\n") {
+			new_text.push('\n');
+		}
+		new_text.push_str("[macros]\n");
+		new_text.push_str(&macro_lines);
+	}
+	new_text
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn key_combo(name: &str) -> KeyCombo {
+		KeyCombo::from_name(name).unwrap()
+	}
+
+	#[test]
+	fn key_names_round_trip() {
+		for name in [
+			"Escape",
+			"Up",
+			"Q",
+			"Ctrl+S",
+			"Ctrl+Shift+Alt+F5",
+			"+",
+			"Ctrl++",
+		] {
+			assert_eq!(key_combo(name).name(), name);
+			assert_eq!(key_combo(&key_combo(name).name()), key_combo(name));
+		}
+		assert_eq!(key_combo("Shift+Ctrl+K").name(), "Ctrl+Shift+K");
+		assert!(KeyCombo::from_name("Hyper+K").is_err());
+		assert!(KeyCombo::from_name("Ctrl+NotAKey").is_err());
+	}
+
+	#[test]
+	fn macros_are_written_over_the_old_ones() {
+		let text = "# Keys.\nquit = [\"Escape\"]\n\n[macros]\n# Walks.\n\"Ctrl+1\" = [\"Up\"]\n\n\
+			# Not macros.\n[other]\nthing = 1\n";
+		let macros = HashMap::from([
+			(
+				key_combo("Ctrl+2"),
+				vec![key_combo("Left"), key_combo("Shift+H")],
+			),
+			(key_combo("Ctrl+1"), vec![key_combo("Right")]),
+		]);
+		let new_text = with_macros(text, &macros);
+		assert_eq!(
+			new_text,
+			"# Keys.\nquit = [\"Escape\"]\n\n[macros]\n# Walks.\n\
+			\"Ctrl+1\" = [\"Right\"]\n\"Ctrl+2\" = [\"Left\", \"Shift+H\"]\n\n\
+			# Not macros.\n[other]\nthing = 1\n"
+		);
+		let table: toml::Table = new_text.parse().unwrap();
+		assert_eq!(table["macros"]["Ctrl+2"][1].as_str(), Some("Shift+H"));
+	}
+
+	#[test]
+	fn macros_are_added_at_the_end() {
+		let macros = HashMap::from([(key_combo("F1"), vec![key_combo("Down")])]);
+		assert_eq!(
+			with_macros("quit = [\"Escape\"]\n", &macros),
+			"quit = [\"Escape\"]\n\n[macros]\n\"F1\" = [\"Down\"]\n"
+		);
+	}
+}
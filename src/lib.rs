@@ -0,0 +1,35 @@
+pub mod ansi;
+pub mod assets;
+pub mod color;
+pub mod config;
+pub mod cp437;
+pub mod devlog;
+pub mod display;
+pub mod engine;
+pub mod entities;
+pub mod fov;
+pub mod input;
+pub mod localization;
+pub mod markup;
+pub mod message;
+pub mod names;
+pub mod narration;
+pub mod path;
+pub mod prefab;
+pub mod render;
+pub mod rng;
+pub mod save;
+pub mod sim;
+pub mod spectate;
+pub mod telnet;
+pub mod terminal;
+pub mod text;
+pub mod theme;
+pub mod ui;
+pub mod world;
+
+pub use render::{
+	BgPattern, CharSpriteSheet, CharSpriteSheets, GlyphStyle, RegionSnapshot, ScreenGrid,
+	ScreenLayers, ScreenTile, Slide, Sprite, TransparencyPolicy,
+};
+pub use text::{RichText, RichTextModifier};
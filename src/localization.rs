@@ -0,0 +1,139 @@
+//! Translation of the text shown to the player.
+//!
+//! Each language has a string catalog in the `lang/<language>.toml` asset that maps keys
+//! to translated strings, with nested tables giving dotted keys (so that
+//! `title = "..."` in a `[window]` table is found with the key `window.title`).
+//! The strings are obtained with `tr!("window.title")`, and when a key is missing
+//! from the catalog of the current language then the English catalog is used instead.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::assets;
+use crate::devlog::{self, log};
+
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+struct Catalog {
+	strings: HashMap<String, String>,
+}
+
+impl Catalog {
+	fn empty() -> Catalog {
+		Catalog { strings: HashMap::new() }
+	}
+
+	fn from_asset(asset_path: &str) -> Result<Catalog, Box<dyn std::error::Error>> {
+		let text = assets::read_to_string(asset_path)?;
+		let table: toml::Table = text.parse()?;
+		let mut strings = HashMap::new();
+		flatten_table(&table, "", &mut strings)?;
+		Ok(Catalog { strings })
+	}
+
+	fn from_language(language: &str) -> Catalog {
+		let asset_path = format!("lang/{language}.toml");
+		Catalog::from_asset(&asset_path).unwrap_or_else(|error| {
+			log!(
+				Error,
+				"Could not load the string catalog \"{asset_path}\": {error}"
+			);
+			Catalog::empty()
+		})
+	}
+}
+
+fn flatten_table(
+	table: &toml::Table,
+	key_prefix: &str,
+	strings: &mut HashMap<String, String>,
+) -> Result<(), String> {
+	for (key, value) in table.iter() {
+		let full_key = format!("{key_prefix}{key}");
+		match value {
+			toml::Value::String(string) => {
+				strings.insert(full_key, string.clone());
+			},
+			toml::Value::Table(sub_table) => {
+				flatten_table(sub_table, &format!("{full_key}."), strings)?;
+			},
+			_ => return Err(format!("the value of \"{full_key}\" is not a string")),
+		}
+	}
+	Ok(())
+}
+
+struct Localization {
+	language: String,
+	catalog: Catalog,
+	fallback_catalog: Catalog,
+}
+
+static LOCALIZATION: RwLock<Option<Localization>> = RwLock::new(None);
+
+/// Loads the string catalog of the given language (like `"en"` or `"fr"`)
+/// to be used by all the following translations.
+pub fn set_language(language: &str) {
+	let catalog = Catalog::from_language(language);
+	let fallback_catalog = if language == FALLBACK_LANGUAGE {
+		Catalog::empty()
+	} else {
+		Catalog::from_language(FALLBACK_LANGUAGE)
+	};
+	*LOCALIZATION.write().unwrap() = Some(Localization {
+		language: language.to_string(),
+		catalog,
+		fallback_catalog,
+	});
+}
+
+/// The language of the user according to the environment (`LANG` and such),
+/// which is a good default if the user did not ask for a specific language.
+pub fn system_language() -> String {
+	["LC_ALL", "LC_MESSAGES", "LANG"]
+		.iter()
+		.filter_map(|variable| std::env::var(variable).ok())
+		.find(|value| !value.is_empty())
+		// Something like "fr_FR.UTF-8" gives "fr".
+		.and_then(|value| value.split(['_', '.', '@']).next().map(str::to_lowercase))
+		.filter(|language| !language.is_empty() && language != "c" && language != "posix")
+		.unwrap_or_else(|| FALLBACK_LANGUAGE.to_string())
+}
+
+/// The translation of the string of the given key in the current language.
+///
+/// Missing keys fall back to English, and then to the key itself
+/// (so that it shows up on screen as something to fix).
+pub fn translate(key: &str) -> String {
+	if LOCALIZATION.read().unwrap().is_none() {
+		set_language(FALLBACK_LANGUAGE);
+	}
+	let localization = LOCALIZATION.read().unwrap();
+	let localization = localization.as_ref().unwrap();
+	localization
+		.catalog
+		.strings
+		.get(key)
+		.or_else(|| localization.fallback_catalog.strings.get(key))
+		.cloned()
+		.unwrap_or_else(|| {
+			// Texts are translated again for every frame that they are drawn in.
+			let problem = format!(
+				"Missing translation for \"{key}\" in \"{}\"",
+				localization.language
+			);
+			if devlog::is_first_report(&problem) {
+				log!(Warning, "{problem}");
+			}
+			key.to_string()
+		})
+}
+
+/// Translates the string of the given key (see `translate`).
+#[macro_export]
+macro_rules! tr {
+	($key:expr) => {
+		$crate::localization::translate($key)
+	};
+}
+pub use crate::tr;
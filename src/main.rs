@@ -1,458 +1,1087 @@
-use sdl2::event::{Event, WindowEvent};
-use sdl2::image::LoadSurface;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::{Color, PixelFormatEnum};
+use std::cell::Cell;
+use std::rc::Rc;
+
+use sdl2::clipboard::ClipboardUtil;
+use sdl2::event::Event;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture, TextureCreator};
-use sdl2::surface::Surface;
-use sdl2::video::{Window, WindowContext};
-
-fn map_surface_pixels(surface: &Surface, mut f: impl FnMut(Color) -> Color) -> Surface<'static> {
-	let mut new_surface = surface.convert_format(PixelFormatEnum::RGBA8888).unwrap();
-
-	// From what I may have understood from posts on the Internet,
-	// `SDL_ConvertSurfaceFormat` may decide to choose a format similar
-	// to the one being requested if such format is not available
-	// (because some pixel formats may not be available??), and also
-	// the endianness may mess with our expectations of the order of bytes
-	// in a pixel value (and on my machine it did be revered).
-	// Thus, we will only assume that the format is of the size of a `u32`
-	// and we also need transparency so it better be supported.
-	let pixel_format_enum = new_surface.pixel_format_enum();
-	assert!(pixel_format_enum.supports_alpha());
-	assert!(pixel_format_enum.byte_size_per_pixel() == 4);
-
-	let pixel_format = new_surface.pixel_format();
-	let pitch = new_surface.pitch();
-	let wh = (new_surface.width(), new_surface.height());
-	new_surface.with_lock_mut(|pixels: &mut [u8]| {
-		for y in 0..wh.1 {
-			for x in 0..wh.0 {
-				let index = (y * pitch + x * 4) as usize;
-				let pixel = pixels[index..(index + 4)].as_mut_ptr() as *mut u32;
-
-				// SAFETY: If this does not work then just go program something else.
-				let old_color = unsafe { Color::from_u32(&pixel_format, *pixel) };
-				let new_color = f(old_color);
-				unsafe {
-					*pixel = new_color.to_u32(&pixel_format);
-				}
-			}
-		}
-	});
-	new_surface
+use serde::{Deserialize, Serialize};
+
+use why_crystals::color::{self, Ramp};
+use why_crystals::config::{self, Config};
+use why_crystals::display::{self, ColorblindMode};
+use why_crystals::engine::{
+	draw_text_clipped, DisplayChange, Engine, EngineBuilder, Game, GridCursor, GridMouseEvent,
+	Scaling,
+};
+use why_crystals::entities::{Entities, Entity};
+use why_crystals::fov::Visibility;
+use why_crystals::input::{self, Action};
+use why_crystals::localization::{self, tr};
+use why_crystals::message::{self, msg};
+use why_crystals::names::NameGenerator;
+use why_crystals::path;
+use why_crystals::rng::Rng;
+use why_crystals::save;
+use why_crystals::sim::crystal::{CrystalGrowth, CrystalSpecies};
+use why_crystals::spectate::{SpectateClient, SpectateServer, SpectateUpdate};
+use why_crystals::telnet::TelnetServer;
+use why_crystals::ui::{
+	Column, Label, Menu, MessageLog, Panel, Paragraph, TextField, UiTree, Widget,
+};
+use why_crystals::world::{Camera, Terrain, TileMap};
+use why_crystals::{
+	assets, markup, narration, theme, BgPattern, RichText, ScreenGrid, ScreenLayers, ScreenTile,
+	Slide, Sprite,
+};
+
+/// Where the demo is saved to and loaded from, in the working directory.
+const SAVE_FILE_PATH: &str = "why-crystals-save.toml";
+
+/// What is saved of the demo (see `save`), the rest being made again from it.
+#[derive(Serialize, Deserialize)]
+struct DemoSave {
+	/// In hexadecimal, as it does not always fit in the integers of TOML.
+	seed: String,
+	rng_state: Rng,
+	ticks: u32,
+	play_time: f64,
+	walker_x: u32,
+	cave: TileMap,
+	crystals: Vec<String>,
+	player: (u32, u32),
+	mites: Vec<(u32, u32)>,
+	cave_entrance: (u32, u32),
 }
 
-/// Sprite sheet with ASCII-like sprites.
-///
-/// The order of the character sprites (left to right and top to bottom) is expected
-/// to be the same as in [CP437](https://en.wikipedia.org/wiki/Code_page_437).
-///
-/// If the sheet texture is loaded from a file, such file can be obtained from
-/// [the Dwarf Fortress wiki tileset repo](https://dwarffortresswiki.org/Tileset_repository).
-struct CharSpriteSheet {
-	texture: Texture,
-	grid_wh: (u32, u32),
-	tile_wh: (u32, u32),
+/// The demo screen, showing off what can be drawn for now.
+struct Demo {
+	seed: u64,
+	iteration_number: u32,
+	/// Time spent running the game, which does not count the time spent paused.
+	play_time: std::time::Duration,
+	/// The `@` moves along a line.
+	walker_x: u32,
+	walker_slide: Option<Slide>,
+	spectate_server: Option<SpectateServer>,
+	telnet_server: Option<TelnetServer>,
+	ui_tree: UiTree,
+	/// The index of the item of the menu that was chosen, if one was since last time.
+	menu_choice: Rc<Cell<Option<usize>>>,
+	/// Same for the options menu, that changes how the game is shown.
+	options_choice: Rc<Cell<Option<usize>>>,
+	display_changes: Vec<DisplayChange>,
+	/// What happens in the cave, under the view of the cave.
+	message_log: MessageLog,
+	/// The tile under the mouse, described in a tooltip when it is in the cave.
+	hovered_grid_xy: Option<(u32, u32)>,
+	/// Effects drawn over the demo, a sparkle (on top) and its trail (below it).
+	effect_layers: ScreenLayers,
+	/// The cave, seen by a camera that pans along it.
+	cave: TileMap,
+	camera: Camera,
+	crystals: CrystalGrowth,
+	is_growth_paused: bool,
+	toast: Option<RichText>,
+	/// The player (moved with the move actions, the camera following it, and seeing
+	/// around with a lantern) and the mites in the cave.
+	entities: Entities,
+	cave_visibility: Visibility,
+	/// Where the player started, the way back there being shown.
+	cave_entrance_xy: (u32, u32),
+	/// What is random once the run has started, like the growth of the crystals.
+	rng: Rng,
+	/// To name the cave and its crystals again when loading a save from another seed.
+	name_generator: NameGenerator,
+	is_over: bool,
 }
 
-impl CharSpriteSheet {
-	fn from_filepath(
-		filepath: &str,
-		tile_wh: (u32, u32),
-		texture_creator: &TextureCreator<WindowContext>,
-	) -> CharSpriteSheet {
-		let raw_surface = Surface::from_file(filepath).unwrap();
-		let pink_and_black_to_transparent = |color| {
-			if matches!(
-				color,
-				Color { r: 255, g: 0, b: 255, .. } | Color { r: 0, g: 0, b: 0, .. }
-			) {
-				Color::RGBA(0, 0, 0, 0)
-			} else {
-				color
-			}
-		};
-		let surface = map_surface_pixels(&raw_surface, pink_and_black_to_transparent);
-		let mut texture = texture_creator
-			.create_texture_from_surface(surface)
-			.unwrap();
-		texture.set_blend_mode(BlendMode::Blend);
-		CharSpriteSheet::from_texture(texture, tile_wh)
-	}
+impl Demo {
+	fn new(seed: u64, clipboard: ClipboardUtil, name_generator: NameGenerator) -> Demo {
+		// All the randomness of the run should come from there,
+		// so that a seed always gives the same run.
+		let mut rng = Rng::new(seed);
+		set_name_variables(&name_generator, &mut rng);
+		message::set_variable(
+			"play_time",
+			format_play_time(std::time::Duration::ZERO).as_str(),
+		);
+		let mut cave = TileMap::generate_cave((80, 30), &mut rng);
+		let cave_visibility = Visibility::new(cave.map_wh);
+		let mut crystals = CrystalGrowth::new(cave.map_wh, CrystalSpecies::default_species());
+		crystals.plant_random_seeds(&mut cave, &mut rng);
+		// The cave is dug from its center, so there is room around there.
+		let cave_center_xy = (cave.map_wh.0 / 2, cave.map_wh.1 / 2);
+		let cave_entrance_xy = (0..cave.map_wh.0)
+			.flat_map(|x| (0..cave.map_wh.1).map(move |y| (x, y)))
+			.filter(|&xy| cave.terrain(xy).is_passable())
+			.min_by_key(|&xy| xy.0.abs_diff(cave_center_xy.0) + xy.1.abs_diff(cave_center_xy.1))
+			.unwrap_or(cave_center_xy);
+		let mut entities = Entities::new(Entity::player(cave_entrance_xy));
+		entities.spawn_mites(&cave, &mut rng, 6);
 
-	fn from_texture(texture: Texture, tile_wh: (u32, u32)) -> CharSpriteSheet {
-		let texture_query = texture.query();
-		let texture_wh = (texture_query.width, texture_query.height);
-		assert!(texture_wh.0 % tile_wh.0 == 0);
-		assert!(texture_wh.1 % tile_wh.1 == 0);
-		let grid_wh = (texture_wh.0 / tile_wh.0, texture_wh.1 / tile_wh.1);
-		CharSpriteSheet { texture, grid_wh, tile_wh }
-	}
+		let mut message_log = MessageLog::new();
+		message_log.push(msg!("demo.log_enter"));
 
-	fn char_index_to_rect(&self, char_index: u32) -> Rect {
-		assert!(char_index < self.grid_wh.0 * self.grid_wh.1);
-		let grid_xy = (char_index % self.grid_wh.0, char_index / self.grid_wh.1);
-		let xy = (grid_xy.0 * self.tile_wh.0, grid_xy.1 * self.tile_wh.1);
-		Rect::new(xy.0 as i32, xy.1 as i32, self.tile_wh.0, self.tile_wh.1)
-	}
+		let menu_choice = Rc::new(Cell::new(None));
+		let menu_choice_setter = Rc::clone(&menu_choice);
+		let options_choice = Rc::new(Cell::new(None));
+		let options_choice_setter = Rc::clone(&options_choice);
+		let ui_tree = UiTree::new(
+			Panel::new(
+				Column::new()
+					.with(Label::message("demo.hum"))
+					.with(Paragraph::message("demo.cave"))
+					.with(Label::new(""))
+					.with(
+						TextField::new(tr!("demo.name_prompt"), 20)
+							.bound_to_variable("player_name"),
+					)
+					.with(Label::message("demo.welcome"))
+					.with(Label::message("demo.play_time"))
+					.with(Label::new(""))
+					.with(
+						Menu::new()
+							.message_item("demo.menu_save")
+							.message_item("demo.menu_load")
+							.message_item("demo.menu_quit")
+							.on_choice(move |index| menu_choice_setter.set(Some(index))),
+					)
+					.with(Label::new(""))
+					.with(Label::message("demo.options"))
+					.with(
+						Menu::new()
+							.message_item("demo.option_fullscreen")
+							.message_item("demo.option_vsync")
+							.message_item("demo.option_scaling")
+							.message_item("demo.option_tileset")
+							.on_choice(move |index| options_choice_setter.set(Some(index))),
+					),
+			)
+			.title_message("demo.panel_title"),
+			Rect::new(1, 5, 1, 1),
+			clipboard,
+		);
 
-	fn draw_char_to_canvas<T: RenderTarget>(
-		&mut self,
-		char_index: u32,
-		canvas: &mut Canvas<T>,
-		color: Color,
-		dst: Rect,
-	) {
-		self.texture.set_color_mod(color.r, color.g, color.b);
-		canvas
-			.copy(&self.texture, self.char_index_to_rect(char_index), dst)
-			.unwrap();
+		Demo {
+			seed,
+			iteration_number: 0,
+			play_time: std::time::Duration::ZERO,
+			walker_x: 1,
+			walker_slide: None,
+			spectate_server: None,
+			telnet_server: None,
+			ui_tree,
+			menu_choice,
+			options_choice,
+			display_changes: Vec::new(),
+			message_log,
+			hovered_grid_xy: None,
+			effect_layers: ScreenLayers::new(2, (1, 1)),
+			cave,
+			camera: Camera::new(Rect::new(0, 0, 1, 1)),
+			crystals,
+			is_growth_paused: false,
+			toast: None,
+			entities,
+			cave_visibility,
+			cave_entrance_xy,
+			rng,
+			name_generator,
+			is_over: false,
+		}
 	}
 }
 
-type SpriteIndex = u32;
-
-#[derive(Clone, Copy)]
-struct ScreenTile {
-	sprite: SpriteIndex,
-	fg_color: Color,
-	bg_color: Color,
+/// Names the cave and its crystals, for the messages that talk about them.
+fn set_name_variables(name_generator: &NameGenerator, rng: &mut Rng) {
+	// The articles are in the templates, "the" for only some of the caves.
+	let cave_name = name_generator.generate("cave", rng);
+	let cave_name = if cave_name.has_tag("definite") {
+		msg!(&format!("demo.cave_name_{}", cave_name.form()), name => cave_name.text)
+	} else {
+		cave_name.text.as_str().into()
+	};
+	message::set_variable("cave_name", cave_name);
+	let crystal_name = name_generator.generate("crystal", rng);
+	message::set_variable(
+		"crystal_name",
+		msg!(&format!("demo.crystal_name_{}", crystal_name.form()), name => crystal_name.text),
+	);
 }
 
-const COLOR_WHITE: Color = Color { r: 180, g: 220, b: 200, a: 255 };
-const COLOR_BG: Color = Color { r: 5, g: 30, b: 25, a: 255 };
+impl Demo {
+	fn save_state(&self) -> DemoSave {
+		DemoSave {
+			seed: format!("{:x}", self.seed),
+			rng_state: self.rng.clone(),
+			ticks: self.iteration_number,
+			play_time: self.play_time.as_secs_f64(),
+			walker_x: self.walker_x,
+			cave: self.cave.clone(),
+			crystals: self.crystals.cells_to_rows(),
+			player: self.entities.player.xy,
+			mites: self.entities.creatures.iter().map(|mite| mite.xy).collect(),
+			cave_entrance: self.cave_entrance_xy,
+		}
+	}
+
+	fn save(&mut self) {
+		let path = std::path::Path::new(SAVE_FILE_PATH);
+		let text = match save::write_save(path, &self.save_state()) {
+			Ok(()) => msg!("demo.saved", path => SAVE_FILE_PATH),
+			Err(error) => msg!("demo.save_error", error => error),
+		};
+		self.message_log.push(text.clone());
+		self.toast = Some(text);
+	}
 
-impl ScreenTile {
-	fn new() -> ScreenTile {
-		ScreenTile {
-			sprite: 0,
-			fg_color: COLOR_WHITE,
-			bg_color: COLOR_BG,
+	fn load(&mut self) {
+		let path = std::path::Path::new(SAVE_FILE_PATH);
+		let result = save::read_save(path).and_then(|state| self.load_state(state));
+		let is_loaded = result.is_ok();
+		let text = match result {
+			Ok(()) => msg!("demo.loaded", path => SAVE_FILE_PATH),
+			Err(error) => msg!("demo.load_error", error => error),
+		};
+		self.message_log.push(text.clone());
+		self.toast = Some(text);
+		if is_loaded {
+			// The cave may be another one.
+			self.message_log.push(msg!("demo.log_enter"));
 		}
 	}
 
-	fn from_char(character: char) -> ScreenTile {
-		ScreenTile {
-			sprite: character as SpriteIndex,
-			fg_color: COLOR_WHITE,
-			bg_color: COLOR_BG,
+	/// What is seen on the tile of the cave that is at the screen tile, if anything.
+	fn describe_cave_tile_at(&self, grid_xy: (u32, u32)) -> Option<RichText> {
+		let map_xy = self.camera.screen_xy_to_map_xy(grid_xy)?;
+		if !self.cave.contains(map_xy) {
+			return None;
 		}
+		let map_xy = (map_xy.0 as u32, map_xy.1 as u32);
+		if !self.cave_visibility.is_remembered(map_xy) {
+			return None;
+		}
+		if self.cave_visibility.is_visible(map_xy) {
+			if self.entities.player.xy == map_xy {
+				return Some(msg!("demo.thing_player"));
+			} else if self.entities.is_occupied(map_xy) {
+				return Some(msg!("demo.thing_mite"));
+			}
+		}
+		if let Some(species) = self.crystals.species_at(map_xy) {
+			return Some(msg!("demo.thing_crystal", species => species.name));
+		}
+		Some(match self.cave.terrain(map_xy) {
+			Terrain::Air => msg!("demo.thing_air"),
+			Terrain::Rock => msg!("demo.thing_rock"),
+			Terrain::Crystal => msg!("demo.thing_wall_crystal"),
+			Terrain::Water => msg!("demo.thing_water"),
+		})
 	}
-}
 
-struct ScreenGrid {
-	tiles: Vec<ScreenTile>,
-	grid_wh: (u32, u32),
-	tile_wh: (u32, u32),
-}
+	/// Nothing changes if the state cannot be loaded.
+	fn load_state(&mut self, state: DemoSave) -> Result<(), String> {
+		let seed = u64::from_str_radix(&state.seed, 16)
+			.map_err(|error| format!("the seed in the save is not a number: {error}"))?;
+		let play_time = std::time::Duration::try_from_secs_f64(state.play_time)
+			.map_err(|error| format!("the play time is wrong: {error}"))?;
+		let walker_x = state.walker_x;
+		if !(1..=26).contains(&walker_x) {
+			return Err(format!("the walker is out of its line (at {walker_x})"));
+		}
+		let cave = state.cave;
+		let mut crystals = CrystalGrowth::new(cave.map_wh, CrystalSpecies::default_species());
+		crystals.set_cells_from_rows(&state.crystals)?;
+		let in_cave = |xy: (u32, u32)| {
+			if xy.0 < cave.map_wh.0 && xy.1 < cave.map_wh.1 {
+				Ok(xy)
+			} else {
+				Err(format!("{xy:?} is out of the cave"))
+			}
+		};
+		// The entities must be where they could have moved to, and not on each other.
+		let walkable_in_cave = |xy: (u32, u32), entities: Option<&Entities>| {
+			let xy = in_cave(xy)?;
+			if !cave.terrain(xy).is_passable() {
+				Err(format!("an entity is stuck in the terrain at {xy:?}"))
+			} else if entities.is_some_and(|entities| entities.is_occupied(xy)) {
+				Err(format!("two entities are at {xy:?}"))
+			} else {
+				Ok(xy)
+			}
+		};
+		let player_xy = walkable_in_cave(state.player, None)?;
+		let mut entities = Entities::new(Entity::player(player_xy));
+		for mite_xy in state.mites {
+			let mite_xy = walkable_in_cave(mite_xy, Some(&entities))?;
+			entities.creatures.push(Entity::mite(mite_xy));
+		}
+		let cave_entrance_xy = in_cave(state.cave_entrance)?;
 
-impl ScreenGrid {
-	fn new(grid_wh: (u32, u32), tile_wh: (u32, u32)) -> ScreenGrid {
-		let tiles = std::iter::repeat(ScreenTile::new())
-			.take((grid_wh.0 * grid_wh.1) as usize)
-			.collect();
-		ScreenGrid { tiles, grid_wh, tile_wh }
+		self.seed = seed;
+		// The names were the first thing that came from the seed.
+		set_name_variables(&self.name_generator, &mut Rng::new(seed));
+		self.rng = state.rng_state;
+		self.iteration_number = state.ticks;
+		self.play_time = play_time;
+		self.walker_x = walker_x;
+		self.walker_slide = None;
+		self.entities = entities;
+		self.cave_entrance_xy = cave_entrance_xy;
+		self.cave_visibility = Visibility::new(cave.map_wh);
+		self.cave = cave;
+		self.crystals = crystals;
+		Ok(())
 	}
+}
 
-	fn resize_grid(&mut self, new_grid_wh: (u32, u32)) {
-		self.grid_wh = new_grid_wh;
-		self.tiles = std::iter::repeat(ScreenTile::new())
-			.take((self.grid_wh.0 * self.grid_wh.1) as usize)
-			.collect();
+impl Game for Demo {
+	fn is_in_danger(&self) -> bool {
+		self.entities
+			.creatures
+			.iter()
+			.any(|creature| self.cave_visibility.is_visible(creature.xy))
 	}
 
-	fn tile_index(&self, xy: (u32, u32)) -> usize {
-		assert!(xy.0 < self.grid_wh.0);
-		assert!(xy.1 < self.grid_wh.1);
-		(xy.0 * self.grid_wh.1 + xy.1) as usize
+	fn handle_event(&mut self, event: &Event) {
+		if self.ui_tree.handle_event(event) {
+			match self.menu_choice.take() {
+				Some(0) => self.save(),
+				Some(1) => self.load(),
+				Some(2) => self.is_over = true,
+				_ => {},
+			}
+			let display_change = match self.options_choice.take() {
+				Some(0) => Some(DisplayChange::ToggleFullscreen),
+				Some(1) => Some(DisplayChange::ToggleVsync),
+				Some(2) => Some(DisplayChange::NextScaling),
+				Some(3) => Some(DisplayChange::NextTileset),
+				_ => None,
+			};
+			self.display_changes.extend(display_change);
+			return;
+		}
+		if self.message_log.handle_event(event) {
+			return;
+		}
+		let player_move = match input::action_for_event(event) {
+			Some(Action::Quit) => {
+				self.is_over = true;
+				return;
+			},
+			Some(Action::TogglePause) => {
+				self.is_growth_paused = !self.is_growth_paused;
+				return;
+			},
+			Some(Action::Save) => {
+				self.save();
+				return;
+			},
+			Some(Action::Load) => {
+				self.load();
+				return;
+			},
+			Some(Action::MoveNorth) => (0, -1),
+			Some(Action::MoveEast) => (1, 0),
+			Some(Action::MoveSouth) => (0, 1),
+			Some(Action::MoveWest) => (-1, 0),
+			None => return,
+		};
+		if !self.entities.move_player(&self.cave, player_move) {
+			let player_xy = self.entities.player.xy;
+			let to_xy = (
+				player_xy.0.wrapping_add_signed(player_move.0),
+				player_xy.1.wrapping_add_signed(player_move.1),
+			);
+			self.message_log.push(if self.entities.is_occupied(to_xy) {
+				msg!("demo.log_mite_in_the_way")
+			} else {
+				msg!("demo.log_bump")
+			});
+		}
 	}
 
-	fn tile(&self, xy: (u32, u32)) -> &ScreenTile {
-		let tile_index = self.tile_index(xy);
-		&self.tiles[tile_index]
+	fn update(&mut self, dt: std::time::Duration) {
+		// The telnet sessions play too, with the keys they send.
+		if let Some(telnet_server) = &mut self.telnet_server {
+			for event in telnet_server.take_key_events() {
+				self.handle_event(&event);
+			}
+		}
+		self.iteration_number += 1;
+		self.play_time += dt;
+		message::set_variable("play_time", format_play_time(self.play_time).as_str());
+		message::set_variable("hum_count", self.iteration_number / 60);
+		if !self.is_growth_paused {
+			let entities = &self.entities;
+			self.crystals
+				.step(&mut self.cave, &mut self.rng, |xy| entities.is_occupied(xy));
+		}
+		if self.iteration_number.is_multiple_of(20) {
+			self.entities.wander_creatures(&self.cave, &mut self.rng);
+		}
+		if self.iteration_number.is_multiple_of(12) {
+			let new_walker_x = 1 + (self.walker_x % 26);
+			self.walker_slide = (new_walker_x == self.walker_x + 1)
+				.then(|| Slide::new((self.walker_x, 3), (new_walker_x, 3)));
+			self.walker_x = new_walker_x;
+		}
 	}
 
-	fn tile_mut(&mut self, xy: (u32, u32)) -> &mut ScreenTile {
-		let tile_index = self.tile_index(xy);
-		&mut self.tiles[tile_index]
-	}
+	fn draw(&mut self, screen_grid: &mut ScreenGrid, _tick_progress: f32) {
+		// The letters shine like crystals, with their hues going round.
+		let crystal_color = theme::color("crystal").unwrap_or_else(theme::text_color);
+		let mut shining_letters = RichText::from("");
+		for (i, letter) in ('a'..='z').enumerate() {
+			let angle = (i as f32 * 14.0) + self.iteration_number as f32 * 2.0;
+			shining_letters += RichText::from(letter.to_string())
+				.fg_color(color::rotate_hue(crystal_color, angle));
+		}
+		screen_grid.darw_text(shining_letters, (1, 1));
+		screen_grid.darw_text(
+			RichText::from("abcdef")
+				+ RichText::from("ghijkl").fg_color(Color::RGB(240, 40, 5))
+				+ RichText::from("mnopqr").bg_color(Color::RGB(10, 40, 150))
+				+ RichText::from("stuvwx")
+					.fg_color(Color::RGB(240, 40, 5))
+					.bg_color(Color::RGB(10, 40, 150))
+				+ (RichText::from("y") + RichText::from("z")).fg_color(Color::RGB(10, 210, 40)),
+			(1, 2),
+		);
 
-	fn clear(&mut self) {
-		self.tiles = std::iter::repeat(ScreenTile::new())
-			.take((self.grid_wh.0 * self.grid_wh.1) as usize)
-			.collect();
-	}
+		// A zone to beware of, on the way of the `@`.
+		for x in 20..27 {
+			screen_grid.tile_mut((x, 3)).bg_pattern =
+				Some((BgPattern::DiagonalStripes, Color::RGB(90, 20, 10)));
+		}
+		let tile_wh = screen_grid.tile_wh;
+		let walker_tile = screen_grid.tile_mut((self.walker_x, 3));
+		walker_tile.sprite = Sprite::Char('@');
+		if let Some(walker_slide) = &self.walker_slide {
+			walker_tile.sprite_offset = walker_slide.sprite_offset(tile_wh);
+		}
+		// A sparkle runs along the shining letters, leaving a trail behind.
+		if self.effect_layers.grid_wh != screen_grid.grid_wh {
+			self.effect_layers.resize(screen_grid.grid_wh);
+		}
+		self.effect_layers.clear();
+		let sparkle_x = 1 + (self.iteration_number / 6) % 26;
+		for trail_x in sparkle_x.saturating_sub(3).max(1)..sparkle_x {
+			let mut trail_tile = ScreenTile::from_char('.');
+			trail_tile.fg_color = crystal_color;
+			*self.effect_layers.tile_mut(0, (trail_x, 1)) = Some(trail_tile);
+		}
+		let mut sparkle_tile = ScreenTile::from_char('*');
+		sparkle_tile.fg_color = Color::WHITE;
+		*self.effect_layers.tile_mut(1, (sparkle_x, 1)) = Some(sparkle_tile);
+		self.effect_layers.composite_into(screen_grid);
+		// The lines above the UI are like a map seen by the `@` with a torch.
+		screen_grid.apply_fog(
+			(self.walker_x, 3),
+			Rect::new(0, 0, screen_grid.grid_wh.0, 5),
+		);
 
-	fn grid_coords_to_rect(&self, xy: (u32, u32)) -> Rect {
-		Rect::new(
-			(xy.0 * self.tile_wh.0) as i32,
-			(xy.1 * self.tile_wh.1) as i32,
-			self.tile_wh.0,
-			self.tile_wh.1,
-		)
-	}
+		// A bar filling up between two hums, from red to green.
+		let bar_ramp = Ramp::new(vec![
+			(0.0, Color::RGB(200, 30, 20)),
+			(0.5, Color::RGB(220, 200, 30)),
+			(1.0, Color::RGB(40, 200, 60)),
+		]);
+		let bar_fill = (self.iteration_number % 60) as f32 / 60.0;
+		let bar_length = 26;
+		let bar_color = bar_ramp.sample(bar_fill);
+		for i in 0..bar_length {
+			let tile = screen_grid.tile_mut((1 + i, 4));
+			tile.sprite = Sprite::Index(if (i as f32) < bar_fill * bar_length as f32 {
+				219
+			} else {
+				176
+			});
+			tile.fg_color = bar_color;
+		}
+
+		// The UI is on the left half, and the cave on the right half.
+		let half_width = screen_grid.grid_wh.0 / 2;
+		self.ui_tree.set_rect(Rect::new(
+			1,
+			5,
+			half_width.saturating_sub(2),
+			screen_grid.grid_wh.1.saturating_sub(5),
+		));
+		self.ui_tree.draw(screen_grid);
+		// The message log takes the bottom of the right half.
+		let message_log_height = 5;
+		let right_width = (screen_grid.grid_wh.0 - half_width).saturating_sub(1);
+		let below_ui_height = screen_grid.grid_wh.1.saturating_sub(6);
+		self.camera.view_rect = Rect::new(
+			half_width as i32,
+			5,
+			right_width,
+			below_ui_height.saturating_sub(message_log_height + 1),
+		);
+		self.message_log.layout(Rect::new(
+			half_width as i32,
+			self.camera.view_rect.bottom() + 1,
+			right_width,
+			message_log_height.min(below_ui_height),
+		));
+		let player_xy = self.entities.player.xy;
+		self.camera
+			.center_on(&self.cave, (player_xy.0 as i32, player_xy.1 as i32));
 
-	fn draw_to_canvas<T: RenderTarget>(
-		&self,
-		canvas: &mut Canvas<T>,
-		char_sprite_sheet: &mut CharSpriteSheet,
-	) {
-		for y in 0..self.grid_wh.1 {
-			for x in 0..self.grid_wh.0 {
-				let xy = (x, y);
-				let dst = self.grid_coords_to_rect((x, y));
-
-				// Fill the tile with the background.
-				let bg_color = self.tile(xy).bg_color;
-				canvas.set_draw_color(bg_color);
-				canvas.fill_rect(dst).unwrap();
-
-				// Draw the sprite after the background so that it is on the foreground.
-				let sprite = self.tile(xy).sprite;
-				let fg_color = self.tile(xy).fg_color;
-				char_sprite_sheet.draw_char_to_canvas(sprite, canvas, fg_color, dst);
+		self.camera.draw_map(&self.cave, screen_grid);
+		self.crystals.draw(&self.camera, screen_grid);
+		let way_back = path::find_path(
+			&self.cave,
+			player_xy,
+			self.cave_entrance_xy,
+			path::walking_cost,
+		);
+		for &xy in way_back.iter().flatten().skip(1) {
+			let screen_xy = self.camera.map_xy_to_screen_xy((xy.0 as i32, xy.1 as i32));
+			if let Some(screen_xy) =
+				screen_xy.filter(|&(x, y)| x < screen_grid.grid_wh.0 && y < screen_grid.grid_wh.1)
+			{
+				let tile = screen_grid.tile_mut(screen_xy);
+				tile.sprite = Sprite::Index(250);
+				tile.fg_color = Color::RGB(250, 220, 120);
 			}
 		}
+		self.cave_visibility.update(&self.cave, player_xy, 10);
+		self.cave_visibility
+			.apply_to_view(&self.camera, screen_grid);
+		let cave_visibility = &self.cave_visibility;
+		self.entities.draw(&self.camera, screen_grid, |xy| {
+			cave_visibility.is_visible(xy)
+		});
+		self.message_log.draw(screen_grid);
+
+		// The tooltip is above the hovered tile, or below it on the first line.
+		let tooltip = self
+			.hovered_grid_xy
+			.and_then(|xy| Some((xy, self.describe_cave_tile_at(xy)?)));
+		if let Some((hovered_xy, description)) = tooltip {
+			let tiles = (RichText::from(" ") + description + RichText::from(" "))
+				.bg_color(theme::notice_color())
+				.tiles();
+			let y = hovered_xy.1.checked_sub(1).unwrap_or(hovered_xy.1 + 1);
+			let x = hovered_xy
+				.0
+				.min(screen_grid.grid_wh.0.saturating_sub(tiles.len() as u32));
+			if y < screen_grid.grid_wh.1 {
+				for (i, tile) in tiles.iter().enumerate() {
+					if x + (i as u32) < screen_grid.grid_wh.0 {
+						*screen_grid.tile_mut((x + i as u32, y)) = *tile;
+					}
+				}
+			}
+		}
+
+		if let Some(spectate_server) = &mut self.spectate_server {
+			spectate_server.send_frame(screen_grid);
+		}
+		if let Some(telnet_server) = &mut self.telnet_server {
+			telnet_server.send_frame(screen_grid);
+		}
 	}
-}
 
-#[derive(Clone, Copy)]
-enum RichTextModifier {
-	FgColor(Color),
-	BgColor(Color),
-}
+	fn draw_debug(&mut self, screen_grid: &mut ScreenGrid) {
+		let lines = [
+			msg!("demo.debug_seed", seed => self.seed),
+			msg!("demo.debug_iteration", iteration => self.iteration_number),
+			msg!("demo.debug_play_time", play_time => format_play_time(self.play_time)),
+		];
+		for (y, line) in lines.into_iter().enumerate() {
+			draw_text_clipped(screen_grid, line, (0, 2 + y as u32));
+		}
+	}
 
-#[derive(Clone)]
-enum RichText {
-	Text(String),
-	Modifier(RichTextModifier, Box<RichText>),
-	Sequence(Vec<RichText>),
-}
+	fn handle_grid_mouse_event(&mut self, event: GridMouseEvent) {
+		match event {
+			GridMouseEvent::Hover(grid_xy) => self.hovered_grid_xy = grid_xy,
+			GridMouseEvent::Click { grid_xy, button: MouseButton::Left } => {
+				if let Some(description) = self.describe_cave_tile_at(grid_xy) {
+					self.message_log
+						.push(msg!("demo.log_inspect", thing => description));
+				}
+			},
+			GridMouseEvent::Click { .. } => {},
+		}
+	}
 
-impl<T> From<T> for RichText
-where
-	T: Into<String>,
-{
-	fn from(string: T) -> Self {
-		RichText::Text(string.into())
+	fn take_display_changes(&mut self) -> Vec<DisplayChange> {
+		std::mem::take(&mut self.display_changes)
 	}
-}
 
-impl RichText {
-	fn fg_color(self, color: Color) -> RichText {
-		RichText::Modifier(RichTextModifier::FgColor(color), Box::new(self))
+	fn take_toast(&mut self) -> Option<RichText> {
+		self.toast.take()
 	}
 
-	fn bg_color(self, color: Color) -> RichText {
-		RichText::Modifier(RichTextModifier::BgColor(color), Box::new(self))
+	fn is_over(&self) -> bool {
+		self.is_over
 	}
 }
 
-impl std::ops::Add<RichText> for RichText {
-	type Output = RichText;
+/// Like `1:02:03` for an hour, two minutes and three seconds.
+fn format_play_time(play_time: std::time::Duration) -> String {
+	let seconds = play_time.as_secs();
+	format!(
+		"{}:{:02}:{:02}",
+		seconds / 3600,
+		seconds / 60 % 60,
+		seconds % 60
+	)
+}
+
+/// Shows what another game (that runs a spectate server) shows, instead of playing.
+struct Spectator {
+	spectate_client: SpectateClient,
+	/// What the spectated game shows, kept from frame to frame as only the changes arrive.
+	spectated_grid: ScreenGrid,
+	is_connected: bool,
+	is_over: bool,
+}
 
-	fn add(self, rhs: RichText) -> RichText {
-		match self {
-			RichText::Sequence(mut vec) => RichText::Sequence({
-				vec.push(rhs);
-				vec
-			}),
-			lhs => RichText::Sequence(vec![lhs, rhs]),
+impl Spectator {
+	fn new(spectate_client: SpectateClient, tile_wh: (u32, u32)) -> Spectator {
+		Spectator {
+			spectate_client,
+			spectated_grid: ScreenGrid::new((0, 0), tile_wh),
+			is_connected: true,
+			is_over: false,
 		}
 	}
 }
 
-impl std::ops::AddAssign<RichText> for RichText {
-	fn add_assign(&mut self, rhs: RichText) {
-		match self {
-			RichText::Sequence(ref mut vec) => vec.push(rhs),
-			ref lhs => {
-				*self = RichText::Sequence(vec![(*lhs).to_owned(), rhs]);
-			},
+impl Game for Spectator {
+	fn handle_event(&mut self, event: &Event) {
+		if input::action_for_event(event) == Some(Action::Quit) {
+			self.is_over = true;
 		}
 	}
-}
 
-impl RichText {
-	fn tiles(&self) -> Vec<ScreenTile> {
-		fn tiles_rec(
-			formatted_text: &RichText,
-			tiles: &mut Vec<ScreenTile>,
-			modifiers: &mut Vec<RichTextModifier>,
-		) {
-			match formatted_text {
-				RichText::Text(string) => {
-					tiles.append(
-						&mut string
-							.chars()
-							.map(|character| {
-								let mut tile = ScreenTile::from_char(character);
-								for modifier in modifiers.iter() {
-									match *modifier {
-										RichTextModifier::BgColor(bg_color) => {
-											tile.bg_color = bg_color
-										},
-										RichTextModifier::FgColor(fg_color) => {
-											tile.fg_color = fg_color
-										},
-									}
-								}
-								tile
-							})
-							.collect(),
-					);
-				},
-				RichText::Modifier(modifier, sub_formatted_text) => {
-					modifiers.push(*modifier);
-					tiles_rec(&sub_formatted_text, tiles, modifiers);
-					modifiers.pop();
-				},
-				RichText::Sequence(vec) => {
-					for sub_formatted_text in vec.iter() {
-						tiles_rec(&sub_formatted_text, tiles, modifiers);
+	fn update(&mut self, _dt: std::time::Duration) {
+		for update in self.spectate_client.updates() {
+			match update {
+				SpectateUpdate::Resize(grid_wh) => self.spectated_grid.resize_grid(grid_wh),
+				SpectateUpdate::Frame(tiles) => {
+					for (xy, tile) in tiles {
+						if xy.0 < self.spectated_grid.grid_wh.0
+							&& xy.1 < self.spectated_grid.grid_wh.1
+						{
+							*self.spectated_grid.tile_mut(xy) = tile;
+						}
 					}
 				},
+				SpectateUpdate::Disconnected => self.is_connected = false,
 			}
 		}
-
-		let mut tiles = Vec::new();
-		let mut modifiers = Vec::new();
-		tiles_rec(self, &mut tiles, &mut modifiers);
-		tiles
 	}
-}
 
-impl ScreenGrid {
-	fn darw_text(&mut self, text: RichText, dst_xy: (u32, u32)) {
-		for (i, formatted_tile) in text.tiles().iter().enumerate() {
-			let tile = self.tile_mut((dst_xy.0 + i as u32, dst_xy.1));
-			*tile = *formatted_tile;
+	fn draw(&mut self, screen_grid: &mut ScreenGrid, _tick_progress: f32) {
+		*screen_grid = self.spectated_grid.snapshot();
+		if !self.is_connected {
+			let text = RichText::from(tr!("spectate.disconnected")).bg_color(theme::number_color());
+			if text.tiles().len() as u32 <= screen_grid.grid_wh.0 {
+				screen_grid.darw_text(text, (0, 0));
+			}
 		}
 	}
-}
 
-struct Game {
-	sdl_context: sdl2::Sdl,
-	_video_subsystem: sdl2::VideoSubsystem,
-	_sdl_image_context: sdl2::image::Sdl2ImageContext,
-	window_canvas: Canvas<Window>,
-	char_sprite_sheet: CharSpriteSheet,
-	screen_grid: ScreenGrid,
-	iteration_number: u32,
+	fn grid_cursor(&self) -> GridCursor {
+		// Nothing to point at in someone else's game.
+		GridCursor::System
+	}
+
+	fn is_over(&self) -> bool {
+		self.is_over
+	}
 }
 
-impl Game {
-	fn new() -> Game {
-		let sdl_context = sdl2::init().unwrap();
-		let video_subsystem = sdl_context.video().unwrap();
-		let sdl_image_context = sdl2::image::init(sdl2::image::InitFlag::all()).unwrap();
-
-		let mut window_canvas = video_subsystem
-			.window("Why Crystals ?", 1200, 600)
-			.position_centered()
-			.maximized()
-			.resizable()
-			.build()
-			.unwrap()
-			.into_canvas()
-			.present_vsync()
-			.accelerated()
-			.build()
-			.unwrap();
-		window_canvas.set_blend_mode(BlendMode::Blend);
-		let texture_creator = window_canvas.texture_creator();
-
-		// You can get more of these from
-		// [the Dwarf Fortress wiki tileset repo](https://dwarffortresswiki.org/Tileset_repository).
-		let char_sprite_sheet_filepath = "assets/Pastiche_8x8.png";
-		let char_sprite_sheet_tile_wh = (8, 8);
-
-		let char_sprite_sheet = CharSpriteSheet::from_filepath(
-			char_sprite_sheet_filepath,
-			char_sprite_sheet_tile_wh,
-			&texture_creator,
-		);
+/// Renders a big grid full of random tiles and text for `frame_count` frames
+/// as fast as possible, then prints how much time each stage of a frame took.
+fn bench(engine: &mut Engine, frame_count: u32) {
+	let grid_wh = engine.screen_grid.grid_wh;
+	let mut rng = Rng::new(0);
+	let random_color = |rng: &mut Rng| {
+		let rgb = rng.next_u32();
+		Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+	};
+
+	let stage_names = [
+		"randomize tiles",
+		"draw text",
+		"fog",
+		"draw to canvas",
+		"present",
+	];
+	let mut stage_durations = [std::time::Duration::ZERO; 5];
+	let mut stage_max_durations = [std::time::Duration::ZERO; 5];
+	let mut time_stage = |stage: usize, start: &mut std::time::Instant| {
+		let duration = start.elapsed();
+		stage_durations[stage] += duration;
+		stage_max_durations[stage] = stage_max_durations[stage].max(duration);
+		*start = std::time::Instant::now();
+	};
+
+	let bench_start = std::time::Instant::now();
+	let mut frames_done = 0;
+	'benchloop: for frame_index in 0..frame_count {
+		for event in engine.event_pump.poll_iter() {
+			if matches!(event, Event::Quit { .. })
+				|| input::action_for_event(&event) == Some(Action::Quit)
+			{
+				break 'benchloop;
+			}
+		}
 
-		let screen_grid = ScreenGrid::new((30, 30), (16, 16));
+		let mut stage_start = std::time::Instant::now();
 
-		let iteration_number: u32 = 0;
+		for tile in engine.screen_grid.tiles.iter_mut() {
+			tile.sprite = Sprite::Index(rng.below(256));
+			tile.fg_color = random_color(&mut rng);
+			tile.bg_color = random_color(&mut rng);
+		}
+		time_stage(0, &mut stage_start);
 
-		Game {
-			sdl_context,
-			_video_subsystem: video_subsystem,
-			_sdl_image_context: sdl_image_context,
-			window_canvas,
-			char_sprite_sheet,
-			screen_grid,
-			iteration_number,
+		// A paragraph of styled lines drifting down the grid, over the random tiles.
+		// A bit more than the length of the text lines.
+		let line_length = 64;
+		let x = frame_index % (grid_wh.0 - line_length);
+		for i in 0..40 {
+			let y = (frame_index + i * 2) % grid_wh.1;
+			let text = RichText::from("Why crystals ? ")
+				+ RichText::from("Because ").fg_color(random_color(&mut rng))
+				+ RichText::from("jungle").bg_color(random_color(&mut rng))
+				+ RichText::from(" and caves and stuff, ")
+					.fg_color(random_color(&mut rng))
+					.bg_color(random_color(&mut rng))
+				+ RichText::from("that's why.");
+			engine.screen_grid.darw_text(text, (x, y));
 		}
-	}
+		time_stage(1, &mut stage_start);
 
-	fn run(&mut self) {
-		let mut event_pump = self.sdl_context.event_pump().unwrap();
-		'gameloop: loop {
-			self.iteration_number += 1;
-
-			for event in event_pump.poll_iter() {
-				match event {
-					Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-						break 'gameloop;
-					},
-					Event::Window { win_event: WindowEvent::Resized(new_w, new_h), .. } => {
-						self.screen_grid.resize_grid((
-							new_w as u32 / self.screen_grid.tile_wh.0,
-							new_h as u32 / self.screen_grid.tile_wh.1,
-						));
-					},
-					/*
-					Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
-						obj_table.get_mut(player_id).unwrap().loc_mut().xy.1 -= 1;
-					},
-					Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
-						obj_table.get_mut(player_id).unwrap().loc_mut().xy.0 += 1;
-					},
-					Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
-						obj_table.get_mut(player_id).unwrap().loc_mut().xy.1 += 1;
-					},
-					Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
-						obj_table.get_mut(player_id).unwrap().loc_mut().xy.0 -= 1;
-					},
-					*/
-					_ => {},
-				}
-			}
+		let center_xy = (grid_wh.0 / 2, grid_wh.1 / 2);
+		engine
+			.screen_grid
+			.apply_fog(center_xy, Rect::new(0, 0, grid_wh.0, grid_wh.1));
+		time_stage(2, &mut stage_start);
 
-			self.window_canvas.set_draw_color(COLOR_BG);
-			self.window_canvas.clear();
-
-			self.screen_grid.clear();
-
-			self.screen_grid
-				.darw_text("abcdefghijklmnopqrstuvwxyz".into(), (1, 1));
-			self.screen_grid.darw_text(
-				RichText::from("abcdef")
-					+ RichText::from("ghijkl").fg_color(Color::RGB(240, 40, 5))
-					+ RichText::from("mnopqr").bg_color(Color::RGB(10, 40, 150))
-					+ RichText::from("stuvwx")
-						.fg_color(Color::RGB(240, 40, 5))
-						.bg_color(Color::RGB(10, 40, 150))
-					+ (RichText::from("y") + RichText::from("z")).fg_color(Color::RGB(10, 210, 40)),
-				(1, 2),
-			);
+		engine
+			.screen_grid
+			.draw_to_canvas(&mut engine.window_canvas, &mut engine.char_sprite_sheets);
+		time_stage(3, &mut stage_start);
 
-			self.screen_grid
-				.tile_mut((1 + self.iteration_number as u32 % 26, 3))
-				.sprite = '@' as SpriteIndex;
+		engine.window_canvas.present();
+		time_stage(4, &mut stage_start);
 
-			self.screen_grid
-				.draw_to_canvas(&mut self.window_canvas, &mut self.char_sprite_sheet);
+		frames_done += 1;
+	}
+	let total_duration = bench_start.elapsed();
 
-			self.window_canvas.present();
+	println!(
+		"Benchmark: {frames_done} frames of a {}x{} grid in {:.3} s ({:.1} frames per second)",
+		grid_wh.0,
+		grid_wh.1,
+		total_duration.as_secs_f64(),
+		frames_done as f64 / total_duration.as_secs_f64(),
+	);
+	println!(
+		"Dimming: {} light (see \"--linear-light\")",
+		if display::linear_light() {
+			"linear"
+		} else {
+			"direct"
 		}
+	);
+	println!(
+		"{:<16} {:>10} {:>14} {:>14}",
+		"stage", "total (ms)", "mean (ms)", "max (ms)"
+	);
+	for stage in 0..stage_names.len() {
+		println!(
+			"{:<16} {:>10.1} {:>14.3} {:>14.3}",
+			stage_names[stage],
+			stage_durations[stage].as_secs_f64() * 1000.0,
+			stage_durations[stage].as_secs_f64() * 1000.0 / frames_done.max(1) as f64,
+			stage_max_durations[stage].as_secs_f64() * 1000.0,
+		);
 	}
 }
 
 fn main() {
-	Game::new().run();
+	let mut bench_frame_count = None;
+	let mut language = None;
+	let mut extra_sheet_filepaths = Vec::new();
+	let mut seed = None;
+	let mut window_wh = None;
+	let mut fullscreen = false;
+	let mut debug_window = false;
+	let mut backbuffer = false;
+	let mut scaling = Scaling::Resize;
+	let mut margin_color = Color::RGB(0, 0, 0);
+	let mut fallback_char = '?';
+	let mut theme_name = None;
+	let mut colorblind_mode = None;
+	let mut brightness = 1.0;
+	let mut smooth_movement = true;
+	let mut linear_light = false;
+	let mut fog = display::fog();
+	let mut gamma = 1.0;
+	let mut spectate_server_address = None;
+	let mut spectate_address = None;
+	let mut telnet_server_address = None;
+	let mut config_path = None;
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--bench" => bench_frame_count = Some(600),
+			"--bench-frames" => {
+				let frame_count = args.next().and_then(|value| value.parse().ok());
+				let Some(frame_count) = frame_count else {
+					eprintln!("Expected a number of frames after \"--bench-frames\"");
+					std::process::exit(1);
+				};
+				bench_frame_count = Some(frame_count);
+			},
+			"--lang" => {
+				let Some(value) = args.next() else {
+					eprintln!("Expected a language (like \"en\") after \"--lang\"");
+					std::process::exit(1);
+				};
+				language = Some(value);
+			},
+			"--seed" => {
+				let Some(value) = args.next().and_then(|value| value.parse().ok()) else {
+					eprintln!("Expected a number after \"--seed\"");
+					std::process::exit(1);
+				};
+				seed = Some(value);
+			},
+			"--window-size" => {
+				let value = args.next();
+				let wh = value
+					.as_deref()
+					.and_then(|value| value.split_once('x'))
+					.and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+				let Some(wh) = wh else {
+					eprintln!(
+						"Expected a size in pixels (like \"1200x600\") after \"--window-size\""
+					);
+					std::process::exit(1);
+				};
+				window_wh = Some(wh);
+			},
+			"--theme" => {
+				let Some(value) = args.next() else {
+					eprintln!("Expected a theme name (like \"amber\") after \"--theme\"");
+					std::process::exit(1);
+				};
+				theme_name = Some(value);
+			},
+			"--colorblind" => {
+				let mode = args.next().as_deref().and_then(ColorblindMode::from_name);
+				let Some(mode) = mode else {
+					eprintln!(
+						"Expected \"protanopia\", \"deuteranopia\", \"tritanopia\" or \"none\" \
+						after \"--colorblind\""
+					);
+					std::process::exit(1);
+				};
+				colorblind_mode = Some(mode);
+			},
+			"--brightness" | "--gamma" => {
+				let value = args.next().and_then(|value| value.parse::<f32>().ok());
+				let Some(value) = value.filter(|value| *value > 0.0) else {
+					eprintln!("Expected a positive number (like \"1.5\") after \"{arg}\"");
+					std::process::exit(1);
+				};
+				if arg == "--brightness" {
+					brightness = value;
+				} else {
+					gamma = value;
+				}
+			},
+			"--no-smooth-movement" => smooth_movement = false,
+			"--no-fog" => fog = None,
+			"--assets-dir" => {
+				let Some(dir) = args.next() else {
+					eprintln!("Expected a directory path after \"--assets-dir\"");
+					std::process::exit(1);
+				};
+				assets::set_override_dir(dir);
+			},
+			"--narrate" => narration::set_enabled(true),
+			"--linear-light" => linear_light = true,
+			"--fog-falloff" => {
+				let value = args.next().and_then(|value| value.parse::<f32>().ok());
+				let Some(value) = value.filter(|value| *value > 0.0) else {
+					eprintln!("Expected a positive number (like \"2\") after \"--fog-falloff\"");
+					std::process::exit(1);
+				};
+				if let Some(fog) = &mut fog {
+					fog.falloff_exponent = value;
+				}
+			},
+			"--fullscreen" => fullscreen = true,
+			"--debug-window" => debug_window = true,
+			"--backbuffer" => backbuffer = true,
+			"--scaling" => {
+				let Some(new_scaling) = args.next().as_deref().and_then(Scaling::from_name) else {
+					eprintln!(
+						"Expected \"resize\", \"integer\", \"stretch\" or \"letterbox\" \
+						after \"--scaling\""
+					);
+					std::process::exit(1);
+				};
+				scaling = new_scaling;
+			},
+			"--margin-color" => {
+				let color = args.next().as_deref().and_then(markup::parse_hex_color);
+				let Some(color) = color else {
+					eprintln!("Expected a color (like \"101010\") after \"--margin-color\"");
+					std::process::exit(1);
+				};
+				margin_color = color;
+			},
+			"--fallback-char" => {
+				let fallback = args.next().filter(|value| value.chars().count() == 1);
+				let Some(fallback) = fallback else {
+					eprintln!("Expected a character (like \"?\") after \"--fallback-char\"");
+					std::process::exit(1);
+				};
+				fallback_char = fallback.chars().next().unwrap();
+			},
+			"--spectate-server" | "--spectate" | "--telnet-server" => {
+				let Some(value) = args.next() else {
+					eprintln!("Expected an address (like \"localhost:7777\") after \"{arg}\"");
+					std::process::exit(1);
+				};
+				match arg.as_str() {
+					"--spectate-server" => spectate_server_address = Some(value),
+					"--spectate" => spectate_address = Some(value),
+					_ => telnet_server_address = Some(value),
+				}
+			},
+			"--config" => {
+				let Some(value) = args.next() else {
+					eprintln!("Expected a TOML file path after \"--config\"");
+					std::process::exit(1);
+				};
+				config_path = Some(value);
+			},
+			"--extra-sheet" => {
+				let Some(value) = args.next() else {
+					eprintln!("Expected a PNG file path after \"--extra-sheet\"");
+					std::process::exit(1);
+				};
+				extra_sheet_filepaths.push(value);
+			},
+			unknown => {
+				eprintln!("Unknown argument \"{unknown}\"");
+				std::process::exit(1);
+			},
+		}
+	}
+
+	let config_path = config_path.unwrap_or_else(|| config::DEFAULT_CONFIG_PATH.to_string());
+	let config = Config::load(std::path::Path::new(&config_path)).unwrap_or_else(|error| {
+		eprintln!("Could not load the configuration \"{config_path}\": {error}");
+		std::process::exit(1);
+	});
+	// The arguments take precedence over the configuration.
+	let window_wh = window_wh.or(config.window_wh);
+	let theme_name = theme_name.or(config.theme);
+
+	let seed = seed.unwrap_or_else(|| {
+		let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+		now.unwrap().as_nanos() as u64
+	});
+	localization::set_language(&language.unwrap_or_else(localization::system_language));
+	theme::load_themes("data/themes.toml").unwrap_or_else(|error| {
+		eprintln!("Could not load the themes: {error}");
+		std::process::exit(1);
+	});
+	input::load_keybindings("data/keybindings.toml").unwrap_or_else(|error| {
+		eprintln!("Could not load the keybindings: {error}");
+		std::process::exit(1);
+	});
+	if let Some(colorblind_mode) = colorblind_mode {
+		display::set_colorblind_mode(colorblind_mode);
+	}
+	display::set_brightness_and_gamma(brightness, gamma);
+	display::set_smooth_movement(smooth_movement);
+	display::set_fog(fog);
+	display::set_linear_light(linear_light);
+	if let Some(theme_name) = theme_name {
+		theme::set_theme(&theme_name).unwrap_or_else(|error| {
+			eprintln!("Could not use the theme: {error}");
+			std::process::exit(1);
+		});
+	}
+
+	let mut engine_builder = EngineBuilder::new()
+		.title(&config.title.unwrap_or_else(|| tr!("window.title")))
+		.tileset(&config.tileset, config.tileset_tile_wh)
+		.alternate_tilesets(&config.alternate_tilesets)
+		.watch_tileset(config.watch_tileset)
+		.tileset_transparency(config.tileset_transparency)
+		.tile_wh(config.tile_wh)
+		.extra_sheets(&extra_sheet_filepaths)
+		.fullscreen(fullscreen)
+		.debug_window(debug_window)
+		.backbuffer(backbuffer)
+		.scaling(scaling, margin_color)
+		.fallback_char(fallback_char);
+	if let Some(window_wh) = window_wh {
+		engine_builder = engine_builder.window_wh(window_wh);
+	}
+	if let Some(grid_wh) = config.grid_wh {
+		engine_builder = engine_builder.grid_wh(grid_wh);
+	}
+	if bench_frame_count.is_some() {
+		// Without vsync, so that the frame rate is not capped by the display.
+		engine_builder = engine_builder
+			.vsync(false)
+			.grid_wh((200, 100))
+			.tile_wh((8, 8));
+	}
+	let mut engine = engine_builder.build().unwrap_or_else(|error| {
+		eprintln!("Could not set up the window: {error}");
+		std::process::exit(1);
+	});
+
+	if let Some(frame_count) = bench_frame_count {
+		bench(&mut engine, frame_count);
+	} else if let Some(address) = spectate_address {
+		let spectate_client = SpectateClient::connect(&address).unwrap_or_else(|error| {
+			eprintln!("Could not connect to \"{address}\" to spectate: {error}");
+			std::process::exit(1);
+		});
+		let mut spectator = Spectator::new(spectate_client, engine.screen_grid.tile_wh);
+		engine.run(&mut spectator);
+	} else {
+		let name_generator = NameGenerator::from_asset("data/names.toml").unwrap_or_else(|error| {
+			eprintln!("Could not load the name grammars: {error}");
+			std::process::exit(1);
+		});
+		let mut demo = Demo::new(seed, engine.clipboard(), name_generator);
+		if let Some(address) = spectate_server_address {
+			let spectate_server = SpectateServer::start(&address).unwrap_or_else(|error| {
+				eprintln!("Could not start the spectate server on \"{address}\": {error}");
+				std::process::exit(1);
+			});
+			demo.spectate_server = Some(spectate_server);
+		}
+		if let Some(address) = telnet_server_address {
+			let telnet_server = TelnetServer::start(&address).unwrap_or_else(|error| {
+				eprintln!("Could not start the telnet server on \"{address}\": {error}");
+				std::process::exit(1);
+			});
+			demo.telnet_server = Some(telnet_server);
+		}
+		engine.run(&mut demo);
+	}
 }
@@ -0,0 +1,252 @@
+//! Inline markup for text written in data files (like the string catalogs), parsed into
+//! `RichText` when displayed.
+//!
+//! - `[fg=color]...[/fg]` and `[bg=color]...[/bg]` set the foreground or background color
+//!   of the text in between, where the color is either `#rrggbb` or a color name
+//!   (see `named_color`), that follows the theme switches. `[/]` also closes
+//!   the innermost tag.
+//! - `[shadow]...[/shadow]` and `[outline]...[/outline]` give the text in between
+//!   a drop shadow or an outline (see `GlyphStyle`).
+//! - `{name}` is a placeholder, replaced by whatever the caller resolves it to.
+//! - `[[`, `]]`, `{{` and `}}` stand for literal brackets and braces.
+//!
+//! Mistakes (unknown tags, unknown placeholders, unclosed tags) are reported (once) in the log
+//! and left visible in the text, so that they can be noticed and fixed.
+
+use sdl2::pixels::Color;
+
+use crate::devlog::{self, log};
+use crate::{theme, GlyphStyle, RichText, RichTextModifier};
+
+/// The colors of the current theme can be used by name (see `theme`).
+pub fn named_color(name: &str) -> Option<Color> {
+	theme::color(name)
+}
+
+/// Parses the `rrggbb` in `#rrggbb`.
+pub fn parse_hex_color(hex: &str) -> Option<Color> {
+	if hex.len() != 6 {
+		return None;
+	}
+	let rgb = u32::from_str_radix(hex, 16).ok()?;
+	Some(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
+
+fn parse_tag(tag: &str) -> Option<RichTextModifier> {
+	match tag.trim() {
+		"shadow" => return Some(RichTextModifier::GlyphStyle(GlyphStyle::Shadow)),
+		"outline" => return Some(RichTextModifier::GlyphStyle(GlyphStyle::Outline)),
+		_ => {},
+	}
+	let (name, value) = tag.split_once('=')?;
+	let value = value.trim();
+	let is_fg = match name.trim() {
+		"fg" => true,
+		"bg" => false,
+		_ => return None,
+	};
+	if let Some(hex) = value.strip_prefix('#') {
+		let color = parse_hex_color(hex)?;
+		Some(if is_fg {
+			RichTextModifier::FgColor(color)
+		} else {
+			RichTextModifier::BgColor(color)
+		})
+	} else {
+		// The color is looked up when drawing, but it is unknown if it is not in the theme.
+		named_color(value)?;
+		let color_name = value.to_string();
+		Some(if is_fg {
+			RichTextModifier::FgThemeColor(color_name)
+		} else {
+			RichTextModifier::BgThemeColor(color_name)
+		})
+	}
+}
+
+/// Texts are parsed again for every frame that they are drawn in,
+/// so their mistakes are reported only the first time.
+fn warn_once(problem: String) {
+	if devlog::is_first_report(&problem) {
+		log!(Warning, "{problem}");
+	}
+}
+
+/// A tag that is open, with what was parsed since it was opened.
+struct OpenTag {
+	name: String,
+	modifier: Option<RichTextModifier>,
+	pieces: Vec<RichText>,
+}
+
+impl OpenTag {
+	fn into_rich_text(self) -> RichText {
+		let sequence = RichText::Sequence(self.pieces);
+		match self.modifier {
+			Some(modifier) => RichText::Modifier(modifier, Box::new(sequence)),
+			None => sequence,
+		}
+	}
+}
+
+/// Parses the markup into `RichText`, with `resolve_placeholder` giving the text
+/// of each `{name}` placeholder (or `None` if there is no such placeholder).
+pub fn parse(
+	markup: &str,
+	mut resolve_placeholder: impl FnMut(&str) -> Option<RichText>,
+) -> RichText {
+	let mut stack = vec![OpenTag {
+		name: String::new(),
+		modifier: None,
+		pieces: Vec::new(),
+	}];
+	let mut text = String::new();
+	let flush_text = |text: &mut String, stack: &mut Vec<OpenTag>| {
+		if !text.is_empty() {
+			let piece = RichText::from(std::mem::take(text));
+			stack.last_mut().unwrap().pieces.push(piece);
+		}
+	};
+
+	let mut chars = markup.chars().peekable();
+	while let Some(character) = chars.next() {
+		match character {
+			'[' | ']' | '{' | '}' if chars.peek() == Some(&character) => {
+				chars.next();
+				text.push(character);
+			},
+			'[' | '{' => {
+				let closing = if character == '[' { ']' } else { '}' };
+				let mut inside = String::new();
+				let mut is_closed = false;
+				for character in chars.by_ref() {
+					if character == closing {
+						is_closed = true;
+						break;
+					}
+					inside.push(character);
+				}
+				let not_understood = |text: &mut String, what: &str| {
+					warn_once(format!("{what} \"{inside}\" in text \"{markup}\""));
+					text.push(character);
+					text.push_str(&inside);
+					if is_closed {
+						text.push(closing);
+					}
+				};
+
+				if !is_closed {
+					not_understood(&mut text, "Unclosed markup");
+				} else if character == '{' {
+					match resolve_placeholder(inside.trim()) {
+						Some(value) => {
+							flush_text(&mut text, &mut stack);
+							stack.last_mut().unwrap().pieces.push(value);
+						},
+						None => not_understood(&mut text, "Unknown placeholder"),
+					}
+				} else if let Some(closed_name) = inside.strip_prefix('/') {
+					let closes_open_tag = stack.len() > 1
+						&& (closed_name.is_empty() || stack.last().unwrap().name == closed_name);
+					if closes_open_tag {
+						flush_text(&mut text, &mut stack);
+						let open_tag = stack.pop().unwrap();
+						stack
+							.last_mut()
+							.unwrap()
+							.pieces
+							.push(open_tag.into_rich_text());
+					} else {
+						not_understood(&mut text, "Unexpected closing tag");
+					}
+				} else {
+					match parse_tag(&inside) {
+						Some(modifier) => {
+							flush_text(&mut text, &mut stack);
+							let name = inside.split('=').next().unwrap().trim().to_string();
+							stack.push(OpenTag {
+								name,
+								modifier: Some(modifier),
+								pieces: Vec::new(),
+							});
+						},
+						None => not_understood(&mut text, "Unknown tag"),
+					}
+				}
+			},
+			character => text.push(character),
+		}
+	}
+
+	flush_text(&mut text, &mut stack);
+	if stack.len() > 1 {
+		warn_once(format!(
+			"Unclosed tag \"{}\" in text \"{markup}\"",
+			stack.last().unwrap().name
+		));
+	}
+	while stack.len() > 1 {
+		let open_tag = stack.pop().unwrap();
+		stack
+			.last_mut()
+			.unwrap()
+			.pieces
+			.push(open_tag.into_rich_text());
+	}
+	stack.pop().unwrap().into_rich_text()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn no_placeholders(_name: &str) -> Option<RichText> {
+		None
+	}
+
+	#[test]
+	fn placeholders_are_resolved() {
+		let text = parse("Hello, { name }!", |name| {
+			(name == "name").then(|| RichText::from("Zed"))
+		});
+		assert_eq!(text.plain_text(), "Hello, Zed!");
+	}
+
+	#[test]
+	fn doubled_brackets_and_braces_are_literal() {
+		let text = parse("[[a]] {{b}}", no_placeholders);
+		assert_eq!(text.plain_text(), "[a] {b}");
+	}
+
+	#[test]
+	fn tags_apply_to_the_text_in_between() {
+		let tiles = parse("a[fg=#ff8000]b[shadow]c[/][/fg]d", no_placeholders).tiles();
+		let orange = Color::RGB(255, 128, 0);
+		assert_ne!(tiles[0].fg_color, orange);
+		assert_eq!(tiles[1].fg_color, orange);
+		assert_eq!(tiles[2].fg_color, orange);
+		assert_eq!(tiles[2].glyph_style, GlyphStyle::Shadow);
+		assert_ne!(tiles[3].fg_color, orange);
+		assert_ne!(tiles[3].glyph_style, GlyphStyle::Shadow);
+	}
+
+	#[test]
+	fn mistakes_are_left_visible() {
+		let text = parse("[blink]a[/blink] {nope} [fg=#ff", no_placeholders);
+		assert_eq!(text.plain_text(), "[blink]a[/blink] {nope} [fg=#ff");
+	}
+
+	#[test]
+	fn unclosed_tags_are_closed_at_the_end() {
+		let tiles = parse("[outline]ab", no_placeholders).tiles();
+		assert_eq!(tiles.len(), 2);
+		assert_eq!(tiles[1].glyph_style, GlyphStyle::Outline);
+	}
+
+	#[test]
+	fn hex_colors() {
+		assert_eq!(parse_hex_color("0a10ff"), Some(Color::RGB(10, 16, 255)));
+		assert_eq!(parse_hex_color("0a10f"), None);
+		assert_eq!(parse_hex_color("0a10fg"), None);
+	}
+}
@@ -0,0 +1,93 @@
+//! Messages made from translated templates with named placeholders.
+//!
+//! A template like `"{attacker} hits you for {dmg} damage."` (obtained from the string
+//! catalogs, see `localization`) gets its placeholders replaced by the given values with
+//! `msg!("attack.hit", attacker => name, dmg => 5)`, which gives a `RichText` so that values
+//! can be styled (numbers are highlighted, and `RichText` values keep their own style).
+//! Templates can also use game variables (see `set_variable`) and markup (see `markup`).
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::markup;
+use crate::RichText;
+
+/// A value that can replace a placeholder in a message template.
+pub trait MessageArg {
+	fn to_rich_text(&self) -> RichText;
+}
+
+impl MessageArg for RichText {
+	fn to_rich_text(&self) -> RichText {
+		self.clone()
+	}
+}
+
+impl MessageArg for str {
+	fn to_rich_text(&self) -> RichText {
+		RichText::from(self)
+	}
+}
+
+impl MessageArg for String {
+	fn to_rich_text(&self) -> RichText {
+		RichText::from(self.as_str())
+	}
+}
+
+impl<T: MessageArg + ?Sized> MessageArg for &T {
+	fn to_rich_text(&self) -> RichText {
+		(*self).to_rich_text()
+	}
+}
+
+macro_rules! impl_message_arg_for_numbers {
+	($($number_type:ty),*) => {
+		$(
+			impl MessageArg for $number_type {
+				fn to_rich_text(&self) -> RichText {
+					RichText::from(self.to_string()).fg_theme_color("number")
+				}
+			}
+		)*
+	};
+}
+impl_message_arg_for_numbers!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+
+static VARIABLES: RwLock<Option<HashMap<String, RichText>>> = RwLock::new(None);
+
+/// Sets the value of a game variable, that is a placeholder that can be used in any
+/// message template (like `{player_name}`) without having to be given to `msg!`.
+pub fn set_variable(name: &str, value: impl MessageArg) {
+	let mut variables = VARIABLES.write().unwrap();
+	let variables = variables.get_or_insert_with(HashMap::new);
+	variables.insert(name.to_string(), value.to_rich_text());
+}
+
+/// Parses the markup of the template (see `markup`) and replaces its placeholders by
+/// the matching arguments, or else by the game variables of the same name.
+pub fn format_message(template: &str, args: &[(&str, RichText)]) -> RichText {
+	let variables = VARIABLES.read().unwrap();
+	markup::parse(template, |name| {
+		args.iter()
+			.find(|(arg_name, _)| *arg_name == name)
+			.map(|(_, value)| value.clone())
+			.or_else(|| variables.as_ref()?.get(name).cloned())
+	})
+}
+
+/// Translates the message of the given key and fills in its placeholders,
+/// like `msg!("attack.hit", attacker => name, dmg => 5)`.
+#[macro_export]
+macro_rules! msg {
+	($key:expr $(, $name:ident => $value:expr)* $(,)?) => {
+		$crate::message::format_message(
+			&$crate::localization::tr!($key),
+			&[$((
+				stringify!($name),
+				$crate::message::MessageArg::to_rich_text(&$value),
+			)),*],
+		)
+	};
+}
+pub use crate::msg;
@@ -0,0 +1,294 @@
+//! Procedural generation of names (of caves, crystals, people, etc.) from grammars.
+//!
+//! The grammars are described in `assets/data/names.toml`, with one table per culture
+//! (or kind of name). Each table maps rule names to lists of alternatives, and generating
+//! a name expands the `name` rule. An alternative is some text in which `<rule>` gets
+//! replaced by the expansion of a randomly picked alternative of that rule, like this:
+//!
+//! ```text
+//! [cave]
+//! name = ["<root> Hollow", "{definite}<root> Depths{plural}"]
+//! root = ["<syllable><syllable>", "<syllable><syllable><syllable>"]
+//! syllable = ["ka", "mo", "ri", "zul"]
+//! ```
+//!
+//! Alternatives can be repeated to be picked more often.
+//! The first letter of a generated name is always made uppercase.
+//!
+//! An alternative can also have tags, like `{feminine}`, that are not part of the text
+//! but are given with the generated name if the alternative is picked (see `Name`).
+//! This is how names tell which article goes with them, the articles being in the
+//! translated templates (see `Name::form`).
+
+use std::collections::HashMap;
+
+use crate::assets;
+use crate::rng::Rng;
+
+/// Expansions go no deeper than that, to stop grammars that recurse forever.
+const MAX_EXPANSION_DEPTH: u32 = 16;
+
+enum Part {
+	Text(String),
+	Rule(String),
+	Tag(String),
+}
+
+/// Splits an alternative into its text, its `<rule>` references and its `{tag}` tags.
+fn parse_alternative(alternative: &str) -> Result<Vec<Part>, String> {
+	let mut parts = Vec::new();
+	let mut rest = alternative;
+	while let Some(start) = rest.find(['<', '{']) {
+		let (before, after) = rest.split_at(start);
+		if !before.is_empty() {
+			parts.push(Part::Text(before.to_string()));
+		}
+		let (opening, closing) = if after.starts_with('<') {
+			('<', '>')
+		} else {
+			('{', '}')
+		};
+		let (inside, after) = after[1..]
+			.split_once(closing)
+			.ok_or_else(|| format!("there is an unclosed '{opening}'"))?;
+		parts.push(if opening == '<' {
+			Part::Rule(inside.to_string())
+		} else {
+			Part::Tag(inside.to_string())
+		});
+		rest = after;
+	}
+	if !rest.is_empty() {
+		parts.push(Part::Text(rest.to_string()));
+	}
+	Ok(parts)
+}
+
+struct Grammar {
+	rules: HashMap<String, Vec<Vec<Part>>>,
+}
+
+impl Grammar {
+	/// Makes sure that there is a `name` rule and that all the referenced rules exist.
+	fn check(&self) -> Result<(), String> {
+		if !self.rules.contains_key("name") {
+			return Err("there is no \"name\" rule".to_string());
+		}
+		for (rule_name, alternatives) in self.rules.iter() {
+			for part in alternatives.iter().flatten() {
+				if let Part::Rule(referenced_rule_name) = part {
+					if !self.rules.contains_key(referenced_rule_name) {
+						return Err(format!(
+							"rule \"{rule_name}\" references \
+							the unknown rule \"{referenced_rule_name}\""
+						));
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn expand(&self, rule_name: &str, rng: &mut Rng, depth: u32, name: &mut Name) {
+		let alternatives = &self.rules[rule_name];
+		let alternative = &alternatives[rng.below(alternatives.len() as u32) as usize];
+		for part in alternative.iter() {
+			match part {
+				Part::Text(text) => name.text.push_str(text),
+				Part::Rule(referenced_rule_name) => {
+					if depth < MAX_EXPANSION_DEPTH {
+						self.expand(referenced_rule_name, rng, depth + 1, name);
+					}
+				},
+				Part::Tag(tag) => name.tags.push(tag.clone()),
+			}
+		}
+	}
+}
+
+/// A generated name, with the tags of the alternatives it was made from.
+#[derive(Clone, Debug)]
+pub struct Name {
+	pub text: String,
+	pub tags: Vec<String>,
+}
+
+impl Name {
+	pub fn has_tag(&self, tag: &str) -> bool {
+		self.tags.iter().any(|name_tag| name_tag == tag)
+	}
+
+	/// Which article goes with the name, to pick the template that puts it before the name
+	/// (like `"cave_name_feminine"`, each language having all of them): `"elided"` if the
+	/// name starts with a vowel (for the French "l'"), or else the `feminine` or `plural`
+	/// tag if it has one, or else `"masculine"`.
+	pub fn form(&self) -> &'static str {
+		let first_char = self
+			.text
+			.chars()
+			.next()
+			.map(|first| first.to_ascii_lowercase());
+		if first_char.is_some_and(|first| "aeiou".contains(first)) {
+			"elided"
+		} else if self.has_tag("feminine") {
+			"feminine"
+		} else if self.has_tag("plural") {
+			"plural"
+		} else {
+			"masculine"
+		}
+	}
+}
+
+pub struct NameGenerator {
+	cultures: HashMap<String, Grammar>,
+}
+
+impl NameGenerator {
+	/// Loads the grammars from an asset (see `assets`).
+	pub fn from_asset(asset_path: &str) -> Result<NameGenerator, Box<dyn std::error::Error>> {
+		NameGenerator::parse(&assets::read_to_string(asset_path)?)
+	}
+
+	fn parse(text: &str) -> Result<NameGenerator, Box<dyn std::error::Error>> {
+		let table: toml::Table = text.parse()?;
+		let mut cultures = HashMap::new();
+		for (culture_name, culture) in table.iter() {
+			let culture = culture
+				.as_table()
+				.ok_or_else(|| format!("culture \"{culture_name}\" is not a table"))?;
+			let mut rules = HashMap::new();
+			for (rule_name, alternatives) in culture.iter() {
+				let alternatives: Option<Vec<&str>> = alternatives
+					.as_array()
+					.and_then(|array| array.iter().map(toml::Value::as_str).collect());
+				let alternatives = alternatives
+					.filter(|alternatives| !alternatives.is_empty())
+					.ok_or_else(|| {
+						format!("rule \"{culture_name}.{rule_name}\" is not a list of strings")
+					})?;
+				let alternatives = alternatives
+					.into_iter()
+					.map(parse_alternative)
+					.collect::<Result<_, _>>()
+					.map_err(|error| format!("rule \"{culture_name}.{rule_name}\": {error}"))?;
+				rules.insert(rule_name.clone(), alternatives);
+			}
+			let grammar = Grammar { rules };
+			grammar
+				.check()
+				.map_err(|error| format!("culture \"{culture_name}\": {error}"))?;
+			cultures.insert(culture_name.clone(), grammar);
+		}
+		Ok(NameGenerator { cultures })
+	}
+
+	/// Generates a name from the grammar of the given culture.
+	pub fn generate(&self, culture_name: &str, rng: &mut Rng) -> Name {
+		let grammar = self
+			.cultures
+			.get(culture_name)
+			.unwrap_or_else(|| panic!("There is no name culture \"{culture_name}\""));
+		let mut name = Name { text: String::new(), tags: Vec::new() };
+		grammar.expand("name", rng, 0, &mut name);
+		let mut chars = name.text.chars();
+		if let Some(first) = chars.next() {
+			name.text = first.to_uppercase().chain(chars).collect();
+		}
+		name
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const GRAMMARS: &str = r#"
+[cave]
+name = ["<root> hollow", "the <root> depths"]
+root = ["<syllable><syllable>"]
+syllable = ["ka", "mo"]
+
+[forever]
+name = ["a<name>"]
+"#;
+
+	#[test]
+	fn names_follow_the_grammar() {
+		let generator = NameGenerator::parse(GRAMMARS).unwrap();
+		let mut rng = Rng::new(7);
+		for _ in 0..20 {
+			let name = generator.generate("cave", &mut rng).text;
+			assert!(
+				name.starts_with(['K', 'M', 'T']),
+				"\"{name}\" is not capitalized"
+			);
+			let lowercase_name = name.to_lowercase();
+			let root = lowercase_name
+				.strip_suffix(" hollow")
+				.or_else(|| {
+					let rest = lowercase_name.strip_prefix("the ")?;
+					rest.strip_suffix(" depths")
+				})
+				.unwrap_or_else(|| panic!("\"{name}\" does not follow the grammar"));
+			assert_eq!(root.len(), 4);
+			assert!(["ka", "mo"].contains(&&root[..2]) && ["ka", "mo"].contains(&&root[2..]));
+		}
+	}
+
+	#[test]
+	fn names_depend_only_on_the_seed() {
+		let generator = NameGenerator::parse(GRAMMARS).unwrap();
+		let names = |seed| {
+			let mut rng = Rng::new(seed);
+			(0..5)
+				.map(|_| generator.generate("cave", &mut rng).text)
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(names(3), names(3));
+	}
+
+	#[test]
+	fn recursion_stops() {
+		let generator = NameGenerator::parse(GRAMMARS).unwrap();
+		let name = generator.generate("forever", &mut Rng::new(0));
+		assert_eq!(name.text.len() as u32, MAX_EXPANSION_DEPTH + 1);
+	}
+
+	#[test]
+	fn tags_are_given_with_the_name() {
+		let grammars = "[crystal]\nname = [\"<root>ine{feminine}\"]\nroot = [\"lum{shiny}\"]\n";
+		let generator = NameGenerator::parse(grammars).unwrap();
+		let name = generator.generate("crystal", &mut Rng::new(0));
+		assert_eq!(name.text, "Lumine");
+		assert_eq!(name.tags, ["shiny", "feminine"]);
+		assert_eq!(name.form(), "feminine");
+
+		let grammars = "[crystal]\nname = [\"orthite{feminine}\"]\n";
+		let generator = NameGenerator::parse(grammars).unwrap();
+		assert_eq!(
+			generator.generate("crystal", &mut Rng::new(0)).form(),
+			"elided"
+		);
+		let grammars = "[crystal]\nname = [\"vor\"]\n";
+		let generator = NameGenerator::parse(grammars).unwrap();
+		assert_eq!(
+			generator.generate("crystal", &mut Rng::new(0)).form(),
+			"masculine"
+		);
+	}
+
+	#[test]
+	fn grammars_are_checked() {
+		assert!(NameGenerator::parse("[cave]\nroot = [\"a\"]\n").is_err());
+		assert!(NameGenerator::parse("[cave]\nname = [\"<nope>\"]\n").is_err());
+		assert!(NameGenerator::parse("[cave]\nname = [\"<name\"]\n").is_err());
+		assert!(NameGenerator::parse("[cave]\nname = [\"a{tag\"]\n").is_err());
+		assert!(NameGenerator::parse("[cave]\nname = []\n").is_err());
+	}
+
+	#[test]
+	fn the_game_grammars_load() {
+		assert!(NameGenerator::from_asset("data/names.toml").is_ok());
+	}
+}
@@ -0,0 +1,31 @@
+//! Plain text narration of what happens on screen (the notices, the widget that gets
+//! the focus, the tile under the mouse), written to the standard output so that blind
+//! players can follow the game with a screen reader reading the terminal.
+//!
+//! Each line is a kind followed by the text, like `focus: Your name: Bob`,
+//! so that it can also be parsed by a script (to feed a text-to-speech program).
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+	ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Writes a line of narration, if narration is enabled.
+pub fn narrate(kind: &str, text: &str) {
+	if !is_enabled() {
+		return;
+	}
+	// Line breaks in the text would look like other lines of narration.
+	let text = text.replace(['\n', '\r'], " ");
+	let mut stdout = std::io::stdout().lock();
+	let _ = writeln!(stdout, "{kind}: {}", text.trim());
+	let _ = stdout.flush();
+}
@@ -0,0 +1,162 @@
+//! Pathfinding over the tile map, by A*.
+//!
+//! The cost of moving onto a tile is given by a function of its terrain, so that what
+//! moves can have its own costs (like walking, see `walking_cost`, or digging too,
+//! see `digging_cost`). Moves are to the 4 neighboring tiles.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::world::{Terrain, TileMap};
+
+/// Walking through air, and slowly through water.
+pub fn walking_cost(terrain: Terrain) -> Option<u32> {
+	match terrain {
+		Terrain::Air => Some(1),
+		Terrain::Water => Some(3),
+		Terrain::Rock | Terrain::Crystal => None,
+	}
+}
+
+/// Walking (see `walking_cost`), and digging through the rock (but not the crystals,
+/// that are too hard for that).
+pub fn digging_cost(terrain: Terrain) -> Option<u32> {
+	match terrain {
+		Terrain::Rock => Some(8),
+		terrain => walking_cost(terrain),
+	}
+}
+
+/// The tiles of the cheapest path from `from_xy` to `to_xy` (both included), or `None`
+/// if there is none. The cost function gives the cost of moving onto a tile of the
+/// terrain, `None` meaning that it cannot be moved onto (the cost of the starting tile
+/// does not matter). The costs should be at least 1 for the path to be the cheapest.
+pub fn find_path(
+	map: &TileMap,
+	from_xy: (u32, u32),
+	to_xy: (u32, u32),
+	cost: impl Fn(Terrain) -> Option<u32>,
+) -> Option<Vec<(u32, u32)>> {
+	let (map_w, map_h) = map.map_wh;
+	assert!(from_xy.0 < map_w && from_xy.1 < map_h);
+	assert!(to_xy.0 < map_w && to_xy.1 < map_h);
+	let tile_index = |xy: (u32, u32)| (xy.0 * map_h + xy.1) as usize;
+	// The Manhattan distance, that never overestimates as the costs are at least 1.
+	let heuristic = |xy: (u32, u32)| xy.0.abs_diff(to_xy.0) + xy.1.abs_diff(to_xy.1);
+
+	// The cheapest known cost to get to each tile, and from where.
+	let mut best_costs = vec![u32::MAX; (map_w * map_h) as usize];
+	let mut came_from: Vec<Option<(u32, u32)>> = vec![None; (map_w * map_h) as usize];
+	// The tiles to look from, cheapest estimation first.
+	let mut open = BinaryHeap::new();
+	best_costs[tile_index(from_xy)] = 0;
+	open.push(Reverse((heuristic(from_xy), 0, from_xy)));
+
+	while let Some(Reverse((_, cost_so_far, xy))) = open.pop() {
+		if xy == to_xy {
+			let mut path = vec![to_xy];
+			while let Some(previous_xy) = came_from[tile_index(*path.last().unwrap())] {
+				path.push(previous_xy);
+			}
+			path.reverse();
+			return Some(path);
+		}
+		if cost_so_far > best_costs[tile_index(xy)] {
+			// A cheaper way there was found after this one was queued.
+			continue;
+		}
+		let neighbors = [
+			(xy.0.checked_sub(1), Some(xy.1)),
+			(Some(xy.0 + 1).filter(|&x| x < map_w), Some(xy.1)),
+			(Some(xy.0), xy.1.checked_sub(1)),
+			(Some(xy.0), Some(xy.1 + 1).filter(|&y| y < map_h)),
+		];
+		for neighbor_xy in neighbors {
+			let (Some(x), Some(y)) = neighbor_xy else {
+				continue;
+			};
+			let Some(move_cost) = cost(map.terrain((x, y))) else {
+				continue;
+			};
+			let neighbor_cost = cost_so_far + move_cost;
+			if neighbor_cost < best_costs[tile_index((x, y))] {
+				best_costs[tile_index((x, y))] = neighbor_cost;
+				came_from[tile_index((x, y))] = Some(xy);
+				open.push(Reverse((
+					neighbor_cost + heuristic((x, y)),
+					neighbor_cost,
+					(x, y),
+				)));
+			}
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn map(rows: &[&str]) -> TileMap {
+		let rows: Vec<String> = rows.iter().map(|row| row.to_string()).collect();
+		TileMap::from_rows(&rows).unwrap()
+	}
+
+	fn path_cost(map: &TileMap, path: &[(u32, u32)], cost: impl Fn(Terrain) -> Option<u32>) -> u32 {
+		path[1..]
+			.iter()
+			.map(|&xy| cost(map.terrain(xy)).unwrap())
+			.sum()
+	}
+
+	#[test]
+	fn paths_go_from_start_to_end_by_steps() {
+		let map = map(&["....", "....", "...."]);
+		let path = find_path(&map, (0, 0), (3, 2), walking_cost).unwrap();
+		assert_eq!(path.first(), Some(&(0, 0)));
+		assert_eq!(path.last(), Some(&(3, 2)));
+		assert_eq!(path.len(), 6);
+		for step in path.windows(2) {
+			assert_eq!(
+				step[0].0.abs_diff(step[1].0) + step[0].1.abs_diff(step[1].1),
+				1
+			);
+		}
+	}
+
+	#[test]
+	fn paths_go_around_walls_and_water() {
+		let map = map(&[".....", ".#~#.", ".#.#.", "....."]);
+		let path = find_path(&map, (2, 2), (2, 0), walking_cost).unwrap();
+		// Through the water (3) is cheaper than around the walls (8).
+		assert_eq!(path_cost(&map, &path, walking_cost), 4);
+		assert!(path.contains(&(2, 1)));
+	}
+
+	#[test]
+	fn there_may_be_no_path() {
+		let map = map(&[".#.", ".#.", ".#."]);
+		assert_eq!(find_path(&map, (0, 0), (2, 2), walking_cost), None);
+		let path = find_path(&map, (0, 0), (2, 0), digging_cost).unwrap();
+		assert_eq!(path, vec![(0, 0), (1, 0), (2, 0)]);
+	}
+
+	#[test]
+	fn paths_are_the_cheapest() {
+		// Digging through the rock (8) is cheaper than walking the long way around.
+		let map = map(&["..........", "#########.", ".........."]);
+		let path = find_path(&map, (0, 0), (0, 2), digging_cost).unwrap();
+		assert_eq!(path_cost(&map, &path, digging_cost), 9);
+		let path = find_path(&map, (0, 0), (0, 2), walking_cost).unwrap();
+		assert_eq!(path_cost(&map, &path, walking_cost), 20);
+	}
+
+	#[test]
+	fn the_path_to_the_start_is_the_start() {
+		let map = map(&["..."]);
+		assert_eq!(
+			find_path(&map, (1, 0), (1, 0), walking_cost),
+			Some(vec![(1, 0)])
+		);
+	}
+}
@@ -0,0 +1,419 @@
+//! Map prefabs, hand-made pieces of map (vaults, rooms, shrines, etc.) that are meant to be
+//! stamped as-is into generated maps, and that can be read and written by an editor.
+//!
+//! A prefab is stored as text, like this:
+//!
+//! ```text
+//! // Comment lines start with two slashes (except in the map section).
+//! name = crystal shrine
+//! min_depth = 2
+//!
+//! [legend]
+//! # = wall
+//! . = floor
+//! ~ = water
+//!
+//! [markers]
+//! @ = spawn guardian
+//! $ = loot rare
+//! + = connection
+//!
+//! [map]
+//!   #####
+//! ###...###
+//! +..~$~..+
+//! ###.@.###
+//!   #####
+//! ```
+//!
+//! The lines before the first section are metadata, free `key = value` pairs that are kept
+//! in order. Only `name` is required.
+//!
+//! The legend maps a glyph to a terrain name, and the markers map a glyph to a placeholder
+//! that the generator is expected to resolve (spawn a creature, put some loot, connect the
+//! prefab to the rest of the map). A marker can be followed by a free-form tag that tells
+//! the generator what kind of spawn or loot it should be.
+//!
+//! The map section must be the last section and is read verbatim (so `//` has no special
+//! meaning in there). Spaces are not part of the prefab, they leave whatever was already
+//! there untouched when stamped, which allows for non-rectangular prefabs. Rows may be of
+//! different lengths, the shorter ones are padded with spaces.
+
+use std::fmt::Write;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PrefabMarkerKind {
+	Spawn,
+	Loot,
+	Connection,
+}
+
+impl PrefabMarkerKind {
+	fn name(self) -> &'static str {
+		match self {
+			PrefabMarkerKind::Spawn => "spawn",
+			PrefabMarkerKind::Loot => "loot",
+			PrefabMarkerKind::Connection => "connection",
+		}
+	}
+
+	fn from_name(name: &str) -> Option<PrefabMarkerKind> {
+		match name {
+			"spawn" => Some(PrefabMarkerKind::Spawn),
+			"loot" => Some(PrefabMarkerKind::Loot),
+			"connection" => Some(PrefabMarkerKind::Connection),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PrefabMarker {
+	pub kind: PrefabMarkerKind,
+	pub tag: Option<String>,
+}
+
+/// What a prefab says about one of its cells.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PrefabCell<'a> {
+	/// Not part of the prefab, stamping leaves the map untouched there.
+	Empty,
+	Terrain(&'a str),
+	Marker(&'a PrefabMarker),
+}
+
+#[derive(Debug)]
+pub struct PrefabError {
+	/// Line number (starting at 1) in the prefab text.
+	pub line: usize,
+	pub message: String,
+}
+
+impl std::fmt::Display for PrefabError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "prefab line {}: {}", self.line, self.message)
+	}
+}
+
+impl std::error::Error for PrefabError {}
+
+#[derive(Clone, Debug)]
+pub struct Prefab {
+	pub metadata: Vec<(String, String)>,
+	pub legend: Vec<(char, String)>,
+	pub markers: Vec<(char, PrefabMarker)>,
+	/// Row-major, spaces are empty cells.
+	glyphs: Vec<char>,
+	grid_wh: (u32, u32),
+}
+
+enum Section {
+	Metadata,
+	Legend,
+	Markers,
+	Map,
+}
+
+impl Prefab {
+	pub fn from_filepath(filepath: &str) -> Result<Prefab, Box<dyn std::error::Error>> {
+		let text = std::fs::read_to_string(filepath)?;
+		Ok(Prefab::parse(&text)?)
+	}
+
+	pub fn parse(text: &str) -> Result<Prefab, PrefabError> {
+		let mut metadata = Vec::new();
+		let mut legend = Vec::new();
+		let mut markers = Vec::new();
+		let mut rows: Vec<(usize, &str)> = Vec::new();
+
+		let mut section = Section::Metadata;
+		for (line_index, line) in text.lines().enumerate() {
+			let line_number = line_index + 1;
+			let error = |message: String| PrefabError { line: line_number, message };
+
+			if let Section::Map = section {
+				rows.push((line_number, line));
+				continue;
+			}
+
+			let trimmed = line.trim();
+			if trimmed.is_empty() || trimmed.starts_with("//") {
+				continue;
+			}
+			match trimmed {
+				"[legend]" => section = Section::Legend,
+				"[markers]" => section = Section::Markers,
+				"[map]" => section = Section::Map,
+				_ if is_section_header(trimmed) => {
+					return Err(error(format!("unknown section \"{trimmed}\"")));
+				},
+				_ => match section {
+					Section::Metadata => {
+						let (key, value) = trimmed
+							.split_once('=')
+							.ok_or_else(|| error("expected \"key = value\"".to_string()))?;
+						metadata.push((key.trim().to_string(), value.trim().to_string()));
+					},
+					Section::Legend | Section::Markers => {
+						let (glyph, value) = parse_glyph_entry(trimmed)
+							.ok_or_else(|| error("expected \"glyph = value\"".to_string()))?;
+						if glyph == ' ' {
+							return Err(error("the space glyph is reserved".to_string()));
+						}
+						let already_defined = legend.iter().any(|(other, _)| *other == glyph)
+							|| markers.iter().any(|(other, _)| *other == glyph);
+						if already_defined {
+							return Err(error(format!("glyph '{glyph}' is defined twice")));
+						}
+						if let Section::Legend = section {
+							legend.push((glyph, value.to_string()));
+						} else {
+							let (kind_name, tag) = match value.split_once(' ') {
+								Some((kind_name, tag)) => (kind_name, Some(tag.trim().to_string())),
+								None => (value, None),
+							};
+							let kind = PrefabMarkerKind::from_name(kind_name).ok_or_else(|| {
+								error(format!("unknown marker kind \"{kind_name}\""))
+							})?;
+							markers.push((glyph, PrefabMarker { kind, tag }));
+						}
+					},
+					Section::Map => unreachable!(),
+				},
+			}
+		}
+
+		if !metadata.iter().any(|(key, _)| key == "name") {
+			return Err(PrefabError {
+				line: 1,
+				message: "missing \"name\" metadata".to_string(),
+			});
+		}
+
+		// Trailing empty lines are most probably not meant to be a part of the map
+		// (rows of spaces are, as `to_text` writes them).
+		while rows.last().is_some_and(|(_, row)| row.is_empty()) {
+			rows.pop();
+		}
+		let grid_w = rows
+			.iter()
+			.map(|(_, row)| row.chars().count())
+			.max()
+			.unwrap_or(0);
+		let grid_wh = (grid_w as u32, rows.len() as u32);
+		let mut glyphs = Vec::with_capacity(grid_w * rows.len());
+		for (line_number, row) in rows.iter() {
+			for glyph in row.chars() {
+				let is_defined = glyph == ' '
+					|| legend.iter().any(|(other, _)| *other == glyph)
+					|| markers.iter().any(|(other, _)| *other == glyph);
+				if !is_defined {
+					return Err(PrefabError {
+						line: *line_number,
+						message: format!("glyph '{glyph}' is not in the legend nor the markers"),
+					});
+				}
+				glyphs.push(glyph);
+			}
+			glyphs.extend(std::iter::repeat_n(' ', grid_w - row.chars().count()));
+		}
+
+		Ok(Prefab { metadata, legend, markers, glyphs, grid_wh })
+	}
+
+	/// Writes the prefab back as text, in a form that `parse` reads back to the same prefab.
+	pub fn to_text(&self) -> String {
+		let mut text = String::new();
+		for (key, value) in self.metadata.iter() {
+			writeln!(text, "{key} = {value}").unwrap();
+		}
+		text.push_str("\n[legend]\n");
+		for (glyph, terrain) in self.legend.iter() {
+			writeln!(text, "{glyph} = {terrain}").unwrap();
+		}
+		text.push_str("\n[markers]\n");
+		for (glyph, marker) in self.markers.iter() {
+			match &marker.tag {
+				Some(tag) => writeln!(text, "{glyph} = {} {tag}", marker.kind.name()).unwrap(),
+				None => writeln!(text, "{glyph} = {}", marker.kind.name()).unwrap(),
+			}
+		}
+		text.push_str("\n[map]\n");
+		// Rows are not trimmed, so that ones of spaces at the bottom or on the right are kept.
+		for row in self.glyphs.chunks(self.grid_wh.0.max(1) as usize) {
+			let row: String = row.iter().collect();
+			writeln!(text, "{row}").unwrap();
+		}
+		text
+	}
+
+	pub fn save_to_filepath(&self, filepath: &str) -> std::io::Result<()> {
+		std::fs::write(filepath, self.to_text())
+	}
+
+	pub fn name(&self) -> &str {
+		self.metadata_value("name").unwrap()
+	}
+
+	pub fn metadata_value(&self, key: &str) -> Option<&str> {
+		self.metadata
+			.iter()
+			.find(|(other, _)| other == key)
+			.map(|(_, value)| value.as_str())
+	}
+
+	pub fn grid_wh(&self) -> (u32, u32) {
+		self.grid_wh
+	}
+
+	pub fn glyph(&self, xy: (u32, u32)) -> char {
+		assert!(xy.0 < self.grid_wh.0);
+		assert!(xy.1 < self.grid_wh.1);
+		self.glyphs[(xy.1 * self.grid_wh.0 + xy.0) as usize]
+	}
+
+	/// Changes the glyph of a cell, the glyph must be a space or be in the legend or markers.
+	pub fn set_glyph(&mut self, xy: (u32, u32), glyph: char) {
+		assert!(xy.0 < self.grid_wh.0);
+		assert!(xy.1 < self.grid_wh.1);
+		assert!(glyph == ' ' || !matches!(self.cell_of_glyph(glyph), PrefabCell::Empty));
+		self.glyphs[(xy.1 * self.grid_wh.0 + xy.0) as usize] = glyph;
+	}
+
+	pub fn cell(&self, xy: (u32, u32)) -> PrefabCell<'_> {
+		self.cell_of_glyph(self.glyph(xy))
+	}
+
+	fn cell_of_glyph(&self, glyph: char) -> PrefabCell<'_> {
+		if let Some((_, terrain)) = self.legend.iter().find(|(other, _)| *other == glyph) {
+			PrefabCell::Terrain(terrain)
+		} else if let Some((_, marker)) = self.markers.iter().find(|(other, _)| *other == glyph) {
+			PrefabCell::Marker(marker)
+		} else {
+			PrefabCell::Empty
+		}
+	}
+
+	/// Calls `stamp_cell` for every non-empty cell, with the cell coordinates offset by
+	/// `dst_xy`, so that a map generator can copy the prefab at `dst_xy` in its map.
+	pub fn stamp(&self, dst_xy: (i32, i32), mut stamp_cell: impl FnMut((i32, i32), PrefabCell)) {
+		for y in 0..self.grid_wh.1 {
+			for x in 0..self.grid_wh.0 {
+				let cell = self.cell((x, y));
+				if !matches!(cell, PrefabCell::Empty) {
+					stamp_cell((dst_xy.0 + x as i32, dst_xy.1 + y as i32), cell);
+				}
+			}
+		}
+	}
+
+	/// Coordinates (relative to the prefab) of all the markers of the given kind.
+	pub fn marker_coords(&self, kind: PrefabMarkerKind) -> Vec<(u32, u32)> {
+		let mut coords = Vec::new();
+		for y in 0..self.grid_wh.1 {
+			for x in 0..self.grid_wh.0 {
+				if matches!(self.cell((x, y)), PrefabCell::Marker(marker) if marker.kind == kind) {
+					coords.push((x, y));
+				}
+			}
+		}
+		coords
+	}
+}
+
+/// Whether the line is a `[name]` section header, and not a legend or marker entry
+/// for the `[` glyph.
+fn is_section_header(line: &str) -> bool {
+	line.strip_prefix('[')
+		.and_then(|line| line.strip_suffix(']'))
+		.is_some_and(|name| {
+			!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+		})
+}
+
+/// Parses a `glyph = value` line, where the glyph may be any character (including `=`).
+fn parse_glyph_entry(line: &str) -> Option<(char, &str)> {
+	let mut chars = line.chars();
+	let glyph = chars.next()?;
+	let value = chars.as_str().trim_start().strip_prefix('=')?.trim();
+	if value.is_empty() {
+		None
+	} else {
+		Some((glyph, value))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SHRINE: &str = "\
+name = crystal shrine
+min_depth = 2
+
+[legend]
+# = wall
+. = floor
+[ = door
+
+[markers]
+@ = spawn guardian
++ = connection
+
+[map]
+  ###
++.@.[
+  ###
+";
+
+	#[test]
+	fn parse_reads_every_section() {
+		let prefab = Prefab::parse(SHRINE).unwrap();
+		assert_eq!(prefab.name(), "crystal shrine");
+		assert_eq!(prefab.metadata_value("min_depth"), Some("2"));
+		assert_eq!(prefab.grid_wh(), (5, 3));
+		assert_eq!(prefab.cell((0, 0)), PrefabCell::Empty);
+		assert_eq!(prefab.cell((4, 1)), PrefabCell::Terrain("door"));
+		let PrefabCell::Marker(marker) = prefab.cell((2, 1)) else {
+			panic!("expected a marker");
+		};
+		assert_eq!(marker.kind, PrefabMarkerKind::Spawn);
+		assert_eq!(marker.tag.as_deref(), Some("guardian"));
+		assert_eq!(
+			prefab.marker_coords(PrefabMarkerKind::Connection),
+			vec![(0, 1)]
+		);
+	}
+
+	#[test]
+	fn parse_rejects_mistakes() {
+		let unknown_section = "name = a\n[stuff]\n";
+		assert_eq!(Prefab::parse(unknown_section).unwrap_err().line, 2);
+		let undefined_glyph = "name = a\n[legend]\n# = wall\n[map]\n#?\n";
+		assert_eq!(Prefab::parse(undefined_glyph).unwrap_err().line, 5);
+		assert!(Prefab::parse("[map]\n").is_err());
+	}
+
+	#[test]
+	fn to_text_reads_back_to_the_same_prefab() {
+		let prefab = Prefab::parse(SHRINE).unwrap();
+		let reparsed = Prefab::parse(&prefab.to_text()).unwrap();
+		assert_eq!(reparsed.metadata, prefab.metadata);
+		assert_eq!(reparsed.legend, prefab.legend);
+		assert_eq!(reparsed.markers, prefab.markers);
+		assert_eq!(reparsed.grid_wh, prefab.grid_wh);
+		assert_eq!(reparsed.glyphs, prefab.glyphs);
+	}
+
+	#[test]
+	fn to_text_keeps_rows_and_columns_of_spaces() {
+		let mut prefab = Prefab::parse("name = a\n[legend]\n# = wall\n[map]\n###\n###\n").unwrap();
+		prefab.set_glyph((2, 0), ' ');
+		prefab.set_glyph((2, 1), ' ');
+		prefab.set_glyph((0, 1), ' ');
+		prefab.set_glyph((1, 1), ' ');
+		let reparsed = Prefab::parse(&prefab.to_text()).unwrap();
+		assert_eq!(reparsed.grid_wh(), (3, 2));
+		assert_eq!(reparsed.glyphs, prefab.glyphs);
+	}
+}
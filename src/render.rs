@@ -0,0 +1,891 @@
+//! Drawing of a grid of tiles with ASCII-like sprites (see `CharSpriteSheet`),
+//! which is all that the game shows.
+
+use std::collections::HashMap;
+
+use sdl2::image::{ImageRWops, LoadSurface};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture, TextureCreator};
+use sdl2::rwops::RWops;
+use sdl2::surface::Surface;
+use sdl2::video::WindowContext;
+
+use crate::cp437::unicode_to_cp437;
+use crate::terminal::Terminal;
+use crate::{assets, display, theme};
+
+/// A copy of the surface in a format of 32-bit pixels with an alpha channel, which is what
+/// `top_left_color` and `map_surface_pixels` expect.
+fn rgba_surface(surface: &Surface) -> Result<Surface<'static>, String> {
+	let new_surface = surface.convert_format(PixelFormatEnum::RGBA8888)?;
+
+	// From what I may have understood from posts on the Internet,
+	// `SDL_ConvertSurfaceFormat` may decide to choose a format similar
+	// to the one being requested if such format is not available
+	// (because some pixel formats may not be available??), and also
+	// the endianness may mess with our expectations of the order of bytes
+	// in a pixel value (and on my machine it did be revered).
+	// Thus, we will only assume that the format is of the size of a `u32`
+	// and we also need transparency so it better be supported.
+	let pixel_format_enum = new_surface.pixel_format_enum();
+	if !pixel_format_enum.supports_alpha() || pixel_format_enum.byte_size_per_pixel() != 4 {
+		return Err(format!(
+			"the image got converted to the unexpected pixel format {pixel_format_enum:?}"
+		));
+	}
+	Ok(new_surface)
+}
+
+/// The color of the top-left pixel of a surface made by `rgba_surface`, if it has pixels.
+fn top_left_color(surface: &Surface) -> Option<Color> {
+	if surface.width() == 0 || surface.height() == 0 {
+		return None;
+	}
+	let pixel_format = surface.pixel_format();
+	let pixel = surface.with_lock(|pixels: &[u8]| {
+		u32::from_ne_bytes([pixels[0], pixels[1], pixels[2], pixels[3]])
+	});
+	Some(Color::from_u32(&pixel_format, pixel))
+}
+
+/// Changes the color of every pixel of a surface made by `rgba_surface`.
+fn map_surface_pixels(surface: &mut Surface, mut f: impl FnMut(Color) -> Color) {
+	let pixel_format = surface.pixel_format();
+	let pitch = surface.pitch();
+	let wh = (surface.width(), surface.height());
+	surface.with_lock_mut(|pixels: &mut [u8]| {
+		for y in 0..wh.1 {
+			for x in 0..wh.0 {
+				let index = (y * pitch + x * 4) as usize;
+				let pixel = pixels[index..(index + 4)].as_mut_ptr() as *mut u32;
+
+				// SAFETY: If this does not work then just go program something else.
+				let old_color = unsafe { Color::from_u32(&pixel_format, *pixel) };
+				let new_color = f(old_color);
+				unsafe {
+					*pixel = new_color.to_u32(&pixel_format);
+				}
+			}
+		}
+	});
+}
+
+/// Which pixels of a sprite sheet image are made transparent, as the sprites are drawn
+/// in the color of the tiles over their background.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TransparencyPolicy {
+	/// The magenta (255, 0, 255) and black pixels, as in most Dwarf Fortress tilesets.
+	#[default]
+	MagentaAndBlack,
+	/// The magenta pixels, for tilesets with black in their sprites.
+	Magenta,
+	Black,
+	/// The pixels of the color of the top-left pixel.
+	TopLeftPixel,
+	/// The alpha channel of the image is used as it is.
+	Alpha,
+}
+
+impl TransparencyPolicy {
+	pub fn from_name(name: &str) -> Option<TransparencyPolicy> {
+		match name {
+			"magenta_and_black" => Some(TransparencyPolicy::MagentaAndBlack),
+			"magenta" => Some(TransparencyPolicy::Magenta),
+			"black" => Some(TransparencyPolicy::Black),
+			"top_left_pixel" => Some(TransparencyPolicy::TopLeftPixel),
+			"alpha" => Some(TransparencyPolicy::Alpha),
+			_ => None,
+		}
+	}
+}
+
+/// Sprite sheet with ASCII-like sprites.
+///
+/// The order of the character sprites (left to right and top to bottom) is expected
+/// to be the same as in [CP437](https://en.wikipedia.org/wiki/Code_page_437).
+///
+/// If the sheet texture is loaded from a file, such file can be obtained from
+/// [the Dwarf Fortress wiki tileset repo](https://dwarffortresswiki.org/Tileset_repository).
+pub struct CharSpriteSheet {
+	texture: Texture,
+	grid_wh: (u32, u32),
+	pub tile_wh: (u32, u32),
+}
+
+impl CharSpriteSheet {
+	pub fn from_filepath(
+		filepath: &str,
+		tile_wh: (u32, u32),
+		transparency: TransparencyPolicy,
+		texture_creator: &TextureCreator<WindowContext>,
+	) -> Result<CharSpriteSheet, String> {
+		let raw_surface = Surface::from_file(filepath)?;
+		CharSpriteSheet::from_surface(&raw_surface, tile_wh, transparency, texture_creator)
+	}
+
+	/// Like `from_filepath` but for a tileset of the assets (see `assets`).
+	pub fn from_asset(
+		asset_path: &str,
+		tile_wh: (u32, u32),
+		transparency: TransparencyPolicy,
+		texture_creator: &TextureCreator<WindowContext>,
+	) -> Result<CharSpriteSheet, String> {
+		let bytes = assets::read(asset_path)?;
+		let raw_surface = RWops::from_bytes(&bytes)?.load()?;
+		CharSpriteSheet::from_surface(&raw_surface, tile_wh, transparency, texture_creator)
+	}
+
+	pub fn from_surface(
+		raw_surface: &Surface,
+		tile_wh: (u32, u32),
+		transparency: TransparencyPolicy,
+		texture_creator: &TextureCreator<WindowContext>,
+	) -> Result<CharSpriteSheet, String> {
+		let mut surface = rgba_surface(raw_surface)?;
+		let top_left_rgb = top_left_color(&surface).map(|color| (color.r, color.g, color.b));
+		let key_to_transparent = |color: Color| {
+			let rgb = (color.r, color.g, color.b);
+			let is_key = match transparency {
+				TransparencyPolicy::MagentaAndBlack => matches!(rgb, (255, 0, 255) | (0, 0, 0)),
+				TransparencyPolicy::Magenta => rgb == (255, 0, 255),
+				TransparencyPolicy::Black => rgb == (0, 0, 0),
+				TransparencyPolicy::TopLeftPixel => Some(rgb) == top_left_rgb,
+				TransparencyPolicy::Alpha => false,
+			};
+			if is_key {
+				Color::RGBA(0, 0, 0, 0)
+			} else {
+				color
+			}
+		};
+		map_surface_pixels(&mut surface, key_to_transparent);
+		let mut texture = texture_creator
+			.create_texture_from_surface(surface)
+			.map_err(|error| error.to_string())?;
+		texture.set_blend_mode(BlendMode::Blend);
+		CharSpriteSheet::from_texture(texture, tile_wh)
+	}
+
+	pub fn from_texture(texture: Texture, tile_wh: (u32, u32)) -> Result<CharSpriteSheet, String> {
+		let texture_query = texture.query();
+		let texture_wh = (texture_query.width, texture_query.height);
+		if tile_wh.0 == 0
+			|| tile_wh.1 == 0
+			|| !texture_wh.0.is_multiple_of(tile_wh.0)
+			|| !texture_wh.1.is_multiple_of(tile_wh.1)
+		{
+			// SAFETY: The texture is not used anymore.
+			unsafe { texture.destroy() };
+			return Err(format!(
+				"a {}x{} image cannot be cut into {}x{} tiles",
+				texture_wh.0, texture_wh.1, tile_wh.0, tile_wh.1
+			));
+		}
+		let grid_wh = (texture_wh.0 / tile_wh.0, texture_wh.1 / tile_wh.1);
+		Ok(CharSpriteSheet { texture, grid_wh, tile_wh })
+	}
+
+	pub fn char_index_to_rect(&self, char_index: u32) -> Rect {
+		assert!(char_index < self.grid_wh.0 * self.grid_wh.1);
+		let grid_xy = (char_index % self.grid_wh.0, char_index / self.grid_wh.0);
+		let xy = (grid_xy.0 * self.tile_wh.0, grid_xy.1 * self.tile_wh.1);
+		Rect::new(xy.0 as i32, xy.1 as i32, self.tile_wh.0, self.tile_wh.1)
+	}
+
+	pub fn draw_char_to_canvas<T: RenderTarget>(
+		&mut self,
+		char_index: u32,
+		canvas: &mut Canvas<T>,
+		color: Color,
+		dst: Rect,
+	) {
+		self.texture.set_color_mod(color.r, color.g, color.b);
+		canvas
+			.copy(&self.texture, self.char_index_to_rect(char_index), dst)
+			.unwrap();
+	}
+}
+
+type SpriteIndex = u32;
+
+/// The sprite sheets used to draw the screen, that is a main sheet in the CP437 order
+/// and possibly extra sheets for characters that are not in CP437 (like some accented
+/// letters needed by some languages).
+pub struct CharSpriteSheets {
+	pub main_sheet: CharSpriteSheet,
+	extra_sheets: Vec<CharSpriteSheet>,
+	/// Which extra sheet has which character, and where in the sheet it is.
+	extra_chars: HashMap<char, (usize, SpriteIndex)>,
+	/// The sprite (of the main sheet) drawn for the characters that no sheet has.
+	fallback_index: SpriteIndex,
+}
+
+impl CharSpriteSheets {
+	pub fn new(main_sheet: CharSpriteSheet) -> CharSpriteSheets {
+		CharSpriteSheets {
+			main_sheet,
+			extra_sheets: Vec::new(),
+			extra_chars: HashMap::new(),
+			fallback_index: '?' as SpriteIndex,
+		}
+	}
+
+	/// Sets the character drawn for the characters that no sheet has (`?` by default),
+	/// which must be in CP437.
+	pub fn set_fallback_char(&mut self, fallback_char: char) -> Result<(), String> {
+		let fallback_index = unicode_to_cp437(fallback_char)
+			.ok_or_else(|| format!("\"{fallback_char}\" is not in CP437"))?;
+		self.fallback_index = fallback_index as SpriteIndex;
+		Ok(())
+	}
+
+	/// Replaces the main sheet, like to change the tileset.
+	pub fn set_main_sheet(&mut self, main_sheet: CharSpriteSheet) {
+		let old_main_sheet = std::mem::replace(&mut self.main_sheet, main_sheet);
+		// SAFETY: The old sheet is not used anymore.
+		unsafe { old_main_sheet.texture.destroy() };
+	}
+
+	/// Loads an extra sprite sheet from a PNG file that comes with a text file of the same
+	/// name (but with the `.txt` extension) that lists its characters, with each line of the
+	/// text being a row of the grid of sprites (and spaces marking unused sprites).
+	/// The tile size is deduced from the size of that grid.
+	pub fn register_extra_sheet_from_filepath(
+		&mut self,
+		filepath: &str,
+		transparency: TransparencyPolicy,
+		texture_creator: &TextureCreator<WindowContext>,
+	) -> Result<(), String> {
+		let chars_filepath = std::path::Path::new(filepath).with_extension("txt");
+		let chars = std::fs::read_to_string(&chars_filepath)
+			.map_err(|error| format!("could not read \"{}\": {error}", chars_filepath.display()))?;
+		let rows: Vec<Vec<char>> = chars.lines().map(|line| line.chars().collect()).collect();
+		let grid_w = rows.iter().map(|row| row.len()).max().unwrap_or(0) as u32;
+		let grid_h = rows.len() as u32;
+		if grid_w == 0 || grid_h == 0 {
+			return Err(format!(
+				"\"{}\" lists no characters",
+				chars_filepath.display()
+			));
+		}
+
+		let raw_surface = Surface::from_file(filepath)?;
+		let image_wh = raw_surface.size();
+		if !image_wh.0.is_multiple_of(grid_w) || !image_wh.1.is_multiple_of(grid_h) {
+			return Err(format!(
+				"a {}x{} image cannot be cut into the {grid_w}x{grid_h} sprites of \"{}\"",
+				image_wh.0,
+				image_wh.1,
+				chars_filepath.display()
+			));
+		}
+		let tile_wh = (image_wh.0 / grid_w, image_wh.1 / grid_h);
+		let sheet =
+			CharSpriteSheet::from_surface(&raw_surface, tile_wh, transparency, texture_creator)?;
+
+		let sheet_index = self.extra_sheets.len();
+		for (y, row) in rows.iter().enumerate() {
+			for (x, &character) in row.iter().enumerate() {
+				if character != ' ' {
+					let char_index = y as u32 * sheet.grid_wh.0 + x as u32;
+					self.extra_chars
+						.insert(character, (sheet_index, char_index));
+				}
+			}
+		}
+		self.extra_sheets.push(sheet);
+		Ok(())
+	}
+
+	pub fn draw_sprite_to_canvas<T: RenderTarget>(
+		&mut self,
+		sprite: Sprite,
+		canvas: &mut Canvas<T>,
+		color: Color,
+		dst: Rect,
+	) {
+		let (sheet, char_index) = match sprite {
+			Sprite::Index(char_index) => (&mut self.main_sheet, char_index),
+			Sprite::Char(character) if character.is_ascii() => {
+				(&mut self.main_sheet, character as SpriteIndex)
+			},
+			Sprite::Char(character) => match self.extra_chars.get(&character) {
+				Some(&(sheet_index, char_index)) => {
+					(&mut self.extra_sheets[sheet_index], char_index)
+				},
+				None => match unicode_to_cp437(character) {
+					Some(char_index) => (&mut self.main_sheet, char_index as SpriteIndex),
+					None => (&mut self.main_sheet, self.fallback_index),
+				},
+			},
+		};
+		sheet.draw_char_to_canvas(char_index, canvas, color, dst);
+	}
+}
+
+/// What is drawn on a tile, either a sprite of the main sheet or a character
+/// (that is drawn from whichever sheet has it, see `CharSpriteSheets`).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sprite {
+	Index(SpriteIndex),
+	Char(char),
+}
+
+/// Pattern drawn over the background of a tile (but under its sprite),
+/// to mark zones without relying only on colors.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BgPattern {
+	LightShade,
+	/// Looks like a checkerboard in most tilesets.
+	MediumShade,
+	DarkShade,
+	DiagonalStripes,
+}
+
+impl BgPattern {
+	pub const ALL: [BgPattern; 4] = [
+		BgPattern::LightShade,
+		BgPattern::MediumShade,
+		BgPattern::DarkShade,
+		BgPattern::DiagonalStripes,
+	];
+
+	/// The CP437 sprite that the pattern is made of.
+	pub fn sprite_index(self) -> SpriteIndex {
+		match self {
+			BgPattern::LightShade => 176,
+			BgPattern::MediumShade => 177,
+			BgPattern::DarkShade => 178,
+			BgPattern::DiagonalStripes => '/' as SpriteIndex,
+		}
+	}
+}
+
+/// How the sprite of a tile is drawn, as some sprites (like the text of the HUD) must stay
+/// readable whatever is around.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GlyphStyle {
+	Plain,
+	/// A copy of the sprite in a dark color is drawn one pixel down and right first.
+	Shadow,
+	/// Copies of the sprite in a dark color are drawn one pixel around it first.
+	Outline,
+}
+
+impl GlyphStyle {
+	pub const ALL: [GlyphStyle; 3] = [GlyphStyle::Plain, GlyphStyle::Shadow, GlyphStyle::Outline];
+
+	const COLOR: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+
+	/// Where to draw the dark copies of the sprite, relative to the sprite.
+	pub fn offsets(self) -> &'static [(i32, i32)] {
+		match self {
+			GlyphStyle::Plain => &[],
+			GlyphStyle::Shadow => &[(1, 1)],
+			GlyphStyle::Outline => &[
+				(-1, -1),
+				(0, -1),
+				(1, -1),
+				(-1, 0),
+				(1, 0),
+				(-1, 1),
+				(0, 1),
+				(1, 1),
+			],
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ScreenTile {
+	pub sprite: Sprite,
+	pub fg_color: Color,
+	pub bg_color: Color,
+	/// A pattern and its color.
+	pub bg_pattern: Option<(BgPattern, Color)>,
+	/// Moves the sprite by that many pixels from where it should be, to show it sliding
+	/// between tiles (see `Slide`).
+	pub sprite_offset: (i32, i32),
+	pub glyph_style: GlyphStyle,
+}
+
+impl Default for ScreenTile {
+	fn default() -> ScreenTile {
+		ScreenTile::new()
+	}
+}
+
+impl ScreenTile {
+	pub fn new() -> ScreenTile {
+		ScreenTile {
+			sprite: Sprite::Index(0),
+			fg_color: theme::text_color(),
+			bg_color: theme::background_color(),
+			bg_pattern: None,
+			sprite_offset: (0, 0),
+			glyph_style: GlyphStyle::Plain,
+		}
+	}
+
+	pub fn from_char(character: char) -> ScreenTile {
+		ScreenTile {
+			sprite: Sprite::Char(character),
+			fg_color: theme::text_color(),
+			bg_color: theme::background_color(),
+			bg_pattern: None,
+			sprite_offset: (0, 0),
+			glyph_style: GlyphStyle::Plain,
+		}
+	}
+
+	/// Dims all the colors of the tile (see `display::dim_color`).
+	pub fn dim(&mut self, factor: f32) {
+		self.fg_color = display::dim_color(self.fg_color, factor);
+		self.bg_color = display::dim_color(self.bg_color, factor);
+		if let Some((_, pattern_color)) = &mut self.bg_pattern {
+			*pattern_color = display::dim_color(*pattern_color, factor);
+		}
+	}
+}
+
+/// Tells by how many pixels to offset the sprite of something that just moved
+/// from a tile to another so that it looks like it slides there instead of teleporting
+/// (see `ScreenTile::sprite_offset`), for a short time after the move.
+///
+/// It is only visual, what moved is already on its new tile.
+pub struct Slide {
+	pub from_xy: (u32, u32),
+	pub to_xy: (u32, u32),
+	pub start: std::time::Instant,
+}
+
+impl Slide {
+	const DURATION: std::time::Duration = std::time::Duration::from_millis(100);
+
+	pub fn new(from_xy: (u32, u32), to_xy: (u32, u32)) -> Slide {
+		Slide { from_xy, to_xy, start: std::time::Instant::now() }
+	}
+
+	/// The offset of the sprite drawn on the `to_xy` tile.
+	pub fn sprite_offset(&self, tile_wh: (u32, u32)) -> (i32, i32) {
+		if !display::smooth_movement() {
+			return (0, 0);
+		}
+		let progress = self.start.elapsed().as_secs_f32() / Slide::DURATION.as_secs_f32();
+		let remaining = 1.0 - progress.min(1.0);
+		let offset = |from: u32, to: u32, tile_size: u32| {
+			((from as f32 - to as f32) * tile_size as f32 * remaining).round() as i32
+		};
+		(
+			offset(self.from_xy.0, self.to_xy.0, tile_wh.0),
+			offset(self.from_xy.1, self.to_xy.1, tile_wh.1),
+		)
+	}
+}
+
+#[derive(Clone)]
+pub struct ScreenGrid {
+	/// The back buffer, where everything is drawn.
+	pub tiles: Vec<ScreenTile>,
+	pub grid_wh: (u32, u32),
+	pub tile_wh: (u32, u32),
+	/// The front buffer, the tiles as they were when last presented to a terminal
+	/// (see `present`), `None` if they have to be presented again entirely.
+	front_tiles: Option<Vec<ScreenTile>>,
+}
+
+/// Tiles of a rectangle of a screen grid (see `ScreenGrid::save_region`).
+pub struct RegionSnapshot {
+	top_left_xy: (u32, u32),
+	/// Width of the rectangle, the tiles being in row-major order.
+	w: u32,
+	tiles: Vec<ScreenTile>,
+}
+
+impl ScreenGrid {
+	pub fn new(grid_wh: (u32, u32), tile_wh: (u32, u32)) -> ScreenGrid {
+		let tiles =
+			std::iter::repeat_n(ScreenTile::new(), (grid_wh.0 * grid_wh.1) as usize).collect();
+		ScreenGrid { tiles, grid_wh, tile_wh, front_tiles: None }
+	}
+
+	pub fn resize_grid(&mut self, new_grid_wh: (u32, u32)) {
+		self.grid_wh = new_grid_wh;
+		self.front_tiles = None;
+		self.tiles = std::iter::repeat_n(
+			ScreenTile::new(),
+			(self.grid_wh.0 * self.grid_wh.1) as usize,
+		)
+		.collect();
+	}
+
+	pub fn tile_index(&self, xy: (u32, u32)) -> usize {
+		assert!(xy.0 < self.grid_wh.0);
+		assert!(xy.1 < self.grid_wh.1);
+		(xy.0 * self.grid_wh.1 + xy.1) as usize
+	}
+
+	/// The tile at the given pixel position, where `dst_rect` is the rectangle (in pixels)
+	/// that the grid is drawn in, which may be scaled to another size than the tiles
+	/// add up to.
+	pub fn pixel_to_grid_coords(&self, pixel_xy: (i32, i32), dst_rect: Rect) -> Option<(u32, u32)> {
+		let x = pixel_xy.0 - dst_rect.x();
+		let y = pixel_xy.1 - dst_rect.y();
+		if x < 0 || y < 0 || x >= dst_rect.width() as i32 || y >= dst_rect.height() as i32 {
+			return None;
+		}
+		Some((
+			(x as u64 * self.grid_wh.0 as u64 / dst_rect.width() as u64) as u32,
+			(y as u64 * self.grid_wh.1 as u64 / dst_rect.height() as u64) as u32,
+		))
+	}
+
+	pub fn tile(&self, xy: (u32, u32)) -> &ScreenTile {
+		let tile_index = self.tile_index(xy);
+		&self.tiles[tile_index]
+	}
+
+	pub fn tile_mut(&mut self, xy: (u32, u32)) -> &mut ScreenTile {
+		let tile_index = self.tile_index(xy);
+		&mut self.tiles[tile_index]
+	}
+
+	pub fn clear(&mut self) {
+		self.tiles = std::iter::repeat_n(
+			ScreenTile::new(),
+			(self.grid_wh.0 * self.grid_wh.1) as usize,
+		)
+		.collect();
+	}
+
+	/// Darkens the tiles in the rectangle with their distance from `center_xy`
+	/// according to the fog display setting (see `display::Fog`).
+	pub fn apply_fog(&mut self, center_xy: (u32, u32), rect: Rect) {
+		let Some(fog) = display::fog() else {
+			return;
+		};
+		for y in rect.top().max(0) as u32..(rect.bottom().max(0) as u32).min(self.grid_wh.1) {
+			for x in rect.left().max(0) as u32..(rect.right().max(0) as u32).min(self.grid_wh.0) {
+				let dx = x as f32 - center_xy.0 as f32;
+				let dy = y as f32 - center_xy.1 as f32;
+				let brightness = fog.brightness((dx * dx + dy * dy).sqrt());
+				self.tile_mut((x, y)).dim(brightness);
+			}
+		}
+	}
+
+	/// A copy of the tiles in the rectangle (cut to fit in the grid), to be put back later
+	/// with `restore`, like to remove something drawn over the grid for a while.
+	pub fn save_region(&self, rect: Rect) -> RegionSnapshot {
+		let left = (rect.left().max(0) as u32).min(self.grid_wh.0);
+		let top = (rect.top().max(0) as u32).min(self.grid_wh.1);
+		let right = (rect.right().max(0) as u32).min(self.grid_wh.0);
+		let bottom = (rect.bottom().max(0) as u32).min(self.grid_wh.1);
+		let mut tiles = Vec::new();
+		for y in top..bottom {
+			for x in left..right {
+				tiles.push(*self.tile((x, y)));
+			}
+		}
+		RegionSnapshot {
+			top_left_xy: (left, top),
+			w: right.saturating_sub(left),
+			tiles,
+		}
+	}
+
+	/// Puts back the tiles saved with `save_region`. Tiles that are not in the grid
+	/// anymore (if the grid got smaller since then) are left out.
+	pub fn restore(&mut self, snapshot: &RegionSnapshot) {
+		if snapshot.w == 0 {
+			return;
+		}
+		for (i, tile) in snapshot.tiles.iter().enumerate() {
+			let x = snapshot.top_left_xy.0 + i as u32 % snapshot.w;
+			let y = snapshot.top_left_xy.1 + i as u32 / snapshot.w;
+			if x < self.grid_wh.0 && y < self.grid_wh.1 {
+				*self.tile_mut((x, y)) = *tile;
+			}
+		}
+	}
+
+	/// A copy of the grid as it is now, to be compared later with `diff`.
+	pub fn snapshot(&self) -> ScreenGrid {
+		ScreenGrid {
+			tiles: self.tiles.clone(),
+			grid_wh: self.grid_wh,
+			tile_wh: self.tile_wh,
+			front_tiles: None,
+		}
+	}
+
+	/// The tiles that are different from the given previous state of the grid (typically
+	/// obtained with `snapshot`), in row-major order. If the grid size changed since then,
+	/// then all the tiles are considered different.
+	pub fn diff(&self, previous: &ScreenGrid) -> Vec<((u32, u32), ScreenTile)> {
+		let is_same_size = previous.grid_wh == self.grid_wh;
+		let mut changes = Vec::new();
+		for y in 0..self.grid_wh.1 {
+			for x in 0..self.grid_wh.0 {
+				let tile = self.tile((x, y));
+				if !is_same_size || previous.tile((x, y)) != tile {
+					changes.push(((x, y), *tile));
+				}
+			}
+		}
+		changes
+	}
+
+	/// Puts in the terminal only the tiles that changed since the grid was last presented
+	/// (all of them the first time, after a resize, and after the terminal was cleared),
+	/// and presents the terminal. This is the only place where tiles are compared, the
+	/// terminals show what they are given. The grid is expected to always be presented
+	/// to the same terminal.
+	///
+	/// The back buffer is copied to the front buffer instead of being swapped with it,
+	/// so that it still has what was drawn (which is kept between frames sometimes).
+	pub fn present(&mut self, terminal: &mut impl Terminal) -> Result<(), String> {
+		let front_tiles = self.front_tiles.take().filter(|_| !terminal.was_cleared());
+		let terminal_wh = terminal.grid_wh();
+		for y in 0..self.grid_wh.1.min(terminal_wh.1) {
+			for x in 0..self.grid_wh.0.min(terminal_wh.0) {
+				let tile_index = self.tile_index((x, y));
+				let tile = &self.tiles[tile_index];
+				if front_tiles
+					.as_ref()
+					.is_none_or(|front_tiles| front_tiles[tile_index] != *tile)
+				{
+					terminal.put((x, y), tile);
+				}
+			}
+		}
+		let result = terminal.present();
+		if result.is_ok() {
+			let mut front_tiles = front_tiles.unwrap_or_default();
+			front_tiles.clone_from(&self.tiles);
+			self.front_tiles = Some(front_tiles);
+		}
+		result
+	}
+
+	pub fn grid_coords_to_rect(&self, xy: (u32, u32)) -> Rect {
+		Rect::new(
+			(xy.0 * self.tile_wh.0) as i32,
+			(xy.1 * self.tile_wh.1) as i32,
+			self.tile_wh.0,
+			self.tile_wh.1,
+		)
+	}
+
+	pub fn draw_to_canvas<T: RenderTarget>(
+		&self,
+		canvas: &mut Canvas<T>,
+		char_sprite_sheets: &mut CharSpriteSheets,
+	) {
+		self.draw_tiles_to_canvas(canvas, char_sprite_sheets, None);
+	}
+
+	/// Like `draw_to_canvas` but only draws again the tiles that are different from
+	/// the grid that was drawn on the canvas previously, which is only correct for a canvas
+	/// that keeps what is drawn on it from frame to frame (like a texture, not a window).
+	///
+	/// What is drawn also depends on the tileset and the display settings, so the whole
+	/// grid has to be drawn again (with `draw_to_canvas`) when they change.
+	pub fn draw_changes_to_canvas<T: RenderTarget>(
+		&self,
+		canvas: &mut Canvas<T>,
+		char_sprite_sheets: &mut CharSpriteSheets,
+		previous: &ScreenGrid,
+	) {
+		if previous.grid_wh != self.grid_wh || previous.tile_wh != self.tile_wh {
+			self.draw_to_canvas(canvas, char_sprite_sheets);
+			return;
+		}
+		let changed: Vec<bool> = self
+			.tiles
+			.iter()
+			.zip(previous.tiles.iter())
+			.map(|(tile, previous_tile)| tile != previous_tile)
+			.collect();
+		if !changed.contains(&true) {
+			return;
+		}
+		// Sprites can go over the neighboring tiles (when sliding or with a glyph style),
+		// so the neighbors of the changed tiles may look different too.
+		let redrawn = self.with_neighbors(&changed);
+		self.draw_tiles_to_canvas(canvas, char_sprite_sheets, Some(&redrawn));
+	}
+
+	/// The tiles that are in the given tiles (indexed like `tiles`) or next to them.
+	fn with_neighbors(&self, tile_set: &[bool]) -> Vec<bool> {
+		let mut extended = vec![false; tile_set.len()];
+		for y in 0..self.grid_wh.1 {
+			for x in 0..self.grid_wh.0 {
+				if !tile_set[self.tile_index((x, y))] {
+					continue;
+				}
+				for neighbor_y in y.saturating_sub(1)..(y + 2).min(self.grid_wh.1) {
+					for neighbor_x in x.saturating_sub(1)..(x + 2).min(self.grid_wh.0) {
+						extended[self.tile_index((neighbor_x, neighbor_y))] = true;
+					}
+				}
+			}
+		}
+		extended
+	}
+
+	/// Draws the given tiles (indexed like `tiles`), or all of them for `None`.
+	fn draw_tiles_to_canvas<T: RenderTarget>(
+		&self,
+		canvas: &mut Canvas<T>,
+		char_sprite_sheets: &mut CharSpriteSheets,
+		redrawn: Option<&[bool]>,
+	) {
+		// The tiles that get their background drawn again lose what the sprites of their
+		// neighbors drew over them, so these sprites must be drawn again too. Their drawing
+		// is kept to the redrawn tiles, as drawing a sprite over itself where it was not
+		// erased would blend its translucent pixels twice (see `TransparencyPolicy::Alpha`).
+		let sprites_redrawn = redrawn.map(|redrawn| self.with_neighbors(redrawn));
+		let is_redrawn = |tile_set: Option<&[bool]>, xy| {
+			tile_set.is_none_or(|tile_set| tile_set[self.tile_index(xy)])
+		};
+
+		let display_filter = display::filter();
+		let glyph_style_color = display_filter.apply(GlyphStyle::COLOR);
+
+		// All the backgrounds go first so that sprites that slide over the neighboring tiles
+		// are not covered by their backgrounds.
+		for y in 0..self.grid_wh.1 {
+			for x in 0..self.grid_wh.0 {
+				let xy = (x, y);
+				if !is_redrawn(redrawn, xy) {
+					continue;
+				}
+				let dst = self.grid_coords_to_rect((x, y));
+
+				// Fill the tile with the background.
+				let bg_color = display_filter.apply(self.tile(xy).bg_color);
+				canvas.set_draw_color(bg_color);
+				canvas.fill_rect(dst).unwrap();
+				if let Some((bg_pattern, pattern_color)) = self.tile(xy).bg_pattern {
+					let pattern_sprite = Sprite::Index(bg_pattern.sprite_index());
+					let pattern_color = display_filter.apply(pattern_color);
+					char_sprite_sheets.draw_sprite_to_canvas(
+						pattern_sprite,
+						canvas,
+						pattern_color,
+						dst,
+					);
+				}
+			}
+		}
+
+		for y in 0..self.grid_wh.1 {
+			for x in 0..self.grid_wh.0 {
+				let xy = (x, y);
+				if !is_redrawn(sprites_redrawn.as_deref(), xy) {
+					continue;
+				}
+				let tile = self.tile(xy);
+				let mut dst = self.grid_coords_to_rect((x, y));
+				dst.offset(tile.sprite_offset.0, tile.sprite_offset.1);
+				let fg_color = display_filter.apply(tile.fg_color);
+				let mut draw_sprite = |canvas: &mut Canvas<T>| {
+					for &(offset_x, offset_y) in tile.glyph_style.offsets() {
+						let mut copy_dst = dst;
+						copy_dst.offset(offset_x, offset_y);
+						char_sprite_sheets.draw_sprite_to_canvas(
+							tile.sprite,
+							canvas,
+							glyph_style_color,
+							copy_dst,
+						);
+					}
+					char_sprite_sheets.draw_sprite_to_canvas(tile.sprite, canvas, fg_color, dst);
+				};
+				let Some(redrawn) = redrawn else {
+					draw_sprite(canvas);
+					continue;
+				};
+				// A sprite goes over its neighbors at most, so it is drawn (clipped) in
+				// each of the redrawn tiles around it.
+				for neighbor_y in y.saturating_sub(1)..(y + 2).min(self.grid_wh.1) {
+					for neighbor_x in x.saturating_sub(1)..(x + 2).min(self.grid_wh.0) {
+						let neighbor_xy = (neighbor_x, neighbor_y);
+						if redrawn[self.tile_index(neighbor_xy)] {
+							canvas.set_clip_rect(self.grid_coords_to_rect(neighbor_xy));
+							draw_sprite(canvas);
+						}
+					}
+				}
+			}
+		}
+		canvas.set_clip_rect(None);
+	}
+}
+
+/// Screen grids drawn on top of each other (like the world, then effects, then the UI),
+/// where a layer can leave tiles empty to let the layers below show through.
+pub struct ScreenLayers {
+	/// The tiles of each layer, from the bottom one to the top one, in the same order
+	/// as `ScreenGrid::tiles`.
+	layers: Vec<Vec<Option<ScreenTile>>>,
+	pub grid_wh: (u32, u32),
+}
+
+impl ScreenLayers {
+	pub fn new(layer_count: usize, grid_wh: (u32, u32)) -> ScreenLayers {
+		let tile_count = (grid_wh.0 * grid_wh.1) as usize;
+		ScreenLayers {
+			layers: vec![vec![None; tile_count]; layer_count],
+			grid_wh,
+		}
+	}
+
+	/// Changes the size of the layers, which empties them.
+	pub fn resize(&mut self, new_grid_wh: (u32, u32)) {
+		*self = ScreenLayers::new(self.layers.len(), new_grid_wh);
+	}
+
+	/// Empties all the layers.
+	pub fn clear(&mut self) {
+		for layer in self.layers.iter_mut() {
+			layer.fill(None);
+		}
+	}
+
+	/// The tile of the layer, `None` if it is empty.
+	pub fn tile_mut(&mut self, layer_index: usize, xy: (u32, u32)) -> &mut Option<ScreenTile> {
+		assert!(xy.0 < self.grid_wh.0);
+		assert!(xy.1 < self.grid_wh.1);
+		let tile_index = (xy.0 * self.grid_wh.1 + xy.1) as usize;
+		&mut self.layers[layer_index][tile_index]
+	}
+
+	/// Puts the tiles of the layers in the grid (of the same size), each tile being
+	/// the one of the topmost layer that is not empty there. Where all the layers
+	/// are empty, the grid keeps its tile.
+	pub fn composite_into(&self, screen_grid: &mut ScreenGrid) {
+		assert!(screen_grid.grid_wh == self.grid_wh);
+		for (tile_index, tile) in screen_grid.tiles.iter_mut().enumerate() {
+			let top_tile = self.layers.iter().rev().find_map(|layer| layer[tile_index]);
+			if let Some(top_tile) = top_tile {
+				*tile = top_tile;
+			}
+		}
+	}
+
+	/// Draws the layers composited on top of each other (see `composite_into`),
+	/// over a blank grid.
+	pub fn draw_to_canvas<T: RenderTarget>(
+		&self,
+		canvas: &mut Canvas<T>,
+		char_sprite_sheets: &mut CharSpriteSheets,
+		tile_wh: (u32, u32),
+	) {
+		let mut screen_grid = ScreenGrid::new(self.grid_wh, tile_wh);
+		self.composite_into(&mut screen_grid);
+		screen_grid.draw_to_canvas(canvas, char_sprite_sheets);
+	}
+}
@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+/// Small and fast pseudo-random number generator (xorshift64*).
+///
+/// It is not meant to be of cryptographic quality, only to be deterministic given a seed
+/// (so that a seed always gives the same results) and to be cheap to use.
+///
+/// It is serialized as its state in hexadecimal, as it does not always fit
+/// in the (signed) integers of TOML.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rng {
+	state: u64,
+}
+
+impl TryFrom<String> for Rng {
+	type Error = String;
+
+	fn try_from(state: String) -> Result<Rng, String> {
+		let state = u64::from_str_radix(&state, 16)
+			.map_err(|error| format!("\"{state}\" is not a random generator state: {error}"))?;
+		Rng::from_state(state).ok_or_else(|| "the random generator state is zero".to_string())
+	}
+}
+
+impl From<Rng> for String {
+	fn from(rng: Rng) -> String {
+		format!("{:x}", rng.state)
+	}
+}
+
+impl Rng {
+	pub fn new(seed: u64) -> Rng {
+		// The state must never be zero (it would stay zero forever),
+		// so the seed is scrambled a bit (with the SplitMix64 finalizer).
+		let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		state = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		state = (state ^ (state >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		state ^= state >> 31;
+		Rng { state: if state == 0 { 1 } else { state } }
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state ^= self.state >> 12;
+		self.state ^= self.state << 25;
+		self.state ^= self.state >> 27;
+		self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+	}
+
+	pub fn next_u32(&mut self) -> u32 {
+		(self.next_u64() >> 32) as u32
+	}
+
+	/// The whole state, to get the same generator back later with `from_state`.
+	pub fn state(&self) -> u64 {
+		self.state
+	}
+
+	/// `None` if the state cannot be one (as zero).
+	pub fn from_state(state: u64) -> Option<Rng> {
+		(state != 0).then_some(Rng { state })
+	}
+
+	/// Random number in `0..max`.
+	pub fn below(&mut self, max: u32) -> u32 {
+		assert!(max > 0);
+		((self.next_u32() as u64 * max as u64) >> 32) as u32
+	}
+}
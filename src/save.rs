@@ -0,0 +1,169 @@
+//! Save files, that keep the state of a game to get back to it later.
+//!
+//! A save file is a TOML file with a `[header]` table, that tells which game and which
+//! version of the save format it is from, and then the state of the game, serialized
+//! with serde (so the state can be any type that derives `Serialize` and `Deserialize`).
+//! Saves from another version of the format are rejected instead of being misread,
+//! so `FORMAT_VERSION` must be bumped whenever what is saved changes.
+
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The version of the save format that is written, and the only one that can be read.
+pub const FORMAT_VERSION: i64 = 2;
+
+const GAME_NAME: &str = "why-crystals";
+
+/// Writes the save file, with the header added to the state.
+pub fn write_save(path: &Path, state: &impl Serialize) -> Result<(), String> {
+	let toml::Value::Table(mut state) = toml::Value::try_from(state)
+		.map_err(|error| format!("could not serialize the state: {error}"))?
+	else {
+		return Err("the state is not serialized as a table".to_string());
+	};
+	let mut header = toml::Table::new();
+	header.insert("game".to_string(), GAME_NAME.into());
+	header.insert("format_version".to_string(), FORMAT_VERSION.into());
+	state.insert("header".to_string(), header.into());
+	std::fs::write(path, state.to_string())
+		.map_err(|error| format!("could not write \"{}\": {error}", path.display()))
+}
+
+/// Reads the save file, checking the header (that is not part of the state).
+pub fn read_save<State: DeserializeOwned>(path: &Path) -> Result<State, String> {
+	let text = std::fs::read_to_string(path)
+		.map_err(|error| format!("could not read \"{}\": {error}", path.display()))?;
+	let mut state: toml::Table = text
+		.parse()
+		.map_err(|error| format!("\"{}\" is not a save file: {error}", path.display()))?;
+	let header = state.remove("header");
+	let header = header.as_ref().and_then(toml::Value::as_table);
+	if header.and_then(|header| header.get("game")?.as_str()) != Some(GAME_NAME) {
+		return Err(format!(
+			"\"{}\" is not a save file of this game",
+			path.display()
+		));
+	}
+	let format_version = header.and_then(|header| header.get("format_version")?.as_integer());
+	if format_version != Some(FORMAT_VERSION) {
+		let format_version = format_version.map_or("unknown".to_string(), |v| v.to_string());
+		return Err(format!(
+			"\"{}\" is a save from another version of the game \
+			(save format {format_version}, only {FORMAT_VERSION} is supported)",
+			path.display()
+		));
+	}
+	toml::Value::Table(state)
+		.try_into()
+		.map_err(|error| format!("\"{}\" is a broken save: {error}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::rng::Rng;
+	use crate::world::TileMap;
+	use serde::Deserialize;
+
+	/// A file in the temporary directory, removed when dropped.
+	struct TempFile(std::path::PathBuf);
+
+	impl TempFile {
+		fn new(name: &str) -> TempFile {
+			let filename = format!("why-crystals-test-{}-{name}.toml", std::process::id());
+			TempFile(std::env::temp_dir().join(filename))
+		}
+	}
+
+	impl Drop for TempFile {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_file(&self.0);
+		}
+	}
+
+	#[derive(Serialize, Deserialize)]
+	struct State {
+		play_time: f64,
+		name: String,
+		player_xy: (u32, u32),
+		crystals: Vec<(u32, u32)>,
+		map: TileMap,
+		rng: Rng,
+	}
+
+	#[test]
+	fn a_saved_state_reads_back_the_same() {
+		let file = TempFile::new("round-trip");
+		let mut rng = Rng::new(u64::MAX);
+		let map = TileMap::generate_cave((20, 10), &mut rng);
+		let state = State {
+			play_time: 1.5,
+			name: "Zed".to_string(),
+			player_xy: (3, 4),
+			crystals: vec![(1, 2), (5, 6)],
+			map,
+			rng,
+		};
+		write_save(&file.0, &state).unwrap();
+
+		let mut read_state: State = read_save(&file.0).unwrap();
+		assert_eq!(read_state.play_time, 1.5);
+		assert_eq!(read_state.name, "Zed");
+		assert_eq!(read_state.player_xy, (3, 4));
+		assert_eq!(read_state.crystals, [(1, 2), (5, 6)]);
+		assert_eq!(read_state.map.to_rows(), state.map.to_rows());
+		assert_eq!(read_state.rng.next_u64(), state.rng.clone().next_u64());
+	}
+
+	#[test]
+	fn missing_or_mistyped_values_are_errors() {
+		let file = TempFile::new("mistyped");
+		let header = "[header]\ngame = \"why-crystals\"\nformat_version = 2\n";
+		let state = "play_time = 1.0\nname = \"a\"\ncrystals = []\nrng = \"1\"\n";
+		let player_line = "player_xy = [1, 2]\n";
+		let map_line = "map = [\"#.\", \"~*\"]\n";
+		for broken_lines in [
+			format!("player_xy = [1, -2]\n{map_line}"),
+			format!("player_xy = [1]\n{map_line}"),
+			format!("{player_line}map = [\"#?\"]\n"),
+			format!("{player_line}map = [\"#\", \"..\"]\n"),
+			map_line.to_string(),
+		] {
+			std::fs::write(&file.0, format!("{broken_lines}{state}{header}")).unwrap();
+			assert!(
+				read_save::<State>(&file.0).is_err(),
+				"{broken_lines:?} was read"
+			);
+		}
+		std::fs::write(&file.0, format!("{player_line}{map_line}{state}{header}")).unwrap();
+		assert!(read_save::<State>(&file.0).is_ok());
+	}
+
+	#[test]
+	fn other_files_are_rejected() {
+		let file = TempFile::new("other-version");
+		std::fs::write(
+			&file.0,
+			"[header]\ngame = \"why-crystals\"\nformat_version = 1\n",
+		)
+		.unwrap();
+		assert!(read_save::<toml::Table>(&file.0).is_err());
+		std::fs::write(&file.0, "[header]\ngame = \"other\"\nformat_version = 2\n").unwrap();
+		assert!(read_save::<toml::Table>(&file.0).is_err());
+		std::fs::write(&file.0, "not toml at all [").unwrap();
+		assert!(read_save::<toml::Table>(&file.0).is_err());
+	}
+
+	#[test]
+	fn the_rng_state_can_be_saved() {
+		let mut rng = Rng::new(9);
+		rng.next_u64();
+		let mut restored = Rng::from_state(rng.state()).unwrap();
+		assert_eq!(restored.next_u64(), rng.next_u64());
+		assert!(Rng::from_state(0).is_none());
+		assert!(Rng::try_from("0".to_string()).is_err());
+		assert!(Rng::try_from("xyz".to_string()).is_err());
+	}
+}
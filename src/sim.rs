@@ -0,0 +1,3 @@
+//! Simulations of what goes on in the world on its own, stepped every tick.
+
+pub mod crystal;
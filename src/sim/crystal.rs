@@ -0,0 +1,294 @@
+//! Crystals growing in the open parts of the cave, as a cellular automaton.
+//!
+//! Each tick, every open tile that touches crystals may turn into a crystal of the species
+//! it touches the most, with a chance that depends on how many of its 8 neighbors are
+//! of that species. Species that only grow with few neighbors make thin branches,
+//! and species that need many neighbors make compact blobs.
+
+use sdl2::pixels::Color;
+
+use crate::render::{ScreenGrid, Sprite};
+use crate::rng::Rng;
+use crate::world::{Camera, Terrain, TileMap};
+
+pub struct CrystalSpecies {
+	pub name: String,
+	pub sprite: Sprite,
+	pub color: Color,
+	/// The chance (per tick) for an open tile to become a crystal of this species,
+	/// indexed by how many of its neighbors are of this species.
+	pub growth_chances: [f32; 9],
+}
+
+impl CrystalSpecies {
+	/// Some species that grow differently, to have something to look at.
+	pub fn default_species() -> Vec<CrystalSpecies> {
+		vec![
+			CrystalSpecies {
+				name: "needle".to_string(),
+				sprite: Sprite::Char('/'),
+				color: Color::RGB(96, 208, 240),
+				growth_chances: [0.0, 0.02, 0.004, 0.001, 0.0, 0.0, 0.0, 0.0, 0.0],
+			},
+			CrystalSpecies {
+				name: "geode".to_string(),
+				sprite: Sprite::Index(4),
+				color: Color::RGB(180, 90, 230),
+				growth_chances: [0.0, 0.002, 0.006, 0.02, 0.04, 0.06, 0.08, 0.1, 0.1],
+			},
+			CrystalSpecies {
+				name: "star".to_string(),
+				sprite: Sprite::Char('*'),
+				color: Color::RGB(240, 200, 60),
+				growth_chances: [0.0, 0.008, 0.008, 0.008, 0.008, 0.008, 0.008, 0.008, 0.008],
+			},
+		]
+	}
+}
+
+/// Which species of crystal grew on which tiles of a map.
+pub struct CrystalGrowth {
+	pub species: Vec<CrystalSpecies>,
+	/// The species index of the crystal of each tile, in the same order as the map tiles.
+	cells: Vec<Option<usize>>,
+	map_wh: (u32, u32),
+}
+
+impl CrystalGrowth {
+	pub fn new(map_wh: (u32, u32), species: Vec<CrystalSpecies>) -> CrystalGrowth {
+		CrystalGrowth {
+			species,
+			cells: vec![None; (map_wh.0 * map_wh.1) as usize],
+			map_wh,
+		}
+	}
+
+	fn cell_index(&self, xy: (u32, u32)) -> usize {
+		assert!(xy.0 < self.map_wh.0);
+		assert!(xy.1 < self.map_wh.1);
+		(xy.0 * self.map_wh.1 + xy.1) as usize
+	}
+
+	/// The species of the crystal that grew there, if any.
+	pub fn species_at(&self, xy: (u32, u32)) -> Option<&CrystalSpecies> {
+		self.cells[self.cell_index(xy)].map(|species_index| &self.species[species_index])
+	}
+
+	/// Makes a crystal of the species appear there, for others to grow from.
+	pub fn plant_seed(&mut self, map: &mut TileMap, xy: (u32, u32), species_index: usize) {
+		assert!(species_index < self.species.len());
+		let cell_index = self.cell_index(xy);
+		self.cells[cell_index] = Some(species_index);
+		*map.terrain_mut(xy) = Terrain::Crystal;
+	}
+
+	/// The species of each tile as lines of digits (in base 36) of the species indices,
+	/// with `.` where there is no crystal, from the top line down.
+	pub fn cells_to_rows(&self) -> Vec<String> {
+		(0..self.map_wh.1)
+			.map(|y| {
+				(0..self.map_wh.0)
+					.map(|x| match self.cells[self.cell_index((x, y))] {
+						None => '.',
+						Some(species_index) => char::from_digit(species_index as u32, 36)
+							.expect("there are too many species to save them"),
+					})
+					.collect()
+			})
+			.collect()
+	}
+
+	/// Puts back the crystals saved by `cells_to_rows`, the species being the same.
+	pub fn set_cells_from_rows(&mut self, rows: &[String]) -> Result<(), String> {
+		if rows.len() != self.map_wh.1 as usize {
+			return Err("the crystal rows do not match the map".to_string());
+		}
+		for (y, row) in rows.iter().enumerate() {
+			if row.chars().count() != self.map_wh.0 as usize {
+				return Err(format!("crystal row {y} does not match the map"));
+			}
+			for (x, symbol) in row.chars().enumerate() {
+				let species_index = match symbol {
+					'.' => None,
+					symbol => Some(
+						symbol
+							.to_digit(36)
+							.map(|digit| digit as usize)
+							.filter(|&digit| digit < self.species.len())
+							.ok_or_else(|| {
+								format!("'{symbol}' in crystal row {y} is not a species")
+							})?,
+					),
+				};
+				let cell_index = self.cell_index((x as u32, y as u32));
+				self.cells[cell_index] = species_index;
+			}
+		}
+		Ok(())
+	}
+
+	/// Plants a seed of each species on random open tiles (if any are found).
+	pub fn plant_random_seeds(&mut self, map: &mut TileMap, rng: &mut Rng) {
+		for species_index in 0..self.species.len() {
+			// Random tries, so a map with almost no open tiles may get fewer seeds.
+			for _ in 0..100 {
+				let xy = (rng.below(self.map_wh.0), rng.below(self.map_wh.1));
+				if map.terrain(xy).is_passable() {
+					self.plant_seed(map, xy, species_index);
+					break;
+				}
+			}
+		}
+	}
+
+	/// One tick of growth, where the crystals that grow all see the crystals as they
+	/// were before the tick. They do not grow on the tiles for which `is_occupied` returns
+	/// `true` (like the tiles where the entities are, that would get stuck in crystal).
+	pub fn step(
+		&mut self,
+		map: &mut TileMap,
+		rng: &mut Rng,
+		is_occupied: impl Fn((u32, u32)) -> bool,
+	) {
+		let mut new_crystals = Vec::new();
+		let mut neighbor_counts = vec![0; self.species.len()];
+		for x in 0..self.map_wh.0 {
+			for y in 0..self.map_wh.1 {
+				if !map.terrain((x, y)).is_passable() || is_occupied((x, y)) {
+					continue;
+				}
+				neighbor_counts.fill(0);
+				for dx in -1..=1 {
+					for dy in -1..=1 {
+						let neighbor_xy = (x as i32 + dx, y as i32 + dy);
+						if (dx, dy) == (0, 0) || !map.contains(neighbor_xy) {
+							continue;
+						}
+						let neighbor_xy = (neighbor_xy.0 as u32, neighbor_xy.1 as u32);
+						if let Some(species_index) = self.cells[self.cell_index(neighbor_xy)] {
+							neighbor_counts[species_index] += 1;
+						}
+					}
+				}
+				// The species with the most neighbors wins (the first one on a tie).
+				let Some((species_index, &count)) = neighbor_counts
+					.iter()
+					.enumerate()
+					.rev()
+					.max_by_key(|&(_, count)| count)
+				else {
+					continue;
+				};
+				let chance = self.species[species_index].growth_chances[count];
+				if count > 0 && (rng.below(1_000_000) as f32) < chance * 1_000_000.0 {
+					new_crystals.push(((x, y), species_index));
+				}
+			}
+		}
+		for (xy, species_index) in new_crystals {
+			self.plant_seed(map, xy, species_index);
+		}
+	}
+
+	/// Draws the crystals in view over the map drawn by the camera.
+	pub fn draw(&self, camera: &Camera, screen_grid: &mut ScreenGrid) {
+		for x in 0..self.map_wh.0 {
+			for y in 0..self.map_wh.1 {
+				let Some(species) = self.species_at((x, y)) else {
+					continue;
+				};
+				let Some(screen_xy) = camera.map_xy_to_screen_xy((x as i32, y as i32)) else {
+					continue;
+				};
+				if screen_xy.0 >= screen_grid.grid_wh.0 || screen_xy.1 >= screen_grid.grid_wh.1 {
+					continue;
+				}
+				let tile = screen_grid.tile_mut(screen_xy);
+				tile.sprite = species.sprite;
+				tile.fg_color = species.color;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Species that always grow next to at least one crystal of their own.
+	fn always_growing_species(count: usize) -> Vec<CrystalSpecies> {
+		(0..count)
+			.map(|index| CrystalSpecies {
+				name: format!("species {index}"),
+				sprite: Sprite::Char('*'),
+				color: Color::RGB(255, 255, 255),
+				growth_chances: [0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0],
+			})
+			.collect()
+	}
+
+	fn species_index_at(growth: &CrystalGrowth, xy: (u32, u32)) -> Option<usize> {
+		growth.cells[growth.cell_index(xy)]
+	}
+
+	#[test]
+	fn the_first_species_wins_a_tie() {
+		let mut map = TileMap::new((3, 1), Terrain::Air);
+		let mut growth = CrystalGrowth::new((3, 1), always_growing_species(2));
+		growth.plant_seed(&mut map, (2, 0), 1);
+		growth.plant_seed(&mut map, (0, 0), 0);
+		growth.step(&mut map, &mut Rng::new(0), |_| false);
+		assert_eq!(species_index_at(&growth, (1, 0)), Some(0));
+		assert_eq!(map.terrain((1, 0)), Terrain::Crystal);
+	}
+
+	#[test]
+	fn the_species_with_the_most_neighbors_wins() {
+		let mut map = TileMap::new((3, 2), Terrain::Air);
+		let mut growth = CrystalGrowth::new((3, 2), always_growing_species(2));
+		growth.plant_seed(&mut map, (0, 0), 0);
+		growth.plant_seed(&mut map, (2, 0), 1);
+		growth.plant_seed(&mut map, (2, 1), 1);
+		growth.step(&mut map, &mut Rng::new(0), |_| false);
+		assert_eq!(species_index_at(&growth, (1, 0)), Some(1));
+	}
+
+	#[test]
+	fn crystals_do_not_grow_on_occupied_tiles() {
+		let mut map = TileMap::new((3, 1), Terrain::Air);
+		let mut growth = CrystalGrowth::new((3, 1), always_growing_species(1));
+		growth.plant_seed(&mut map, (0, 0), 0);
+		for _ in 0..3 {
+			growth.step(&mut map, &mut Rng::new(0), |xy| xy == (1, 0));
+		}
+		assert_eq!(species_index_at(&growth, (1, 0)), None);
+		assert_eq!(map.terrain((1, 0)), Terrain::Air);
+		// Nor through them, as nothing touches the other side.
+		assert_eq!(species_index_at(&growth, (2, 0)), None);
+	}
+
+	#[test]
+	fn cells_read_back_from_rows_are_the_same() {
+		let mut rng = Rng::new(7);
+		let mut map = TileMap::generate_cave((20, 10), &mut rng);
+		let mut growth = CrystalGrowth::new((20, 10), always_growing_species(3));
+		growth.plant_random_seeds(&mut map, &mut rng);
+		growth.step(&mut map, &mut rng, |_| false);
+		let rows = growth.cells_to_rows();
+		assert_eq!(rows.len(), 10);
+
+		let mut read_growth = CrystalGrowth::new((20, 10), always_growing_species(3));
+		read_growth.set_cells_from_rows(&rows).unwrap();
+		assert_eq!(read_growth.cells, growth.cells);
+	}
+
+	#[test]
+	fn rows_that_do_not_match_are_errors() {
+		let mut growth = CrystalGrowth::new((2, 2), always_growing_species(2));
+		let rows = |rows: &[&str]| rows.iter().map(|row| row.to_string()).collect::<Vec<_>>();
+		assert!(growth.set_cells_from_rows(&rows(&[".."])).is_err());
+		assert!(growth.set_cells_from_rows(&rows(&["..", "..."])).is_err());
+		assert!(growth.set_cells_from_rows(&rows(&["..", ".2"])).is_err());
+		assert!(growth.set_cells_from_rows(&rows(&["0.", ".1"])).is_ok());
+	}
+}
@@ -0,0 +1,377 @@
+//! Live spectating of a game over TCP.
+//!
+//! The game being watched runs a `SpectateServer` that sends to every connected spectator
+//! the tiles of the screen grid that changed since the previous frame, and the viewer
+//! (`SpectateClient`) applies these changes to its own screen grid to draw it.
+//!
+//! The protocol is a stream of messages, each starting with a tag byte,
+//! with all the numbers being big-endian:
+//! - `0`: the grid size changed (and all the tiles are reset),
+//!   followed by the grid width and height as two `u32`s.
+//! - `1`: a frame, followed by the number of changed tiles as a `u32` and then the tiles,
+//!   each being its coordinates as two `u32`s and then the tile itself (see `encode_tile`).
+
+use std::io::{BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sdl2::pixels::Color;
+
+use crate::devlog::log;
+use crate::{BgPattern, GlyphStyle, ScreenGrid, ScreenTile, Sprite};
+
+const TAG_RESIZE: u8 = 0;
+const TAG_FRAME: u8 = 1;
+
+/// How many messages can wait to be written to a spectator, past which the spectator
+/// skips frames (and then gets the whole grid).
+const SPECTATOR_QUEUE_LENGTH: usize = 8;
+
+/// Grid sizes sent by a server cannot have more tiles than that, not to have the spectator
+/// try to allocate whatever a broken (or mean) server asks for.
+const MAX_GRID_TILE_COUNT: u32 = 1 << 20;
+
+/// A spectator that takes longer than that to accept a message is considered gone.
+const SPECTATOR_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A tile is its sprite (a tag byte, `0` for a sprite index or `1` for a character,
+/// followed by the index or the Unicode scalar value as a `u32`), then the foreground
+/// and background colors as RGB bytes, and then its background pattern (a byte, `0`
+/// for none or one plus the index of the pattern in `BgPattern::ALL`, followed by
+/// the pattern color as RGB bytes if there is a pattern), and then its glyph style
+/// (a byte, the index of the style in `GlyphStyle::ALL`).
+fn encode_tile(tile: &ScreenTile, bytes: &mut Vec<u8>) {
+	match tile.sprite {
+		Sprite::Index(index) => {
+			bytes.push(0);
+			bytes.extend(index.to_be_bytes());
+		},
+		Sprite::Char(character) => {
+			bytes.push(1);
+			bytes.extend((character as u32).to_be_bytes());
+		},
+	}
+	bytes.extend([tile.fg_color.r, tile.fg_color.g, tile.fg_color.b]);
+	bytes.extend([tile.bg_color.r, tile.bg_color.g, tile.bg_color.b]);
+	match tile.bg_pattern {
+		None => bytes.push(0),
+		Some((bg_pattern, color)) => {
+			let index = BgPattern::ALL
+				.iter()
+				.position(|&pattern| pattern == bg_pattern)
+				.unwrap();
+			bytes.push(index as u8 + 1);
+			bytes.extend([color.r, color.g, color.b]);
+		},
+	}
+	let glyph_style_index = GlyphStyle::ALL
+		.iter()
+		.position(|&style| style == tile.glyph_style);
+	bytes.push(glyph_style_index.unwrap() as u8);
+}
+
+fn read_u8(stream: &mut impl Read) -> std::io::Result<u8> {
+	let mut bytes = [0; 1];
+	stream.read_exact(&mut bytes)?;
+	Ok(bytes[0])
+}
+
+fn read_u32(stream: &mut impl Read) -> std::io::Result<u32> {
+	let mut bytes = [0; 4];
+	stream.read_exact(&mut bytes)?;
+	Ok(u32::from_be_bytes(bytes))
+}
+
+fn decode_tile(stream: &mut impl Read) -> std::io::Result<ScreenTile> {
+	let sprite = match read_u8(stream)? {
+		0 => Sprite::Index(read_u32(stream)?),
+		1 => Sprite::Char(char::from_u32(read_u32(stream)?).unwrap_or('?')),
+		tag => {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("unknown sprite tag {tag}"),
+			))
+		},
+	};
+	let mut rgbs = [0; 6];
+	stream.read_exact(&mut rgbs)?;
+	let fg_color = Color::RGB(rgbs[0], rgbs[1], rgbs[2]);
+	let bg_color = Color::RGB(rgbs[3], rgbs[4], rgbs[5]);
+	let bg_pattern = match read_u8(stream)? {
+		0 => None,
+		tag => {
+			let bg_pattern = *BgPattern::ALL.get(tag as usize - 1).ok_or_else(|| {
+				std::io::Error::new(
+					std::io::ErrorKind::InvalidData,
+					format!("unknown background pattern tag {tag}"),
+				)
+			})?;
+			let mut rgb = [0; 3];
+			stream.read_exact(&mut rgb)?;
+			Some((bg_pattern, Color::RGB(rgb[0], rgb[1], rgb[2])))
+		},
+	};
+	let glyph_style_tag = read_u8(stream)?;
+	let glyph_style = *GlyphStyle::ALL
+		.get(glyph_style_tag as usize)
+		.ok_or_else(|| {
+			std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("unknown glyph style tag {glyph_style_tag}"),
+			)
+		})?;
+	Ok(ScreenTile {
+		sprite,
+		fg_color,
+		bg_color,
+		bg_pattern,
+		sprite_offset: (0, 0),
+		glyph_style,
+	})
+}
+
+/// A frame message with the given tiles, after a resize message if the grid size is given.
+fn frame_message(grid_wh: Option<(u32, u32)>, tiles: &[((u32, u32), ScreenTile)]) -> Vec<u8> {
+	let mut message = Vec::new();
+	if let Some(grid_wh) = grid_wh {
+		message.push(TAG_RESIZE);
+		message.extend(grid_wh.0.to_be_bytes());
+		message.extend(grid_wh.1.to_be_bytes());
+	}
+	message.push(TAG_FRAME);
+	message.extend((tiles.len() as u32).to_be_bytes());
+	for ((x, y), tile) in tiles.iter() {
+		message.extend(x.to_be_bytes());
+		message.extend(y.to_be_bytes());
+		encode_tile(tile, &mut message);
+	}
+	message
+}
+
+/// A connected spectator, with its own thread that writes the messages to it.
+struct Spectator {
+	message_sender: mpsc::SyncSender<Arc<Vec<u8>>>,
+	/// Set when it just connected or when it skipped a message,
+	/// so that it needs the whole grid and not only what changed.
+	needs_whole_grid: bool,
+}
+
+impl Spectator {
+	fn start(mut stream: TcpStream) -> Spectator {
+		let _ = stream.set_nodelay(true);
+		let _ = stream.set_write_timeout(Some(SPECTATOR_WRITE_TIMEOUT));
+		let (message_sender, message_receiver) =
+			mpsc::sync_channel::<Arc<Vec<u8>>>(SPECTATOR_QUEUE_LENGTH);
+		std::thread::spawn(move || {
+			// When the writing fails the receiver is dropped, so that the server
+			// notices that the spectator is gone.
+			for message in message_receiver.iter() {
+				if stream.write_all(&message).is_err() {
+					break;
+				}
+			}
+		});
+		Spectator { message_sender, needs_whole_grid: true }
+	}
+}
+
+pub struct SpectateServer {
+	spectators: Arc<Mutex<Vec<Spectator>>>,
+	previous_grid: Option<ScreenGrid>,
+}
+
+impl SpectateServer {
+	/// Starts listening for spectators on the given address (like `"0.0.0.0:7777"`).
+	pub fn start(address: &str) -> std::io::Result<SpectateServer> {
+		let listener = TcpListener::bind(address)?;
+		let spectators: Arc<Mutex<Vec<Spectator>>> = Arc::new(Mutex::new(Vec::new()));
+		{
+			let spectators = Arc::clone(&spectators);
+			std::thread::spawn(move || {
+				for stream in listener.incoming().flatten() {
+					let spectator = Spectator::start(stream);
+					spectators.lock().unwrap().push(spectator);
+				}
+			});
+		}
+		Ok(SpectateServer { spectators, previous_grid: None })
+	}
+
+	/// Sends to the spectators what changed in the grid since the previous call
+	/// (or the whole grid to those who need it).
+	///
+	/// This never waits for the spectators, so that a slow spectator cannot slow the game
+	/// nor the other spectators: a spectator whose queue is full skips the frame.
+	pub fn send_frame(&mut self, screen_grid: &ScreenGrid) {
+		let previous_grid = self
+			.previous_grid
+			.take()
+			.filter(|previous_grid| previous_grid.grid_wh == screen_grid.grid_wh);
+		let changed_tiles = previous_grid
+			.as_ref()
+			.map(|previous_grid| screen_grid.diff(previous_grid));
+		let mut whole_grid_message = None;
+		let mut changes_message = None;
+
+		self.spectators.lock().unwrap().retain_mut(|spectator| {
+			let message = match &changed_tiles {
+				Some(changed_tiles) if !spectator.needs_whole_grid => {
+					if changed_tiles.is_empty() {
+						return true;
+					}
+					changes_message
+						.get_or_insert_with(|| Arc::new(frame_message(None, changed_tiles)))
+				},
+				_ => whole_grid_message.get_or_insert_with(|| {
+					let empty_grid = ScreenGrid::new((0, 0), screen_grid.tile_wh);
+					let tiles = screen_grid.diff(&empty_grid);
+					Arc::new(frame_message(Some(screen_grid.grid_wh), &tiles))
+				}),
+			};
+			match spectator.message_sender.try_send(Arc::clone(message)) {
+				Ok(()) => {
+					spectator.needs_whole_grid = false;
+					true
+				},
+				Err(TrySendError::Full(_)) => {
+					spectator.needs_whole_grid = true;
+					true
+				},
+				// Its thread stopped writing, it is gone.
+				Err(TrySendError::Disconnected(_)) => false,
+			}
+		});
+
+		self.previous_grid = Some(screen_grid.snapshot());
+	}
+}
+
+pub enum SpectateUpdate {
+	Resize((u32, u32)),
+	Frame(Vec<((u32, u32), ScreenTile)>),
+	Disconnected,
+}
+
+/// Reads one message sent by a `SpectateServer`.
+fn read_update(stream: &mut impl Read) -> std::io::Result<SpectateUpdate> {
+	match read_u8(stream)? {
+		TAG_RESIZE => {
+			let grid_wh = (read_u32(stream)?, read_u32(stream)?);
+			let tile_count = grid_wh.0.checked_mul(grid_wh.1);
+			if tile_count.is_none_or(|tile_count| tile_count > MAX_GRID_TILE_COUNT) {
+				return Err(std::io::Error::new(
+					std::io::ErrorKind::InvalidData,
+					format!("grid size {}x{} is too big", grid_wh.0, grid_wh.1),
+				));
+			}
+			Ok(SpectateUpdate::Resize(grid_wh))
+		},
+		TAG_FRAME => {
+			let tile_count = read_u32(stream)?;
+			let mut tiles = Vec::with_capacity(tile_count.min(1 << 16) as usize);
+			for _ in 0..tile_count {
+				let xy = (read_u32(stream)?, read_u32(stream)?);
+				tiles.push((xy, decode_tile(stream)?));
+			}
+			Ok(SpectateUpdate::Frame(tiles))
+		},
+		tag => Err(std::io::Error::new(
+			std::io::ErrorKind::InvalidData,
+			format!("unknown message tag {tag}"),
+		)),
+	}
+}
+
+/// Connection to a `SpectateServer`, reading what it sends on another thread.
+pub struct SpectateClient {
+	update_receiver: mpsc::Receiver<SpectateUpdate>,
+}
+
+impl SpectateClient {
+	pub fn connect(address: &str) -> std::io::Result<SpectateClient> {
+		let mut stream = BufReader::new(TcpStream::connect(address)?);
+		let (update_sender, update_receiver) = mpsc::channel();
+		std::thread::spawn(move || loop {
+			match read_update(&mut stream) {
+				Ok(update) => {
+					if update_sender.send(update).is_err() {
+						break;
+					}
+				},
+				Err(error) => {
+					log!(Warning, "Spectating stopped: {error}");
+					let _ = update_sender.send(SpectateUpdate::Disconnected);
+					break;
+				},
+			}
+		});
+		Ok(SpectateClient { update_receiver })
+	}
+
+	/// The updates that arrived since the previous call.
+	pub fn updates(&self) -> impl Iterator<Item = SpectateUpdate> + '_ {
+		self.update_receiver.try_iter()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tiles_round_trip() {
+		let tiles = [
+			ScreenTile::from_char('é'),
+			ScreenTile {
+				sprite: Sprite::Index(219),
+				fg_color: Color::RGB(1, 2, 3),
+				bg_color: Color::RGB(250, 128, 0),
+				bg_pattern: Some((BgPattern::ALL[1], Color::RGB(9, 8, 7))),
+				sprite_offset: (0, 0),
+				glyph_style: GlyphStyle::ALL[GlyphStyle::ALL.len() - 1],
+			},
+		];
+		for tile in tiles {
+			let mut bytes = Vec::new();
+			encode_tile(&tile, &mut bytes);
+			let mut reader = bytes.as_slice();
+			assert_eq!(decode_tile(&mut reader).unwrap(), tile);
+			assert!(reader.is_empty());
+		}
+	}
+
+	#[test]
+	fn frames_round_trip() {
+		let tiles = [((3, 4), ScreenTile::from_char('a'))];
+		let message = frame_message(Some((10, 5)), &tiles);
+		let mut reader = message.as_slice();
+		assert!(matches!(
+			read_update(&mut reader).unwrap(),
+			SpectateUpdate::Resize((10, 5))
+		));
+		let SpectateUpdate::Frame(read_tiles) = read_update(&mut reader).unwrap() else {
+			panic!("expected a frame");
+		};
+		assert_eq!(read_tiles, tiles);
+	}
+
+	#[test]
+	fn huge_grids_are_rejected() {
+		for grid_wh in [(u32::MAX, 2), (1 << 16, 1 << 16)] {
+			let message = frame_message(Some(grid_wh), &[]);
+			let error = read_update(&mut message.as_slice()).err().unwrap();
+			assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+		}
+	}
+
+	#[test]
+	fn unknown_tags_are_rejected() {
+		assert!(read_update(&mut [7u8].as_slice()).is_err());
+		let mut bytes = Vec::new();
+		encode_tile(&ScreenTile::new(), &mut bytes);
+		bytes[0] = 2;
+		assert!(decode_tile(&mut bytes.as_slice()).is_err());
+	}
+}
@@ -0,0 +1,302 @@
+//! Telnet server that shows the game in the terminal of whoever connects to it,
+//! using ANSI escape sequences (see `ansi`).
+//!
+//! Each session is a `CrosstermTerminal` that the frames are presented to (see
+//! `ScreenGrid::present`), so it gets only what changed on the screen since what it last
+//! got, and slow sessions just skip frames. Pressing Ctrl-C (or Ctrl-D) ends a session.
+//!
+//! The keys pressed in the sessions are given to the game (see `take_key_events`), as if
+//! they were pressed in the window. Note that all the sessions see and play the same game,
+//! there is no game per session.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+
+use crate::input::KeyCombo;
+use crate::terminal::{CrosstermTerminal, Terminal};
+use crate::ScreenGrid;
+
+const ESCAPE: u8 = 27;
+const IAC: u8 = 255;
+const WILL: u8 = 251;
+const DONT: u8 = 254;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const OPTION_ECHO: u8 = 1;
+const OPTION_SUPPRESS_GO_AHEAD: u8 = 3;
+
+/// A session that takes longer than that to accept a frame is considered gone
+/// (like for spectators, see `spectate`).
+const SESSION_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct LatestFrame {
+	number: u64,
+	screen_grid: Option<Arc<ScreenGrid>>,
+}
+
+struct Shared {
+	latest_frame: Mutex<LatestFrame>,
+	new_frame: Condvar,
+	session_count: AtomicUsize,
+	/// The keys pressed in all the sessions, yet to be taken by the game.
+	key_combos: Mutex<Vec<KeyCombo>>,
+}
+
+pub struct TelnetServer {
+	shared: Arc<Shared>,
+}
+
+impl TelnetServer {
+	/// Starts accepting telnet connections on the given address (like `"0.0.0.0:2323"`).
+	pub fn start(address: &str) -> std::io::Result<TelnetServer> {
+		let listener = TcpListener::bind(address)?;
+		let shared = Arc::new(Shared {
+			latest_frame: Mutex::new(LatestFrame { number: 0, screen_grid: None }),
+			new_frame: Condvar::new(),
+			session_count: AtomicUsize::new(0),
+			key_combos: Mutex::new(Vec::new()),
+		});
+		let shared_for_listener = Arc::clone(&shared);
+		std::thread::spawn(move || {
+			for stream in listener.incoming().flatten() {
+				let shared = Arc::clone(&shared_for_listener);
+				std::thread::spawn(move || run_session(stream, shared));
+			}
+		});
+		Ok(TelnetServer { shared })
+	}
+
+	/// Makes the given grid the one to be shown in all the sessions.
+	pub fn send_frame(&mut self, screen_grid: &ScreenGrid) {
+		if self.shared.session_count.load(Ordering::Relaxed) == 0 {
+			return;
+		}
+		let mut latest_frame = self.shared.latest_frame.lock().unwrap();
+		latest_frame.number += 1;
+		latest_frame.screen_grid = Some(Arc::new(screen_grid.snapshot()));
+		self.shared.new_frame.notify_all();
+	}
+
+	/// The keys pressed in the sessions since the last call, as key press events
+	/// (that the game handles like the ones from the window).
+	pub fn take_key_events(&mut self) -> Vec<Event> {
+		let key_combos = std::mem::take(&mut *self.shared.key_combos.lock().unwrap());
+		key_combos
+			.into_iter()
+			.map(KeyCombo::key_down_event)
+			.collect()
+	}
+}
+
+fn run_session(mut stream: TcpStream, shared: Arc<Shared>) {
+	shared.session_count.fetch_add(1, Ordering::Relaxed);
+	let is_over = Arc::new(AtomicBool::new(false));
+	let _ = stream.set_write_timeout(Some(SESSION_WRITE_TIMEOUT));
+
+	// Saying that the server will do the echoing (it won't) and that there won't be any
+	// go-ahead puts most telnet clients in character mode, where keys are sent right away.
+	let negotiation = [IAC, WILL, OPTION_ECHO, IAC, WILL, OPTION_SUPPRESS_GO_AHEAD];
+	let _ = stream.write_all(&negotiation);
+
+	if let Ok(input_stream) = stream.try_clone() {
+		let is_over = Arc::clone(&is_over);
+		let shared = Arc::clone(&shared);
+		std::thread::spawn(move || {
+			handle_session_input(input_stream, &shared);
+			is_over.store(true, Ordering::Relaxed);
+		});
+	} else {
+		is_over.store(true, Ordering::Relaxed);
+	}
+
+	let mut terminal = CrosstermTerminal::new(&stream, (0, 0));
+	// The latest frame copied, to be presented as the frames are shared by the sessions
+	// and each session has its own front buffer.
+	let mut session_grid = ScreenGrid::new((0, 0), (1, 1));
+	let mut previous_frame_number = 0;
+	while !is_over.load(Ordering::Relaxed) {
+		let screen_grid = {
+			let latest_frame = shared.latest_frame.lock().unwrap();
+			// Waking up regularly to notice when the session is over.
+			let (latest_frame, _) = shared
+				.new_frame
+				.wait_timeout_while(latest_frame, Duration::from_millis(100), |latest_frame| {
+					latest_frame.number == previous_frame_number
+				})
+				.unwrap();
+			if latest_frame.number == previous_frame_number {
+				continue;
+			}
+			previous_frame_number = latest_frame.number;
+			match &latest_frame.screen_grid {
+				Some(screen_grid) => Arc::clone(screen_grid),
+				None => continue,
+			}
+		};
+
+		if session_grid.grid_wh != screen_grid.grid_wh {
+			session_grid.resize_grid(screen_grid.grid_wh);
+			terminal.resize(screen_grid.grid_wh);
+		}
+		session_grid.tiles.clone_from(&screen_grid.tiles);
+		if session_grid.present(&mut terminal).is_err() {
+			break;
+		}
+	}
+
+	// Dropping the terminal shows the cursor again.
+	terminal.clear();
+	let _ = terminal.present();
+	drop(terminal);
+	let _ = stream.shutdown(std::net::Shutdown::Both);
+	shared.session_count.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Reads what the client sends until it wants to quit or is gone, giving the keys
+/// to the game.
+fn handle_session_input(mut stream: TcpStream, shared: &Shared) {
+	// The bytes of a key (like the escape sequence of an arrow key) come together,
+	// so an escape at the end of what was read is the escape key.
+	let mut buffer = [0; 256];
+	while let Ok(byte_count @ 1..) = stream.read(&mut buffer) {
+		let mut key_combos = Vec::new();
+		let is_quitting = !parse_input(&buffer[..byte_count], &mut key_combos);
+		shared.key_combos.lock().unwrap().extend(key_combos);
+		if is_quitting {
+			return;
+		}
+	}
+}
+
+/// Gets the keys in what the client sent, skipping the telnet commands (the client answers
+/// to the negotiation and may send more). Returns `false` if the client wants to quit.
+fn parse_input(bytes: &[u8], key_combos: &mut Vec<KeyCombo>) -> bool {
+	let key = |keycode| KeyCombo { keycode, ctrl: false, shift: false, alt: false };
+	let mut bytes = bytes.iter().copied().peekable();
+	while let Some(byte) = bytes.next() {
+		let key_combo = match byte {
+			IAC => {
+				match bytes.next() {
+					Some(WILL..=DONT) => {
+						bytes.next();
+					},
+					Some(SB) => {
+						let mut previous = 0;
+						for byte in bytes.by_ref() {
+							if previous == IAC && byte == SE {
+								break;
+							}
+							previous = byte;
+						}
+					},
+					_ => {},
+				}
+				continue;
+			},
+			3 | 4 => return false,
+			ESCAPE if matches!(bytes.peek(), Some(b'[' | b'O')) => {
+				bytes.next();
+				let keycode = match bytes.next() {
+					Some(b'A') => Keycode::Up,
+					Some(b'B') => Keycode::Down,
+					Some(b'C') => Keycode::Right,
+					Some(b'D') => Keycode::Left,
+					_ => continue,
+				};
+				key(keycode)
+			},
+			ESCAPE => key(Keycode::Escape),
+			b'\r' => key(Keycode::Return),
+			b'\t' => key(Keycode::Tab),
+			8 | 127 => key(Keycode::Backspace),
+			// What comes after a carriage return.
+			b'\n' | 0 => continue,
+			1..=26 => KeyCombo { ctrl: true, ..key(letter_keycode(byte + b'a' - 1)) },
+			b'A'..=b'Z' => KeyCombo {
+				shift: true,
+				..key(letter_keycode(byte.to_ascii_lowercase()))
+			},
+			b' '..=b'~' => match Keycode::from_i32(byte as i32) {
+				Some(keycode) => key(keycode),
+				None => continue,
+			},
+			_ => continue,
+		};
+		key_combos.push(key_combo);
+	}
+	true
+}
+
+/// The keycodes of letters are their lowercase ASCII code.
+fn letter_keycode(lowercase_letter: u8) -> Keycode {
+	Keycode::from_i32(lowercase_letter as i32).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn keys(bytes: &[u8]) -> Vec<KeyCombo> {
+		let mut key_combos = Vec::new();
+		assert!(parse_input(bytes, &mut key_combos));
+		key_combos
+	}
+
+	fn key(keycode: Keycode) -> KeyCombo {
+		KeyCombo { keycode, ctrl: false, shift: false, alt: false }
+	}
+
+	#[test]
+	fn keys_are_parsed() {
+		assert_eq!(
+			keys(b"kK\x13 \r\n"),
+			[
+				key(Keycode::K),
+				KeyCombo { shift: true, ..key(Keycode::K) },
+				KeyCombo { ctrl: true, ..key(Keycode::S) },
+				key(Keycode::Space),
+				key(Keycode::Return),
+			]
+		);
+	}
+
+	#[test]
+	fn escape_sequences_are_parsed() {
+		assert_eq!(
+			keys(b"\x1b[A\x1bOD\x1b"),
+			[key(Keycode::Up), key(Keycode::Left), key(Keycode::Escape)]
+		);
+	}
+
+	#[test]
+	fn telnet_commands_are_skipped() {
+		let bytes = [
+			IAC,
+			DONT,
+			OPTION_ECHO,
+			b'j',
+			IAC,
+			SB,
+			24,
+			0,
+			b'x',
+			IAC,
+			SE,
+			b'h',
+		];
+		assert_eq!(keys(&bytes), [key(Keycode::J), key(Keycode::H)]);
+	}
+
+	#[test]
+	fn ctrl_c_quits() {
+		let mut key_combos = Vec::new();
+		assert!(!parse_input(b"l\x03l", &mut key_combos));
+		assert_eq!(key_combos, [key(Keycode::L)]);
+	}
+}
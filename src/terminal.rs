@@ -0,0 +1,344 @@
+//! Where the screen grid can be shown, behind the `Terminal` trait: in the window of the
+//! `Engine` (the default), in a real terminal with crossterm (see `CrosstermTerminal`),
+//! or only in memory (see `HeadlessTerminal`), like to run a game in tests.
+//!
+//! Outside of the window there is no input, the game only runs (see `run_in_terminal`).
+
+use std::io::Write;
+use std::time::Instant;
+
+use crossterm::cursor::{Hide, Show};
+use crossterm::style::ResetColor;
+use crossterm::terminal::{Clear, ClearType};
+
+use crate::engine::{Game, TICK_DURATION};
+use sdl2::pixels::Color;
+
+use crate::{ansi, ScreenGrid, ScreenTile};
+
+/// Something that shows a grid of tiles.
+pub trait Terminal {
+	fn grid_wh(&self) -> (u32, u32);
+
+	/// Sets the tile to be shown at the next `present`, the coordinates being in the grid.
+	fn put(&mut self, xy: (u32, u32), tile: &ScreenTile);
+
+	/// Sets all the tiles to blank ones (see `ScreenTile::new`).
+	fn clear(&mut self);
+
+	/// Whether `clear` was called since the last `present`, in which case the tiles that
+	/// were put before are gone and have to be put again (see `ScreenGrid::present`).
+	fn was_cleared(&self) -> bool;
+
+	/// Shows the tiles as they were put.
+	fn present(&mut self) -> Result<(), String>;
+}
+
+impl ScreenGrid {
+	/// Puts the tiles of the grid in the terminal, the ones that do not fit being left out.
+	pub fn draw_to_terminal(&self, terminal: &mut impl Terminal) {
+		let terminal_wh = terminal.grid_wh();
+		for y in 0..self.grid_wh.1.min(terminal_wh.1) {
+			for x in 0..self.grid_wh.0.min(terminal_wh.0) {
+				terminal.put((x, y), self.tile((x, y)));
+			}
+		}
+	}
+}
+
+/// A terminal that supports 24-bit colors, like the standard output (see `stdout`),
+/// where the tiles that are put are written at the next `present`, so only the ones that
+/// changed should be (see `ScreenGrid::present`).
+pub struct CrosstermTerminal<W: Write> {
+	writer: W,
+	grid_wh: (u32, u32),
+	/// The escape sequences and characters to write at the next `present`.
+	pending_text: String,
+	/// Where the cursor and the colors will be after the pending text, if known.
+	cursor_xy: Option<(u32, u32)>,
+	current_colors: Option<(Color, Color)>,
+	is_cleared: bool,
+	/// Whether the screen was cleared and the cursor hidden, the cursor being shown again
+	/// when dropped.
+	is_set_up: bool,
+}
+
+impl CrosstermTerminal<std::io::Stdout> {
+	/// The standard output, with the size of the terminal it is in.
+	pub fn stdout() -> std::io::Result<CrosstermTerminal<std::io::Stdout>> {
+		let (w, h) = crossterm::terminal::size()?;
+		Ok(CrosstermTerminal::new(
+			std::io::stdout(),
+			(w as u32, h as u32),
+		))
+	}
+}
+
+impl<W: Write> CrosstermTerminal<W> {
+	pub fn new(writer: W, grid_wh: (u32, u32)) -> CrosstermTerminal<W> {
+		CrosstermTerminal {
+			writer,
+			grid_wh,
+			pending_text: String::new(),
+			cursor_xy: None,
+			current_colors: None,
+			is_cleared: false,
+			is_set_up: false,
+		}
+	}
+
+	/// Changes the size of the grid, and clears the terminal.
+	pub fn resize(&mut self, grid_wh: (u32, u32)) {
+		self.grid_wh = grid_wh;
+		self.clear();
+	}
+}
+
+impl<W: Write> Terminal for CrosstermTerminal<W> {
+	fn grid_wh(&self) -> (u32, u32) {
+		self.grid_wh
+	}
+
+	fn put(&mut self, xy: (u32, u32), tile: &ScreenTile) {
+		if self.cursor_xy != Some(xy) {
+			ansi::move_cursor(&mut self.pending_text, xy);
+		}
+		ansi::write_tile(&mut self.pending_text, tile, &mut self.current_colors);
+		self.cursor_xy = Some((xy.0 + 1, xy.1));
+	}
+
+	fn clear(&mut self) {
+		// What was put before is cleared anyway.
+		self.pending_text.clear();
+		ansi::write_command(&mut self.pending_text, ResetColor);
+		ansi::write_command(&mut self.pending_text, Clear(ClearType::All));
+		self.cursor_xy = None;
+		self.current_colors = None;
+		self.is_cleared = true;
+	}
+
+	fn was_cleared(&self) -> bool {
+		self.is_cleared
+	}
+
+	fn present(&mut self) -> Result<(), String> {
+		let mut text = std::mem::take(&mut self.pending_text);
+		if !self.is_set_up {
+			let mut setup = String::new();
+			ansi::write_command(&mut setup, Hide);
+			ansi::write_command(&mut setup, Clear(ClearType::All));
+			text.insert_str(0, &setup);
+			self.is_set_up = true;
+		}
+		if self.current_colors.is_some() {
+			ansi::write_command(&mut text, ResetColor);
+			self.current_colors = None;
+		}
+		self.writer
+			.write_all(text.as_bytes())
+			.and_then(|()| self.writer.flush())
+			.map_err(|error| error.to_string())?;
+		self.is_cleared = false;
+		Ok(())
+	}
+}
+
+impl<W: Write> Drop for CrosstermTerminal<W> {
+	fn drop(&mut self) {
+		if self.is_set_up {
+			let mut goodbye = String::new();
+			ansi::write_command(&mut goodbye, ResetColor);
+			ansi::write_command(&mut goodbye, Show);
+			goodbye.push_str("\r\n");
+			let _ = self.writer.write_all(goodbye.as_bytes());
+			let _ = self.writer.flush();
+		}
+	}
+}
+
+/// Shows nothing, the tiles are only kept, so that what would be shown can be checked.
+pub struct HeadlessTerminal {
+	pub screen_grid: ScreenGrid,
+	/// Where tiles were put, in order, to check which ones were (it is never emptied).
+	pub put_xys: Vec<(u32, u32)>,
+	/// How many times `present` was called.
+	pub present_count: u64,
+	is_cleared: bool,
+}
+
+impl HeadlessTerminal {
+	pub fn new(grid_wh: (u32, u32)) -> HeadlessTerminal {
+		HeadlessTerminal {
+			screen_grid: ScreenGrid::new(grid_wh, (1, 1)),
+			put_xys: Vec::new(),
+			present_count: 0,
+			is_cleared: false,
+		}
+	}
+}
+
+impl Terminal for HeadlessTerminal {
+	fn grid_wh(&self) -> (u32, u32) {
+		self.screen_grid.grid_wh
+	}
+
+	fn put(&mut self, xy: (u32, u32), tile: &ScreenTile) {
+		*self.screen_grid.tile_mut(xy) = *tile;
+		self.put_xys.push(xy);
+	}
+
+	fn clear(&mut self) {
+		self.screen_grid.clear();
+		self.is_cleared = true;
+	}
+
+	fn was_cleared(&self) -> bool {
+		self.is_cleared
+	}
+
+	fn present(&mut self) -> Result<(), String> {
+		self.present_count += 1;
+		self.is_cleared = false;
+		Ok(())
+	}
+}
+
+/// Runs the game and shows it in the terminal after each tick, until the game is over.
+/// With a tick limit, this many ticks at most are run as fast as possible (like to run
+/// a game headlessly), and without one the ticks happen at their normal rate.
+pub fn run_in_terminal(
+	game: &mut impl Game,
+	terminal: &mut impl Terminal,
+	tick_limit: Option<u64>,
+) -> Result<(), String> {
+	let mut screen_grid = ScreenGrid::new(terminal.grid_wh(), (1, 1));
+	let start = Instant::now();
+	let mut tick_number: u64 = 0;
+	while !game.is_over() && tick_limit.is_none_or(|tick_limit| tick_number < tick_limit) {
+		game.update(TICK_DURATION);
+		tick_number += 1;
+		screen_grid.clear();
+		game.draw(&mut screen_grid, 1.0);
+		screen_grid.present(terminal)?;
+		if tick_limit.is_none() {
+			let next_tick_time = start + TICK_DURATION * tick_number as u32;
+			std::thread::sleep(next_tick_time.saturating_duration_since(Instant::now()));
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Sprite;
+
+	fn tile(character: char) -> ScreenTile {
+		ScreenTile::from_char(character)
+	}
+
+	#[test]
+	fn present_puts_only_the_changed_tiles() {
+		let mut terminal = HeadlessTerminal::new((3, 2));
+		let mut screen_grid = ScreenGrid::new((3, 2), (1, 1));
+		*screen_grid.tile_mut((1, 0)) = tile('a');
+		screen_grid.present(&mut terminal).unwrap();
+		// Everything the first time.
+		assert_eq!(terminal.put_xys.len(), 6);
+		assert_eq!(terminal.screen_grid.tile((1, 0)).sprite, Sprite::Char('a'));
+
+		terminal.put_xys.clear();
+		screen_grid.present(&mut terminal).unwrap();
+		assert!(terminal.put_xys.is_empty());
+
+		*screen_grid.tile_mut((2, 1)) = tile('b');
+		*screen_grid.tile_mut((1, 0)) = tile('a');
+		screen_grid.present(&mut terminal).unwrap();
+		assert_eq!(terminal.put_xys, [(2, 1)]);
+		assert_eq!(terminal.screen_grid.tile((2, 1)).sprite, Sprite::Char('b'));
+		assert_eq!(terminal.present_count, 3);
+	}
+
+	#[test]
+	fn present_puts_everything_after_a_resize() {
+		let mut terminal = HeadlessTerminal::new((3, 2));
+		let mut screen_grid = ScreenGrid::new((3, 2), (1, 1));
+		screen_grid.present(&mut terminal).unwrap();
+		terminal.put_xys.clear();
+		screen_grid.resize_grid((2, 2));
+		screen_grid.present(&mut terminal).unwrap();
+		assert_eq!(terminal.put_xys.len(), 4);
+	}
+
+	#[test]
+	fn present_puts_everything_after_a_clear() {
+		let mut terminal = HeadlessTerminal::new((3, 2));
+		let mut screen_grid = ScreenGrid::new((3, 2), (1, 1));
+		*screen_grid.tile_mut((0, 0)) = tile('a');
+		screen_grid.present(&mut terminal).unwrap();
+		terminal.clear();
+		terminal.put_xys.clear();
+		screen_grid.present(&mut terminal).unwrap();
+		assert_eq!(terminal.put_xys.len(), 6);
+		assert_eq!(terminal.screen_grid.tile((0, 0)).sprite, Sprite::Char('a'));
+	}
+
+	#[test]
+	fn ansi_terminals_write_what_they_are_given() {
+		let mut terminal = CrosstermTerminal::new(Vec::new(), (3, 1));
+		terminal.put((1, 0), &tile('a'));
+		terminal.put((2, 0), &tile('b'));
+		terminal.present().unwrap();
+		let text = String::from_utf8(std::mem::take(&mut terminal.writer)).unwrap();
+		assert!(text.starts_with("\x1b[?25l"));
+		// One cursor move, then the two tiles one after the other.
+		assert_eq!(text.matches('H').count(), 1);
+		assert!(text.contains("\x1b[1;2H"));
+		assert!(text.contains("mab"));
+
+		terminal.present().unwrap();
+		assert!(terminal.writer.is_empty());
+	}
+
+	#[test]
+	fn tiles_out_of_the_terminal_are_left_out() {
+		let mut terminal = HeadlessTerminal::new((2, 1));
+		let mut screen_grid = ScreenGrid::new((3, 2), (1, 1));
+		screen_grid.present(&mut terminal).unwrap();
+		assert_eq!(terminal.put_xys, [(0, 0), (1, 0)]);
+	}
+
+	struct CountingGame {
+		tick_count: u32,
+	}
+
+	impl Game for CountingGame {
+		fn update(&mut self, _dt: std::time::Duration) {
+			self.tick_count += 1;
+		}
+
+		fn draw(&mut self, screen_grid: &mut ScreenGrid, _tick_progress: f32) {
+			let digit = char::from_digit(self.tick_count % 10, 10).unwrap();
+			*screen_grid.tile_mut((0, 0)) = tile(digit);
+		}
+
+		fn is_over(&self) -> bool {
+			self.tick_count >= 5
+		}
+	}
+
+	#[test]
+	fn games_run_headlessly() {
+		let mut terminal = HeadlessTerminal::new((4, 2));
+		let mut game = CountingGame { tick_count: 0 };
+		run_in_terminal(&mut game, &mut terminal, Some(3)).unwrap();
+		assert_eq!(game.tick_count, 3);
+		assert_eq!(terminal.present_count, 3);
+		assert_eq!(terminal.screen_grid.tile((0, 0)).sprite, Sprite::Char('3'));
+		// All the tiles at first, and then only the one that changes.
+		assert_eq!(terminal.put_xys.len(), 8 + 2);
+
+		run_in_terminal(&mut game, &mut terminal, Some(100)).unwrap();
+		assert_eq!(game.tick_count, 5);
+	}
+}
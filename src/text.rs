@@ -0,0 +1,297 @@
+//! Text with colors and styles (`RichText`), that turns into screen tiles.
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+use crate::render::{GlyphStyle, ScreenGrid, ScreenTile, Sprite};
+use crate::theme;
+
+#[derive(Clone)]
+pub enum RichTextModifier {
+	FgColor(Color),
+	BgColor(Color),
+	/// The color of that name in the theme that is current when the text is drawn
+	/// (see `theme::color`), so that the text follows the theme switches.
+	/// The text or background color of the theme if there is no such color.
+	FgThemeColor(String),
+	BgThemeColor(String),
+	GlyphStyle(GlyphStyle),
+}
+
+#[derive(Clone)]
+pub enum RichText {
+	Text(String),
+	Modifier(RichTextModifier, Box<RichText>),
+	Sequence(Vec<RichText>),
+}
+
+impl<T> From<T> for RichText
+where
+	T: Into<String>,
+{
+	fn from(string: T) -> Self {
+		RichText::Text(string.into())
+	}
+}
+
+impl RichText {
+	pub fn fg_color(self, color: Color) -> RichText {
+		RichText::Modifier(RichTextModifier::FgColor(color), Box::new(self))
+	}
+
+	pub fn bg_color(self, color: Color) -> RichText {
+		RichText::Modifier(RichTextModifier::BgColor(color), Box::new(self))
+	}
+
+	/// See `RichTextModifier::FgThemeColor`.
+	pub fn fg_theme_color(self, color_name: &str) -> RichText {
+		let modifier = RichTextModifier::FgThemeColor(color_name.to_string());
+		RichText::Modifier(modifier, Box::new(self))
+	}
+
+	pub fn bg_theme_color(self, color_name: &str) -> RichText {
+		let modifier = RichTextModifier::BgThemeColor(color_name.to_string());
+		RichText::Modifier(modifier, Box::new(self))
+	}
+
+	pub fn glyph_style(self, glyph_style: GlyphStyle) -> RichText {
+		RichText::Modifier(RichTextModifier::GlyphStyle(glyph_style), Box::new(self))
+	}
+}
+
+impl std::ops::Add<RichText> for RichText {
+	type Output = RichText;
+
+	fn add(self, rhs: RichText) -> RichText {
+		match self {
+			RichText::Sequence(mut vec) => RichText::Sequence({
+				vec.push(rhs);
+				vec
+			}),
+			lhs => RichText::Sequence(vec![lhs, rhs]),
+		}
+	}
+}
+
+impl std::ops::AddAssign<RichText> for RichText {
+	fn add_assign(&mut self, rhs: RichText) {
+		match self {
+			RichText::Sequence(ref mut vec) => vec.push(rhs),
+			ref lhs => {
+				*self = RichText::Sequence(vec![(*lhs).to_owned(), rhs]);
+			},
+		}
+	}
+}
+
+impl RichText {
+	/// The text without its colors and styles.
+	pub fn plain_text(&self) -> String {
+		match self {
+			RichText::Text(string) => string.clone(),
+			RichText::Modifier(_, sub_formatted_text) => sub_formatted_text.plain_text(),
+			RichText::Sequence(vec) => vec.iter().map(RichText::plain_text).collect(),
+		}
+	}
+
+	pub fn tiles(&self) -> Vec<ScreenTile> {
+		fn tiles_rec(
+			formatted_text: &RichText,
+			tiles: &mut Vec<ScreenTile>,
+			modifiers: &mut Vec<RichTextModifier>,
+		) {
+			match formatted_text {
+				RichText::Text(string) => {
+					tiles.append(
+						&mut string
+							.chars()
+							.map(|character| {
+								let mut tile = ScreenTile::from_char(character);
+								for modifier in modifiers.iter() {
+									match modifier {
+										RichTextModifier::BgColor(bg_color) => {
+											tile.bg_color = *bg_color
+										},
+										RichTextModifier::FgColor(fg_color) => {
+											tile.fg_color = *fg_color
+										},
+										RichTextModifier::FgThemeColor(color_name) => {
+											tile.fg_color = theme::color(color_name)
+												.unwrap_or_else(theme::text_color)
+										},
+										RichTextModifier::BgThemeColor(color_name) => {
+											tile.bg_color = theme::color(color_name)
+												.unwrap_or_else(theme::background_color)
+										},
+										RichTextModifier::GlyphStyle(glyph_style) => {
+											tile.glyph_style = *glyph_style
+										},
+									}
+								}
+								tile
+							})
+							.collect(),
+					);
+				},
+				RichText::Modifier(modifier, sub_formatted_text) => {
+					modifiers.push(modifier.clone());
+					tiles_rec(sub_formatted_text, tiles, modifiers);
+					modifiers.pop();
+				},
+				RichText::Sequence(vec) => {
+					for sub_formatted_text in vec.iter() {
+						tiles_rec(sub_formatted_text, tiles, modifiers);
+					}
+				},
+			}
+		}
+
+		let mut tiles = Vec::new();
+		let mut modifiers = Vec::new();
+		tiles_rec(self, &mut tiles, &mut modifiers);
+		tiles
+	}
+
+	/// The tiles of the text cut in lines of at most `width` tiles, breaking at the spaces
+	/// (that are dropped at the line breaks) and at the `\n`s. Words longer than a line
+	/// are cut wherever the line ends.
+	pub fn wrapped_lines(&self, width: u32) -> Vec<Vec<ScreenTile>> {
+		let width = width as usize;
+		if width == 0 {
+			return Vec::new();
+		}
+		let mut lines = Vec::new();
+		let mut line: Vec<ScreenTile> = Vec::new();
+		let mut word: Vec<ScreenTile> = Vec::new();
+		let place_word = |lines: &mut Vec<Vec<ScreenTile>>,
+		                  line: &mut Vec<ScreenTile>,
+		                  word: &mut Vec<ScreenTile>| {
+			if line.len() + word.len() > width && !line.is_empty() {
+				while line
+					.last()
+					.is_some_and(|tile| tile.sprite == Sprite::Char(' '))
+				{
+					line.pop();
+				}
+				lines.push(std::mem::take(line));
+			}
+			let mut word = std::mem::take(word).into_iter().peekable();
+			while word.peek().is_some() {
+				if line.len() == width {
+					lines.push(std::mem::take(line));
+				}
+				line.push(word.next().unwrap());
+			}
+		};
+		for tile in self.tiles() {
+			match tile.sprite {
+				Sprite::Char('\n') => {
+					place_word(&mut lines, &mut line, &mut word);
+					lines.push(std::mem::take(&mut line));
+				},
+				Sprite::Char(' ') => {
+					place_word(&mut lines, &mut line, &mut word);
+					// A space that does not fit is where the line breaks anyway.
+					if line.len() < width {
+						line.push(tile);
+					}
+				},
+				_ => word.push(tile),
+			}
+		}
+		place_word(&mut lines, &mut line, &mut word);
+		if !line.is_empty() || lines.is_empty() {
+			lines.push(line);
+		}
+		lines
+	}
+}
+
+impl ScreenGrid {
+	/// Draws the text in the rectangle, wrapping it in lines (see `RichText::wrapped_lines`)
+	/// and cutting off what does not fit. Returns the number of rows drawn, so that
+	/// paragraphs can be stacked.
+	pub fn draw_text_wrapped(&mut self, text: RichText, rect: Rect) -> u32 {
+		let lines = text.wrapped_lines(rect.width());
+		let row_count = (lines.len() as u32).min(rect.height());
+		for (y, line) in lines.iter().take(row_count as usize).enumerate() {
+			let y = rect.y() as u32 + y as u32;
+			if y >= self.grid_wh.1 {
+				break;
+			}
+			for (x, tile) in line.iter().enumerate() {
+				let x = rect.x() as u32 + x as u32;
+				if x < self.grid_wh.0 {
+					*self.tile_mut((x, y)) = *tile;
+				}
+			}
+		}
+		row_count
+	}
+
+	pub fn darw_text(&mut self, text: RichText, dst_xy: (u32, u32)) {
+		for (i, formatted_tile) in text.tiles().iter().enumerate() {
+			let tile = self.tile_mut((dst_xy.0 + i as u32, dst_xy.1));
+			*tile = *formatted_tile;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn wrapped(text: &str, width: u32) -> Vec<String> {
+		RichText::from(text)
+			.wrapped_lines(width)
+			.iter()
+			.map(|line| {
+				line.iter()
+					.map(|tile| match tile.sprite {
+						Sprite::Char(character) => character,
+						_ => '?',
+					})
+					.collect()
+			})
+			.collect()
+	}
+
+	#[test]
+	fn lines_break_at_spaces() {
+		assert_eq!(
+			wrapped("the quick brown fox", 10),
+			["the quick", "brown fox"]
+		);
+		assert_eq!(wrapped("the quick brown fox", 100), ["the quick brown fox"]);
+	}
+
+	#[test]
+	fn long_words_are_cut() {
+		assert_eq!(wrapped("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+		assert_eq!(wrapped("a abcdefghij", 4), ["a", "abcd", "efgh", "ij"]);
+	}
+
+	#[test]
+	fn newlines_break_lines() {
+		assert_eq!(wrapped("a\n\nb", 5), ["a", "", "b"]);
+	}
+
+	#[test]
+	fn degenerate_widths_and_texts() {
+		assert!(wrapped("abc", 0).is_empty());
+		assert_eq!(wrapped("", 5), [""]);
+	}
+
+	#[test]
+	fn modifiers_are_kept_on_the_wrapped_tiles() {
+		let text = RichText::Sequence(vec![
+			RichText::from("plain "),
+			RichText::from("red").fg_color(Color::RGB(255, 0, 0)),
+		]);
+		let lines = text.wrapped_lines(6);
+		assert_eq!(lines.len(), 2);
+		assert!(lines[1]
+			.iter()
+			.all(|tile| tile.fg_color == Color::RGB(255, 0, 0)));
+	}
+}
@@ -0,0 +1,178 @@
+//! Color themes, that give the colors used by default by everything that is drawn
+//! (the text, the background, the numbers, etc.) and that can be switched at any time.
+//!
+//! The themes are described in `assets/data/themes.toml`, with one table per theme
+//! mapping color names to `#rrggbb` colors. These names can also be used in the markup
+//! of the texts (see `markup`) and in `RichText` (see `RichText::fg_theme_color`),
+//! so that data files do not have to hardcode colors that would only look good in one
+//! theme, and so that the texts kept for a while (like in a log) follow the switches.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use sdl2::pixels::Color;
+
+use crate::assets;
+use crate::markup::parse_hex_color;
+
+/// The colors that every theme must have.
+const REQUIRED_COLOR_NAMES: [&str; 4] = ["text", "background", "number", "notice"];
+
+struct Theme {
+	name: String,
+	colors: HashMap<String, Color>,
+}
+
+struct Themes {
+	themes: Vec<Theme>,
+	current_index: usize,
+}
+
+static THEMES: RwLock<Option<Themes>> = RwLock::new(None);
+
+/// The theme that is the current one once the themes are loaded.
+const DEFAULT_THEME_NAME: &str = "default";
+
+/// Loads the themes, the one named `default` becoming the current one (or the first one
+/// if there is none of that name). The themes are in the alphabetical order of their names,
+/// as the tables of the file are.
+pub fn load_themes(asset_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+	let text = assets::read_to_string(asset_path)?;
+	*THEMES.write().unwrap() = Some(parse_themes(&text)?);
+	Ok(())
+}
+
+fn parse_themes(text: &str) -> Result<Themes, Box<dyn std::error::Error>> {
+	let table: toml::Table = text.parse()?;
+	let mut themes = Vec::new();
+	for (theme_name, colors_table) in table.iter() {
+		let colors_table = colors_table
+			.as_table()
+			.ok_or_else(|| format!("theme \"{theme_name}\" is not a table"))?;
+		let mut colors = HashMap::new();
+		for (color_name, color) in colors_table.iter() {
+			let color = color
+				.as_str()
+				.and_then(|color| color.strip_prefix('#'))
+				.and_then(parse_hex_color)
+				.ok_or_else(|| {
+					format!("color \"{theme_name}.{color_name}\" is not like \"#rrggbb\"")
+				})?;
+			colors.insert(color_name.clone(), color);
+		}
+		for required_color_name in REQUIRED_COLOR_NAMES {
+			if !colors.contains_key(required_color_name) {
+				return Err(format!(
+					"theme \"{theme_name}\" has no \"{required_color_name}\" color"
+				)
+				.into());
+			}
+		}
+		themes.push(Theme { name: theme_name.clone(), colors });
+	}
+	if themes.is_empty() {
+		return Err("there is no theme".into());
+	}
+	let current_index = themes
+		.iter()
+		.position(|theme| theme.name == DEFAULT_THEME_NAME)
+		.unwrap_or(0);
+	Ok(Themes { themes, current_index })
+}
+
+pub fn set_theme(theme_name: &str) -> Result<(), String> {
+	let mut themes = THEMES.write().unwrap();
+	let themes = themes.as_mut().ok_or("the themes are not loaded")?;
+	themes.current_index = themes
+		.themes
+		.iter()
+		.position(|theme| theme.name == theme_name)
+		.ok_or_else(|| format!("there is no theme \"{theme_name}\""))?;
+	Ok(())
+}
+
+/// Switches to the theme that comes after the current one, and returns its name.
+pub fn switch_to_next_theme() -> Option<String> {
+	let mut themes = THEMES.write().unwrap();
+	let themes = themes.as_mut()?;
+	themes.current_index = (themes.current_index + 1) % themes.themes.len();
+	Some(themes.themes[themes.current_index].name.clone())
+}
+
+/// The color of the given name in the current theme.
+pub fn color(color_name: &str) -> Option<Color> {
+	let themes = THEMES.read().unwrap();
+	let themes = themes.as_ref()?;
+	themes.themes[themes.current_index]
+		.colors
+		.get(color_name)
+		.copied()
+}
+
+/// Before the themes are loaded, the required colors are still there.
+fn required_color(color_name: &str, fallback: Color) -> Color {
+	color(color_name).unwrap_or(fallback)
+}
+
+pub fn text_color() -> Color {
+	required_color("text", Color::RGB(180, 220, 200))
+}
+
+pub fn background_color() -> Color {
+	required_color("background", Color::RGB(5, 30, 25))
+}
+
+pub fn number_color() -> Color {
+	required_color("number", Color::RGB(240, 200, 60))
+}
+
+/// The background of the notices shown over the game (like toasts).
+pub fn notice_color() -> Color {
+	required_color("notice", Color::RGB(20, 70, 60))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn theme_table(name: &str) -> String {
+		format!(
+			"[{name}]\ntext = \"#ffffff\"\nbackground = \"#000000\"\n\
+			number = \"#ffff00\"\nnotice = \"#00ff00\"\n"
+		)
+	}
+
+	#[test]
+	fn the_default_theme_is_the_current_one() {
+		let text = ["amber", "default", "ocean"].map(theme_table).concat();
+		let themes = parse_themes(&text).unwrap();
+		assert_eq!(themes.themes[themes.current_index].name, "default");
+	}
+
+	#[test]
+	fn without_a_default_theme_the_first_one_is_the_current_one() {
+		let text = ["ocean", "amber"].map(theme_table).concat();
+		let themes = parse_themes(&text).unwrap();
+		assert_eq!(themes.themes[themes.current_index].name, "amber");
+	}
+
+	#[test]
+	fn themes_missing_a_required_color_are_rejected() {
+		for required_color_name in REQUIRED_COLOR_NAMES {
+			let text = theme_table("default")
+				.lines()
+				.filter(|line| !line.starts_with(required_color_name))
+				.collect::<Vec<_>>()
+				.join("\n");
+			let error = parse_themes(&text).err().unwrap();
+			assert!(error.to_string().contains(required_color_name), "{error}");
+		}
+	}
+
+	#[test]
+	fn colors_must_be_like_rrggbb() {
+		let text = theme_table("default") + "crystal = \"#12345\"\n";
+		assert!(parse_themes(&text).is_err());
+		assert!(parse_themes("").is_err());
+	}
+}
@@ -0,0 +1,937 @@
+//! User interface made of widgets (labels, text fields, containers, etc.) that are kept
+//! in a tree from frame to frame, that draw themselves into the screen grid and that get
+//! the input events when they have the focus.
+//!
+//! Widgets work in grid coordinates (a `Rect` is a rectangle of tiles here).
+
+use std::collections::VecDeque;
+
+use sdl2::clipboard::ClipboardUtil;
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::rect::Rect;
+
+use crate::devlog::log;
+use crate::message::{self, MessageArg};
+use crate::{narration, theme, RichText, ScreenGrid, ScreenTile, Sprite};
+
+pub trait Widget {
+	/// The size (in tiles) that the widget would like to have, given the maximum size
+	/// that it can have.
+	fn measure(&self, max_wh: (u32, u32)) -> (u32, u32);
+
+	/// Places the widget (and its children) in the given rectangle.
+	fn layout(&mut self, rect: Rect);
+
+	fn draw(&self, screen_grid: &mut ScreenGrid);
+
+	/// Returns `true` if the event was used (and thus should not be used by anything else).
+	fn handle_event(&mut self, _event: &Event) -> bool {
+		false
+	}
+
+	fn is_focusable(&self) -> bool {
+		false
+	}
+
+	fn set_focused(&mut self, _is_focused: bool) {}
+
+	/// Whether the widget is for typing text, so that Escape takes the focus away from it
+	/// (instead of reaching whatever is bound to it, see `UiTree::handle_event`).
+	fn is_text_entry(&self) -> bool {
+		false
+	}
+
+	/// What the widget says, for the narration of the widget that gets the focus
+	/// (see `narration`).
+	fn narrated_text(&self) -> Option<String> {
+		None
+	}
+
+	/// The text to put in the clipboard when copying (Ctrl+C) while the widget has the focus.
+	fn copied_text(&self) -> Option<String> {
+		None
+	}
+
+	fn children(&self) -> &[Box<dyn Widget>] {
+		&[]
+	}
+
+	fn children_mut(&mut self) -> &mut [Box<dyn Widget>] {
+		&mut []
+	}
+}
+
+/// Draws the tiles in a row starting from the top left corner of the rectangle,
+/// cutting off what does not fit in the rectangle (or in the grid).
+fn draw_tiles_clipped(screen_grid: &mut ScreenGrid, tiles: &[ScreenTile], rect: Rect) {
+	if rect.height() == 0 || rect.y() as u32 >= screen_grid.grid_wh.1 {
+		return;
+	}
+	for (i, tile) in tiles.iter().take(rect.width() as usize).enumerate() {
+		let x = rect.x() as u32 + i as u32;
+		if x >= screen_grid.grid_wh.0 {
+			break;
+		}
+		*screen_grid.tile_mut((x, rect.y() as u32)) = *tile;
+	}
+}
+
+enum LabelText {
+	Fixed(RichText),
+	/// Key of a message (see `msg!`), formatted again each time it is drawn so that it
+	/// follows the game variables it uses.
+	Message(String),
+}
+
+/// One line of text.
+pub struct Label {
+	text: LabelText,
+	rect: Rect,
+}
+
+impl Label {
+	pub fn new(text: impl Into<RichText>) -> Label {
+		Label {
+			text: LabelText::Fixed(text.into()),
+			rect: Rect::new(0, 0, 0, 0),
+		}
+	}
+
+	pub fn message(key: &str) -> Label {
+		Label {
+			text: LabelText::Message(key.to_string()),
+			rect: Rect::new(0, 0, 0, 0),
+		}
+	}
+
+	fn tiles(&self) -> Vec<ScreenTile> {
+		match &self.text {
+			LabelText::Fixed(text) => text.tiles(),
+			LabelText::Message(key) => message::msg!(key).tiles(),
+		}
+	}
+}
+
+impl Widget for Label {
+	fn measure(&self, max_wh: (u32, u32)) -> (u32, u32) {
+		((self.tiles().len() as u32).min(max_wh.0), 1.min(max_wh.1))
+	}
+
+	fn layout(&mut self, rect: Rect) {
+		self.rect = rect;
+	}
+
+	fn draw(&self, screen_grid: &mut ScreenGrid) {
+		draw_tiles_clipped(screen_grid, &self.tiles(), self.rect);
+	}
+
+	fn narrated_text(&self) -> Option<String> {
+		Some(match &self.text {
+			LabelText::Fixed(text) => text.plain_text(),
+			LabelText::Message(key) => message::msg!(key).plain_text(),
+		})
+	}
+}
+
+/// Text wrapped on as many lines as it needs (see `RichText::wrapped_lines`).
+pub struct Paragraph {
+	text: LabelText,
+	rect: Rect,
+}
+
+impl Paragraph {
+	pub fn message(key: &str) -> Paragraph {
+		Paragraph {
+			text: LabelText::Message(key.to_string()),
+			rect: Rect::new(0, 0, 0, 0),
+		}
+	}
+
+	fn text(&self) -> RichText {
+		match &self.text {
+			LabelText::Fixed(text) => text.clone(),
+			LabelText::Message(key) => message::msg!(key),
+		}
+	}
+}
+
+impl Widget for Paragraph {
+	fn measure(&self, max_wh: (u32, u32)) -> (u32, u32) {
+		let lines = self.text().wrapped_lines(max_wh.0);
+		let w = lines
+			.iter()
+			.map(|line| line.len() as u32)
+			.max()
+			.unwrap_or(0);
+		(w, (lines.len() as u32).min(max_wh.1))
+	}
+
+	fn layout(&mut self, rect: Rect) {
+		self.rect = rect;
+	}
+
+	fn draw(&self, screen_grid: &mut ScreenGrid) {
+		screen_grid.draw_text_wrapped(self.text(), self.rect);
+	}
+
+	fn narrated_text(&self) -> Option<String> {
+		Some(self.text().plain_text())
+	}
+}
+
+/// Single line text input, that can set a game variable (see `message::set_variable`)
+/// to what is typed into it.
+pub struct TextField {
+	prompt: RichText,
+	text: String,
+	max_length: usize,
+	variable_name: Option<String>,
+	is_focused: bool,
+	rect: Rect,
+}
+
+impl TextField {
+	pub fn new(prompt: impl Into<RichText>, max_length: usize) -> TextField {
+		TextField {
+			prompt: prompt.into(),
+			text: String::new(),
+			max_length,
+			variable_name: None,
+			is_focused: false,
+			rect: Rect::new(0, 0, 0, 0),
+		}
+	}
+
+	/// Keeps the game variable of the given name set to the text of the field.
+	pub fn bound_to_variable(mut self, variable_name: &str) -> TextField {
+		message::set_variable(variable_name, self.text.as_str());
+		self.variable_name = Some(variable_name.to_string());
+		self
+	}
+
+	fn text_changed(&self) {
+		if let Some(variable_name) = &self.variable_name {
+			message::set_variable(variable_name, self.text.as_str());
+		}
+	}
+
+	fn tiles(&self) -> Vec<ScreenTile> {
+		let mut text = self.text.to_rich_text();
+		if self.is_focused {
+			text = (text + RichText::from("_")).fg_color(theme::number_color());
+		}
+		(self.prompt.clone() + text).tiles()
+	}
+}
+
+impl Widget for TextField {
+	fn measure(&self, max_wh: (u32, u32)) -> (u32, u32) {
+		let prompt_length = self.prompt.tiles().len();
+		// Room for the cursor after the longest possible text.
+		let length = (prompt_length + self.max_length + 1) as u32;
+		(length.min(max_wh.0), 1.min(max_wh.1))
+	}
+
+	fn layout(&mut self, rect: Rect) {
+		self.rect = rect;
+	}
+
+	fn draw(&self, screen_grid: &mut ScreenGrid) {
+		draw_tiles_clipped(screen_grid, &self.tiles(), self.rect);
+	}
+
+	fn handle_event(&mut self, event: &Event) -> bool {
+		match event {
+			Event::TextInput { text, .. } => {
+				for character in text.chars() {
+					if self.text.chars().count() < self.max_length && !character.is_control() {
+						self.text.push(character);
+					}
+				}
+				self.text_changed();
+				true
+			},
+			Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+				self.text.pop();
+				self.text_changed();
+				true
+			},
+			// The keys that type something come as text input events, but their key presses
+			// must not reach the keybindings either (typing a name should not make moves).
+			Event::KeyDown { keycode: Some(keycode), keymod, .. } => {
+				let is_ctrl_or_alt =
+					keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD | Mod::LALTMOD | Mod::RALTMOD);
+				let name = keycode.name();
+				let is_typing = *keycode == Keycode::Space
+					|| name.chars().count() == 1
+					|| (name.starts_with("Keypad ") && *keycode != Keycode::KpEnter);
+				is_typing && !is_ctrl_or_alt
+			},
+			_ => false,
+		}
+	}
+
+	fn is_focusable(&self) -> bool {
+		true
+	}
+
+	fn is_text_entry(&self) -> bool {
+		true
+	}
+
+	fn set_focused(&mut self, is_focused: bool) {
+		self.is_focused = is_focused;
+	}
+
+	fn narrated_text(&self) -> Option<String> {
+		Some(self.prompt.plain_text() + &self.text)
+	}
+
+	fn copied_text(&self) -> Option<String> {
+		Some(self.text.clone())
+	}
+}
+
+/// Container that stacks its children from top to bottom.
+#[derive(Default)]
+pub struct Column {
+	children: Vec<Box<dyn Widget>>,
+}
+
+impl Column {
+	pub fn new() -> Column {
+		Column { children: Vec::new() }
+	}
+
+	pub fn with(mut self, child: impl Widget + 'static) -> Column {
+		self.children.push(Box::new(child));
+		self
+	}
+}
+
+impl Widget for Column {
+	fn measure(&self, max_wh: (u32, u32)) -> (u32, u32) {
+		let mut wh = (0, 0);
+		for child in self.children.iter() {
+			let child_wh = child.measure((max_wh.0, max_wh.1.saturating_sub(wh.1)));
+			wh = (wh.0.max(child_wh.0), wh.1 + child_wh.1);
+		}
+		wh
+	}
+
+	fn layout(&mut self, rect: Rect) {
+		let mut y = 0;
+		for child in self.children.iter_mut() {
+			let child_wh = child.measure((rect.width(), rect.height().saturating_sub(y)));
+			child.layout(Rect::new(
+				rect.x(),
+				rect.y() + y as i32,
+				child_wh.0,
+				child_wh.1,
+			));
+			y += child_wh.1;
+		}
+	}
+
+	fn draw(&self, screen_grid: &mut ScreenGrid) {
+		for child in self.children.iter() {
+			child.draw(screen_grid);
+		}
+	}
+
+	fn children(&self) -> &[Box<dyn Widget>] {
+		&self.children
+	}
+
+	fn children_mut(&mut self) -> &mut [Box<dyn Widget>] {
+		&mut self.children
+	}
+}
+
+#[derive(Clone, Copy)]
+pub enum BorderStyle {
+	Single,
+	Double,
+}
+
+impl BorderStyle {
+	/// The CP437 box-drawing glyphs of the top left corner, the top right corner,
+	/// the bottom left corner, the bottom right corner, the horizontal lines
+	/// and the vertical lines.
+	fn glyph_indices(self) -> [u32; 6] {
+		match self {
+			BorderStyle::Single => [218, 191, 192, 217, 196, 179],
+			BorderStyle::Double => [201, 187, 200, 188, 205, 186],
+		}
+	}
+}
+
+/// Container that draws a border (with a title in it, if any) around its child,
+/// on a blank background.
+pub struct Panel {
+	title: Option<LabelText>,
+	border_style: BorderStyle,
+	/// Only one child, in a `Vec` to be given by `children`.
+	children: Vec<Box<dyn Widget>>,
+	rect: Rect,
+}
+
+impl Panel {
+	pub fn new(child: impl Widget + 'static) -> Panel {
+		Panel {
+			title: None,
+			border_style: BorderStyle::Single,
+			children: vec![Box::new(child)],
+			rect: Rect::new(0, 0, 0, 0),
+		}
+	}
+
+	pub fn title(mut self, title: impl Into<RichText>) -> Panel {
+		self.title = Some(LabelText::Fixed(title.into()));
+		self
+	}
+
+	/// The title is the message of the given key (see `Label::message`).
+	pub fn title_message(mut self, key: &str) -> Panel {
+		self.title = Some(LabelText::Message(key.to_string()));
+		self
+	}
+
+	pub fn border_style(mut self, border_style: BorderStyle) -> Panel {
+		self.border_style = border_style;
+		self
+	}
+}
+
+impl Widget for Panel {
+	fn measure(&self, max_wh: (u32, u32)) -> (u32, u32) {
+		let child_wh =
+			self.children[0].measure((max_wh.0.saturating_sub(2), max_wh.1.saturating_sub(2)));
+		(
+			(child_wh.0 + 2).min(max_wh.0),
+			(child_wh.1 + 2).min(max_wh.1),
+		)
+	}
+
+	fn layout(&mut self, rect: Rect) {
+		self.rect = rect;
+		self.children[0].layout(Rect::new(
+			rect.x() + 1,
+			rect.y() + 1,
+			rect.width().saturating_sub(2),
+			rect.height().saturating_sub(2),
+		));
+	}
+
+	fn draw(&self, screen_grid: &mut ScreenGrid) {
+		let (w, h) = (self.rect.width(), self.rect.height());
+		if w < 2 || h < 2 {
+			return;
+		}
+		let [top_left, top_right, bottom_left, bottom_right, horizontal, vertical] =
+			self.border_style.glyph_indices();
+		for dy in 0..h {
+			for dx in 0..w {
+				let xy = (self.rect.x() as u32 + dx, self.rect.y() as u32 + dy);
+				if xy.0 >= screen_grid.grid_wh.0 || xy.1 >= screen_grid.grid_wh.1 {
+					continue;
+				}
+				let glyph_index = match (dx, dy) {
+					(0, 0) => Some(top_left),
+					(dx, 0) if dx == w - 1 => Some(top_right),
+					(0, dy) if dy == h - 1 => Some(bottom_left),
+					(dx, dy) if dx == w - 1 && dy == h - 1 => Some(bottom_right),
+					(_, 0) => Some(horizontal),
+					(_, dy) if dy == h - 1 => Some(horizontal),
+					(0, _) => Some(vertical),
+					(dx, _) if dx == w - 1 => Some(vertical),
+					_ => None,
+				};
+				let mut tile = ScreenTile::new();
+				if let Some(glyph_index) = glyph_index {
+					tile.sprite = Sprite::Index(glyph_index);
+				}
+				*screen_grid.tile_mut(xy) = tile;
+			}
+		}
+		if let Some(title) = &self.title {
+			let title = match title {
+				LabelText::Fixed(text) => text.clone(),
+				LabelText::Message(key) => message::msg!(key),
+			};
+			let title = RichText::from(" ") + title + RichText::from(" ");
+			let title_rect = Rect::new(self.rect.x() + 2, self.rect.y(), w.saturating_sub(4), 1);
+			draw_tiles_clipped(screen_grid, &title.tiles(), title_rect);
+		}
+		self.children[0].draw(screen_grid);
+	}
+
+	fn children(&self) -> &[Box<dyn Widget>] {
+		&self.children
+	}
+
+	fn children_mut(&mut self) -> &mut [Box<dyn Widget>] {
+		&mut self.children
+	}
+}
+
+/// List of items, one per line, among which one is selected with Up and Down
+/// and chosen with Enter, which calls the choice handler with the index of the item.
+pub struct Menu {
+	items: Vec<LabelText>,
+	selected_index: usize,
+	choice_handler: Option<Box<dyn FnMut(usize)>>,
+	is_focused: bool,
+	rect: Rect,
+}
+
+impl Default for Menu {
+	fn default() -> Menu {
+		Menu::new()
+	}
+}
+
+impl Menu {
+	pub fn new() -> Menu {
+		Menu {
+			items: Vec::new(),
+			selected_index: 0,
+			choice_handler: None,
+			is_focused: false,
+			rect: Rect::new(0, 0, 0, 0),
+		}
+	}
+
+	pub fn item(mut self, text: impl Into<RichText>) -> Menu {
+		self.items.push(LabelText::Fixed(text.into()));
+		self
+	}
+
+	/// An item that is the message of the given key (see `Label::message`).
+	pub fn message_item(mut self, key: &str) -> Menu {
+		self.items.push(LabelText::Message(key.to_string()));
+		self
+	}
+
+	pub fn on_choice(mut self, choice_handler: impl FnMut(usize) + 'static) -> Menu {
+		self.choice_handler = Some(Box::new(choice_handler));
+		self
+	}
+
+	fn item_text(&self, index: usize) -> RichText {
+		match &self.items[index] {
+			LabelText::Fixed(text) => text.clone(),
+			LabelText::Message(key) => message::msg!(key),
+		}
+	}
+
+	fn select(&mut self, index: usize) {
+		self.selected_index = index;
+		narration::narrate("focus", &self.item_text(index).plain_text());
+	}
+}
+
+impl Widget for Menu {
+	fn measure(&self, max_wh: (u32, u32)) -> (u32, u32) {
+		// Room for the selection marker before the items.
+		let w = (0..self.items.len())
+			.map(|index| self.item_text(index).tiles().len() as u32 + 2)
+			.max()
+			.unwrap_or(0);
+		(w.min(max_wh.0), (self.items.len() as u32).min(max_wh.1))
+	}
+
+	fn layout(&mut self, rect: Rect) {
+		self.rect = rect;
+	}
+
+	fn draw(&self, screen_grid: &mut ScreenGrid) {
+		for index in 0..(self.items.len() as u32).min(self.rect.height()) {
+			let is_selected = index as usize == self.selected_index;
+			let mut text = self.item_text(index as usize);
+			text = if is_selected {
+				RichText::from("> ") + text
+			} else {
+				RichText::from("  ") + text
+			};
+			if is_selected && self.is_focused {
+				text = text
+					.fg_color(theme::background_color())
+					.bg_color(theme::text_color());
+			}
+			let item_rect = Rect::new(
+				self.rect.x(),
+				self.rect.y() + index as i32,
+				self.rect.width(),
+				1,
+			);
+			draw_tiles_clipped(screen_grid, &text.tiles(), item_rect);
+		}
+	}
+
+	fn handle_event(&mut self, event: &Event) -> bool {
+		if self.items.is_empty() {
+			return false;
+		}
+		match event {
+			Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+				self.select((self.selected_index + self.items.len() - 1) % self.items.len());
+				true
+			},
+			Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+				self.select((self.selected_index + 1) % self.items.len());
+				true
+			},
+			Event::KeyDown {
+				keycode: Some(Keycode::Return | Keycode::KpEnter),
+				repeat: false,
+				..
+			} => {
+				if let Some(choice_handler) = &mut self.choice_handler {
+					choice_handler(self.selected_index);
+				}
+				true
+			},
+			_ => false,
+		}
+	}
+
+	fn is_focusable(&self) -> bool {
+		true
+	}
+
+	fn set_focused(&mut self, is_focused: bool) {
+		self.is_focused = is_focused;
+	}
+
+	fn narrated_text(&self) -> Option<String> {
+		(!self.items.is_empty()).then(|| self.item_text(self.selected_index).plain_text())
+	}
+}
+
+/// Latest messages of the game (like "You mine the rock."), the newest one at the bottom,
+/// each wrapped on as many lines as it needs. The same message several times in a row
+/// is shown once with how many times it came (like "You mine the rock. x3").
+///
+/// PageUp and PageDown scroll through the older messages.
+pub struct MessageLog {
+	/// The messages and how many times in a row they came, the oldest first.
+	entries: VecDeque<(RichText, u32)>,
+	/// By how many lines the log is scrolled up from the newest message.
+	scroll: u32,
+	rect: Rect,
+}
+
+impl Default for MessageLog {
+	fn default() -> MessageLog {
+		MessageLog::new()
+	}
+}
+
+impl MessageLog {
+	/// The oldest messages are dropped past that many.
+	const MAX_ENTRIES: usize = 200;
+
+	pub fn new() -> MessageLog {
+		MessageLog {
+			entries: VecDeque::new(),
+			scroll: 0,
+			rect: Rect::new(0, 0, 0, 0),
+		}
+	}
+
+	pub fn push(&mut self, text: RichText) {
+		if let Some((last_text, count)) = self.entries.back_mut() {
+			if last_text.plain_text() == text.plain_text() {
+				*count += 1;
+				return;
+			}
+		}
+		narration::narrate("message", &text.plain_text());
+		self.entries.push_back((text, 1));
+		if self.entries.len() > MessageLog::MAX_ENTRIES {
+			self.entries.pop_front();
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+		self.scroll = 0;
+	}
+
+	/// The lines of all the messages, the oldest first.
+	fn lines(&self, width: u32) -> Vec<Vec<ScreenTile>> {
+		let mut lines = Vec::new();
+		for (text, count) in self.entries.iter() {
+			let text = if *count > 1 {
+				text.clone() + RichText::from(format!(" x{count}")).fg_color(theme::number_color())
+			} else {
+				text.clone()
+			};
+			lines.append(&mut text.wrapped_lines(width));
+		}
+		lines
+	}
+
+	fn max_scroll(&self) -> u32 {
+		let line_count = self.lines(self.rect.width()).len() as u32;
+		line_count.saturating_sub(self.rect.height())
+	}
+}
+
+impl Widget for MessageLog {
+	/// As big as it can be, as there may always be more messages.
+	fn measure(&self, max_wh: (u32, u32)) -> (u32, u32) {
+		max_wh
+	}
+
+	fn layout(&mut self, rect: Rect) {
+		self.rect = rect;
+		self.scroll = self.scroll.min(self.max_scroll());
+	}
+
+	fn draw(&self, screen_grid: &mut ScreenGrid) {
+		let lines = self.lines(self.rect.width());
+		let shown_line_count = (self.rect.height() as usize).min(lines.len());
+		let last_index = lines.len() - (self.scroll as usize).min(lines.len());
+		let first_index = last_index.saturating_sub(shown_line_count);
+		// The newest lines are at the bottom of the rectangle.
+		let top_y = self.rect.bottom() - (last_index - first_index) as i32;
+		for (y, line) in lines[first_index..last_index].iter().enumerate() {
+			let line_rect = Rect::new(self.rect.x(), top_y + y as i32, self.rect.width(), 1);
+			draw_tiles_clipped(screen_grid, line, line_rect);
+		}
+	}
+
+	fn handle_event(&mut self, event: &Event) -> bool {
+		let page = self.rect.height().saturating_sub(1).max(1);
+		match event {
+			Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => {
+				self.scroll = (self.scroll + page).min(self.max_scroll());
+				true
+			},
+			Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => {
+				self.scroll = self.scroll.saturating_sub(page);
+				true
+			},
+			_ => false,
+		}
+	}
+}
+
+/// The root of a tree of widgets, placed in a rectangle of the screen grid,
+/// which keeps track of which widget has the focus.
+///
+/// Tab and Shift+Tab move the focus between the focusable widgets, and the events
+/// go to the focused widget. Escape takes the focus away from a text entry.
+/// Ctrl+C copies the text of the focused widget (if it has some) and Ctrl+V pastes
+/// as if the clipboard text was typed.
+pub struct UiTree {
+	root: Box<dyn Widget>,
+	rect: Rect,
+	clipboard: ClipboardUtil,
+	/// Indices of the children to go through from the root to get to the focused widget.
+	focus_path: Option<Vec<usize>>,
+}
+
+impl UiTree {
+	pub fn new(root: impl Widget + 'static, rect: Rect, clipboard: ClipboardUtil) -> UiTree {
+		let mut ui_tree = UiTree {
+			root: Box::new(root),
+			rect,
+			clipboard,
+			focus_path: None,
+		};
+		ui_tree.move_focus(true);
+		ui_tree
+	}
+
+	pub fn set_rect(&mut self, rect: Rect) {
+		self.rect = rect;
+	}
+
+	pub fn draw(&mut self, screen_grid: &mut ScreenGrid) {
+		self.root.layout(self.rect);
+		self.root.draw(screen_grid);
+	}
+
+	/// Returns `true` if the event was used by the UI.
+	pub fn handle_event(&mut self, event: &Event) -> bool {
+		if let Event::KeyDown { keycode: Some(Keycode::Tab), keymod, .. } = event {
+			let is_backwards = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+			self.move_focus(!is_backwards);
+			return true;
+		}
+		if let Event::KeyDown { keycode: Some(Keycode::Escape), .. } = event {
+			if let Some(widget) = self
+				.focused_widget_mut()
+				.filter(|widget| widget.is_text_entry())
+			{
+				widget.set_focused(false);
+				self.focus_path = None;
+				return true;
+			}
+		}
+		if let Event::KeyDown { keycode: Some(keycode), keymod, window_id, .. } = event {
+			let is_ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+			if is_ctrl && *keycode == Keycode::C {
+				let copied_text = self
+					.focused_widget_mut()
+					.and_then(|widget| widget.copied_text());
+				if let Some(copied_text) = copied_text {
+					if let Err(error) = self.clipboard.set_clipboard_text(&copied_text) {
+						log!(Warning, "Could not copy to the clipboard: {error}");
+					}
+					return true;
+				}
+			} else if is_ctrl && *keycode == Keycode::V && self.clipboard.has_clipboard_text() {
+				let pasted_text = match self.clipboard.clipboard_text() {
+					Ok(text) => text,
+					Err(error) => {
+						log!(Warning, "Could not paste from the clipboard: {error}");
+						return true;
+					},
+				};
+				let paste_event = Event::TextInput {
+					timestamp: 0,
+					window_id: *window_id,
+					text: pasted_text,
+				};
+				return self.handle_event(&paste_event);
+			}
+		}
+		match self.focused_widget_mut() {
+			Some(widget) => widget.handle_event(event),
+			None => false,
+		}
+	}
+
+	fn focused_widget_mut(&mut self) -> Option<&mut dyn Widget> {
+		let mut widget = self.root.as_mut();
+		for &index in self.focus_path.as_ref()?.iter() {
+			widget = widget.children_mut()[index].as_mut();
+		}
+		Some(widget)
+	}
+
+	fn move_focus(&mut self, is_forward: bool) {
+		fn collect_focusable(
+			widget: &dyn Widget,
+			path: &mut Vec<usize>,
+			paths: &mut Vec<Vec<usize>>,
+		) {
+			if widget.is_focusable() {
+				paths.push(path.clone());
+			}
+			for (index, child) in widget.children().iter().enumerate() {
+				path.push(index);
+				collect_focusable(child.as_ref(), path, paths);
+				path.pop();
+			}
+		}
+		let mut paths = Vec::new();
+		collect_focusable(self.root.as_ref(), &mut Vec::new(), &mut paths);
+		if paths.is_empty() {
+			return;
+		}
+
+		let current_index = self
+			.focus_path
+			.as_ref()
+			.and_then(|focus_path| paths.iter().position(|path| path == focus_path));
+		let new_index = match current_index {
+			None => 0,
+			Some(index) if is_forward => (index + 1) % paths.len(),
+			Some(index) => (index + paths.len() - 1) % paths.len(),
+		};
+
+		if let Some(widget) = self.focused_widget_mut() {
+			widget.set_focused(false);
+		}
+		self.focus_path = Some(paths[new_index].clone());
+		if let Some(widget) = self.focused_widget_mut() {
+			widget.set_focused(true);
+			if let Some(text) = widget.narrated_text() {
+				narration::narrate("focus", &text);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn key_down(keycode: Keycode) -> Event {
+		Event::KeyDown {
+			timestamp: 0,
+			window_id: 0,
+			keycode: Some(keycode),
+			scancode: None,
+			keymod: sdl2::keyboard::Mod::NOMOD,
+			repeat: false,
+		}
+	}
+
+	fn entry_texts(log: &MessageLog) -> Vec<(String, u32)> {
+		log.entries
+			.iter()
+			.map(|(text, count)| (text.plain_text(), *count))
+			.collect()
+	}
+
+	#[test]
+	fn the_same_message_in_a_row_is_counted() {
+		let mut log = MessageLog::new();
+		for text in ["Bump.", "Bump.", "Hum.", "Bump."] {
+			log.push(RichText::from(text));
+		}
+		let expected = [("Bump.", 2), ("Hum.", 1), ("Bump.", 1)];
+		assert_eq!(
+			entry_texts(&log),
+			expected.map(|(text, count)| (text.to_string(), count))
+		);
+		// The count is on the same line.
+		assert_eq!(log.lines(80).len(), 3);
+	}
+
+	#[test]
+	fn the_oldest_messages_are_dropped() {
+		let mut log = MessageLog::new();
+		for i in 0..MessageLog::MAX_ENTRIES + 10 {
+			log.push(RichText::from(format!("Message {i}.")));
+		}
+		let texts = entry_texts(&log);
+		assert_eq!(texts.len(), MessageLog::MAX_ENTRIES);
+		assert_eq!(texts[0].0, "Message 10.");
+		assert_eq!(
+			texts.last().unwrap().0,
+			format!("Message {}.", MessageLog::MAX_ENTRIES + 9)
+		);
+	}
+
+	#[test]
+	fn scrolling_stays_within_the_messages() {
+		let mut log = MessageLog::new();
+		for i in 0..10 {
+			log.push(RichText::from(format!("Message {i}.")));
+		}
+		log.layout(Rect::new(0, 0, 20, 4));
+		assert_eq!(log.max_scroll(), 6);
+		for _ in 0..5 {
+			assert!(log.handle_event(&key_down(Keycode::PageUp)));
+		}
+		assert_eq!(log.scroll, 6);
+		log.handle_event(&key_down(Keycode::PageDown));
+		assert_eq!(log.scroll, 3);
+		// Getting more room scrolls down to keep no empty space above the oldest message.
+		log.layout(Rect::new(0, 0, 20, 8));
+		assert_eq!(log.scroll, 2);
+		for _ in 0..5 {
+			log.handle_event(&key_down(Keycode::PageDown));
+		}
+		assert_eq!(log.scroll, 0);
+		assert!(!log.handle_event(&key_down(Keycode::Home)));
+	}
+}
@@ -0,0 +1,277 @@
+//! The world as the game sees it (what is where), apart from how it is drawn.
+//!
+//! A `TileMap` of terrain can be bigger than the screen, and a `Camera` tells which part
+//! of it is shown where on the `ScreenGrid`.
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::render::{BgPattern, ScreenGrid, ScreenTile, Sprite};
+use crate::rng::Rng;
+use crate::theme;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Terrain {
+	Air,
+	Rock,
+	Crystal,
+	Water,
+}
+
+impl Terrain {
+	/// Can things walk (or float) through it.
+	pub fn is_passable(self) -> bool {
+		matches!(self, Terrain::Air | Terrain::Water)
+	}
+
+	/// How it is written in the rows of a saved map.
+	pub fn symbol(self) -> char {
+		match self {
+			Terrain::Air => '.',
+			Terrain::Rock => '#',
+			Terrain::Crystal => '*',
+			Terrain::Water => '~',
+		}
+	}
+
+	pub fn from_symbol(symbol: char) -> Option<Terrain> {
+		[
+			Terrain::Air,
+			Terrain::Rock,
+			Terrain::Crystal,
+			Terrain::Water,
+		]
+		.into_iter()
+		.find(|terrain| terrain.symbol() == symbol)
+	}
+
+	/// Can things be seen through it.
+	pub fn is_transparent(self) -> bool {
+		matches!(self, Terrain::Air | Terrain::Water)
+	}
+
+	pub fn screen_tile(self) -> ScreenTile {
+		let mut tile = ScreenTile::new();
+		match self {
+			Terrain::Air => {
+				tile.sprite = Sprite::Char('.');
+				tile.fg_color = Color::RGB(50, 70, 65);
+			},
+			Terrain::Rock => {
+				tile.sprite = Sprite::Char('#');
+				tile.fg_color = Color::RGB(120, 110, 100);
+				tile.bg_pattern = Some((BgPattern::DarkShade, Color::RGB(35, 32, 30)));
+			},
+			Terrain::Crystal => {
+				tile.sprite = Sprite::Index(4);
+				tile.fg_color = theme::color("crystal").unwrap_or_else(theme::text_color);
+			},
+			Terrain::Water => {
+				tile.sprite = Sprite::Char('~');
+				tile.fg_color = Color::RGB(60, 120, 230);
+				tile.bg_color = Color::RGB(10, 30, 80);
+			},
+		}
+		tile
+	}
+}
+
+/// A grid of terrain tiles, in the same order as `ScreenGrid::tiles`.
+///
+/// It is serialized as its rows (see `to_rows`).
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct TileMap {
+	terrain: Vec<Terrain>,
+	pub map_wh: (u32, u32),
+}
+
+impl TryFrom<Vec<String>> for TileMap {
+	type Error = String;
+
+	fn try_from(rows: Vec<String>) -> Result<TileMap, String> {
+		TileMap::from_rows(&rows)
+	}
+}
+
+impl From<TileMap> for Vec<String> {
+	fn from(map: TileMap) -> Vec<String> {
+		map.to_rows()
+	}
+}
+
+impl TileMap {
+	pub fn new(map_wh: (u32, u32), filling: Terrain) -> TileMap {
+		TileMap {
+			terrain: vec![filling; (map_wh.0 * map_wh.1) as usize],
+			map_wh,
+		}
+	}
+
+	/// A cave of rock dug by random tunnels, with some crystals in its walls and some
+	/// pools of water.
+	pub fn generate_cave(map_wh: (u32, u32), rng: &mut Rng) -> TileMap {
+		let mut map = TileMap::new(map_wh, Terrain::Rock);
+		if map_wh.0 < 3 || map_wh.1 < 3 {
+			return map;
+		}
+		let mut digger_xy = (map_wh.0 / 2, map_wh.1 / 2);
+		let dig_count = map_wh.0 * map_wh.1 / 2;
+		for _ in 0..dig_count {
+			*map.terrain_mut(digger_xy) = Terrain::Air;
+			digger_xy = match rng.below(4) {
+				0 => (digger_xy.0.saturating_sub(1).max(1), digger_xy.1),
+				1 => ((digger_xy.0 + 1).min(map_wh.0 - 2), digger_xy.1),
+				2 => (digger_xy.0, digger_xy.1.saturating_sub(1).max(1)),
+				_ => (digger_xy.0, (digger_xy.1 + 1).min(map_wh.1 - 2)),
+			};
+		}
+		for x in 1..(map_wh.0 - 1) {
+			for y in 1..(map_wh.1 - 1) {
+				let next_to_air = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+					.into_iter()
+					.any(|neighbor_xy| map.terrain(neighbor_xy) == Terrain::Air);
+				match map.terrain((x, y)) {
+					Terrain::Rock if next_to_air && rng.below(12) == 0 => {
+						*map.terrain_mut((x, y)) = Terrain::Crystal;
+					},
+					// Water gathers where there is rock below.
+					Terrain::Air
+						if map.terrain((x, y + 1)) == Terrain::Rock && rng.below(6) == 0 =>
+					{
+						*map.terrain_mut((x, y)) = Terrain::Water;
+					},
+					_ => {},
+				}
+			}
+		}
+		map
+	}
+
+	/// The map as lines of terrain symbols (see `Terrain::symbol`), from the top one down.
+	pub fn to_rows(&self) -> Vec<String> {
+		(0..self.map_wh.1)
+			.map(|y| {
+				(0..self.map_wh.0)
+					.map(|x| self.terrain((x, y)).symbol())
+					.collect()
+			})
+			.collect()
+	}
+
+	/// The map back from its rows (see `to_rows`).
+	pub fn from_rows(rows: &[String]) -> Result<TileMap, String> {
+		let map_w = rows.first().map_or(0, |row| row.chars().count());
+		let mut map = TileMap::new((map_w as u32, rows.len() as u32), Terrain::Rock);
+		for (y, row) in rows.iter().enumerate() {
+			if row.chars().count() != map_w {
+				return Err(format!("map row {y} is not as long as the first one"));
+			}
+			for (x, symbol) in row.chars().enumerate() {
+				*map.terrain_mut((x as u32, y as u32)) = Terrain::from_symbol(symbol)
+					.ok_or_else(|| format!("'{symbol}' in map row {y} is not a terrain"))?;
+			}
+		}
+		Ok(map)
+	}
+
+	pub fn contains(&self, xy: (i32, i32)) -> bool {
+		(0..self.map_wh.0 as i32).contains(&xy.0) && (0..self.map_wh.1 as i32).contains(&xy.1)
+	}
+
+	pub fn terrain(&self, xy: (u32, u32)) -> Terrain {
+		assert!(xy.0 < self.map_wh.0);
+		assert!(xy.1 < self.map_wh.1);
+		self.terrain[(xy.0 * self.map_wh.1 + xy.1) as usize]
+	}
+
+	pub fn terrain_mut(&mut self, xy: (u32, u32)) -> &mut Terrain {
+		assert!(xy.0 < self.map_wh.0);
+		assert!(xy.1 < self.map_wh.1);
+		&mut self.terrain[(xy.0 * self.map_wh.1 + xy.1) as usize]
+	}
+}
+
+/// Shows a window of the map in a rectangle of the screen grid.
+pub struct Camera {
+	/// The map coordinates of the tile shown at the top left corner of the view.
+	pub top_left_xy: (i32, i32),
+	/// Where the view is on the screen grid.
+	pub view_rect: Rect,
+}
+
+impl Camera {
+	pub fn new(view_rect: Rect) -> Camera {
+		Camera { top_left_xy: (0, 0), view_rect }
+	}
+
+	/// Moves the camera so that the map tile is (about) in the middle of the view,
+	/// without showing beyond the map edges if the map is big enough.
+	pub fn center_on(&mut self, map: &TileMap, xy: (i32, i32)) {
+		let axis = |center: i32, view_size: u32, map_size: u32| {
+			let top_left = center - view_size as i32 / 2;
+			if view_size >= map_size {
+				// The map is centered in the view instead.
+				-((view_size - map_size) as i32 / 2)
+			} else {
+				top_left.clamp(0, (map_size - view_size) as i32)
+			}
+		};
+		self.top_left_xy = (
+			axis(xy.0, self.view_rect.width(), map.map_wh.0),
+			axis(xy.1, self.view_rect.height(), map.map_wh.1),
+		);
+	}
+
+	/// Where the map tile is on the screen grid, if it is in the view.
+	pub fn map_xy_to_screen_xy(&self, xy: (i32, i32)) -> Option<(u32, u32)> {
+		let view_xy = (xy.0 - self.top_left_xy.0, xy.1 - self.top_left_xy.1);
+		let in_view = (0..self.view_rect.width() as i32).contains(&view_xy.0)
+			&& (0..self.view_rect.height() as i32).contains(&view_xy.1);
+		in_view.then(|| {
+			(
+				(self.view_rect.x() + view_xy.0) as u32,
+				(self.view_rect.y() + view_xy.1) as u32,
+			)
+		})
+	}
+
+	/// The map tile shown on the screen tile, even if it is beyond the map edges.
+	pub fn screen_xy_to_map_xy(&self, screen_xy: (u32, u32)) -> Option<(i32, i32)> {
+		self.view_rect
+			.contains_point((screen_xy.0 as i32, screen_xy.1 as i32))
+			.then(|| {
+				(
+					screen_xy.0 as i32 - self.view_rect.x() + self.top_left_xy.0,
+					screen_xy.1 as i32 - self.view_rect.y() + self.top_left_xy.1,
+				)
+			})
+	}
+
+	/// Draws the part of the map in view, the view tiles beyond the map edges being
+	/// left blank. The parts of the view that are out of the grid are cut off.
+	pub fn draw_map(&self, map: &TileMap, screen_grid: &mut ScreenGrid) {
+		for view_x in 0..self.view_rect.width() {
+			for view_y in 0..self.view_rect.height() {
+				let screen_xy = (
+					self.view_rect.x() as u32 + view_x,
+					self.view_rect.y() as u32 + view_y,
+				);
+				if screen_xy.0 >= screen_grid.grid_wh.0 || screen_xy.1 >= screen_grid.grid_wh.1 {
+					continue;
+				}
+				let map_xy = (
+					self.top_left_xy.0 + view_x as i32,
+					self.top_left_xy.1 + view_y as i32,
+				);
+				*screen_grid.tile_mut(screen_xy) = if map.contains(map_xy) {
+					map.terrain((map_xy.0 as u32, map_xy.1 as u32))
+						.screen_tile()
+				} else {
+					ScreenTile::new()
+				};
+			}
+		}
+	}
+}